@@ -0,0 +1,317 @@
+#[cfg(feature = "gui")]
+use ggez::graphics::Image;
+use mint::Point2;
+#[cfg(feature = "gui")]
+use ggez::{Context, GameResult};
+use serde::{Deserialize, Serialize};
+
+use crate::logic_gates::DEFAULT_GATE_DELAY;
+use crate::structure::*;
+use crate::error::SimError;
+
+const BLOCK_WIDTH: f32 = 60.0;
+const BLOCK_HEIGHT: f32 = 80.0;
+
+// Input pins (ioc 1)
+pub const A_PIN: usize = 1;
+pub const B_PIN: usize = 2;
+pub const CARRY_IN_PIN: usize = 3;
+
+// Output pins (ioc 0)
+pub const SUM_PIN: usize = 1;
+pub const CARRY_OUT_PIN: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AdderType {
+    Half,
+    Full,
+    RippleCarry,
+    CarryLookahead,
+}
+
+/// Half, full and N-bit adders. The operands A and B and the carry in are inputs (ioc 1), the sum and
+/// the carry out are outputs (ioc 0). The N-bit adders take buses, bit 0 is the least significant.
+///
+/// `delay` is the delay of a single gate level, the propagation delay of the adder depends on its model:
+/// - a half adder takes one level and a full adder two.
+/// - a ripple carry adder waits for the carry to ripple through the chain of full adders, two levels
+///   for every bit the carry actually travels through, so the delay depends on the operands.
+/// - a carry lookahead adder computes the carries with a tree of 4-bit lookahead blocks, its delay
+///   only depends on the width: two levels for the generate/propagate signals and the sum, and two
+///   for every level of the tree.
+#[derive(Debug, Clone)]
+pub struct Adder {
+    pub id: usize,
+    pub r#type: AdderType,
+    pub bits: usize,
+    pub input: Vec<Pin>,
+    pub output: Vec<Pin>,
+    pub position: Point2<f32>,
+    #[cfg(feature = "gui")]
+    pub image: Option<Image>,
+    pub hitbox: Hitbox,
+    pub ref_pin_pos: Point2<f32>,
+    pub delay: u64,     // Delay of a single gate level in abstract time units
+}
+
+impl Adder {
+    // `bits` is only used by the N-bit adders, half and full adders add single bits
+    pub fn new(r#type: AdderType, bits: usize) -> Self {
+        let bits = match r#type {
+            AdderType::Half | AdderType::Full => 1,
+            _ => bits.max(1),
+        };
+        let operand = || match r#type {
+            AdderType::Half | AdderType::Full => PinValue::Single(Signal::Undefined),
+            _ => PinValue::Multiple(vec![Signal::Undefined; bits]),
+        };
+        let single = || PinValue::Single(Signal::Undefined);
+
+        let inputs = if r#type == AdderType::Half { 2 } else { 3 };
+        let mut input = vec![Pin::new(A_PIN, 1, 0.0, 20.0, operand()), Pin::new(B_PIN, 1, 0.0, 40.0, operand())];
+        if inputs == 3 {
+            input.push(Pin::new(CARRY_IN_PIN, 1, 0.0, 60.0, single()));
+        }
+
+        Self {
+            id: 0,
+            r#type,
+            bits,
+            input,
+            output: vec![
+                Pin::new(SUM_PIN, 0, BLOCK_WIDTH, 20.0, operand()),
+                Pin::new(CARRY_OUT_PIN, 0, BLOCK_WIDTH, 60.0, single()),
+            ],
+            position: Point2 { x: 0.0, y: 0.0 },
+            #[cfg(feature = "gui")]
+            image: None,
+            hitbox: Hitbox {
+                rect: Rect { x: 0.0, y: 0.0, w: BLOCK_WIDTH, h: BLOCK_HEIGHT },
+                r#type: HitboxType::Component,
+            },
+            ref_pin_pos: Point2 { x: 0.0, y: 20.0 },
+            delay: DEFAULT_GATE_DELAY,
+        }
+    }
+
+    fn pins(&self) -> impl Iterator<Item = &Pin> {
+        self.input.iter().chain(&self.output)
+    }
+
+    fn pins_mut(&mut self) -> impl Iterator<Item = &mut Pin> {
+        self.input.iter_mut().chain(&mut self.output)
+    }
+
+    pub fn set_id(&mut self, id: usize) {
+        self.id = id;
+        set_pins_cid(self.pins_mut(), id);
+    }
+
+    pub fn get_pin(&mut self, pid: usize, ioc: usize) -> Result<&mut Pin, SimError> {
+        let id = self.id;
+        find_pin(self.pins_mut(), id, pid, ioc)
+    }
+
+    // Bits of an operand, None when it is undefined or doesn't have the width of the adder
+    fn operand(&self, pid: usize) -> Option<Vec<bool>> {
+        let signals = match &self.input.iter().find(|pin| pin.pid == pid)?.value {
+            PinValue::Single(signal) if self.bits == 1 => vec![*signal],
+            PinValue::Multiple(signals) if signals.len() == self.bits => signals.clone(),
+            _ => return None,
+        };
+        signals.iter()
+            .map(|signal| match signal {
+                Signal::On => Some(true),
+                Signal::Off => Some(false),
+                _ => None,
+            })
+            .collect()
+    }
+
+    // Operands and carry in, None if any of them is undefined. Half adders have no carry in
+    fn operands(&self) -> Option<(Vec<bool>, Vec<bool>, bool)> {
+        let carry_in = match self.r#type {
+            AdderType::Half => false,
+            _ => match self.input[2].value {
+                PinValue::Single(Signal::On) => true,
+                PinValue::Single(Signal::Off) => false,
+                _ => return None,
+            },
+        };
+        Some((self.operand(A_PIN)?, self.operand(B_PIN)?, carry_in))
+    }
+
+    pub fn get_output(&mut self) {
+        let signal = |bit: bool| if bit { Signal::On } else { Signal::Off };
+
+        let (sum, carry_out) = match self.operands() {
+            Some((a, b, carry_in)) => {
+                let mut carry = carry_in;
+                let sum: Vec<Signal> = a.iter().zip(&b)
+                    .map(|(&a, &b)| {
+                        let sum = a ^ b ^ carry;
+                        carry = (a && b) || (carry && (a ^ b));
+                        signal(sum)
+                    })
+                    .collect();
+                (sum, signal(carry))
+            }
+            None => (vec![Signal::Undefined; self.bits], Signal::Undefined),
+        };
+
+        self.output[0].value = match self.r#type {
+            AdderType::Half | AdderType::Full => PinValue::Single(sum[0]),
+            _ => PinValue::Multiple(sum),
+        };
+        self.output[1].value = PinValue::Single(carry_out);
+    }
+
+    // Longest run of bits the carry travels through with the current operands,
+    // every bit when they are undefined
+    fn carry_chain(&self) -> usize {
+        let Some((a, b, carry_in)) = self.operands() else { return self.bits };
+
+        let mut longest = 0;
+        let mut run = 0;
+        let mut carry = carry_in;
+        for (&a, &b) in a.iter().zip(&b) {
+            // A carry generated here starts a new chain, a propagated one makes the chain longer
+            run = if carry && (a ^ b) { run + 1 } else { 1 };
+            longest = longest.max(run);
+            carry = (a && b) || (carry && (a ^ b));
+        }
+        longest
+    }
+
+    // Time the outputs take to follow the inputs with the current operands
+    pub fn propagation_delay(&self) -> u64 {
+        let levels = match self.r#type {
+            AdderType::Half => 1,
+            AdderType::Full => 2,
+            AdderType::RippleCarry => 2 * self.carry_chain() as u64,
+            AdderType::CarryLookahead => {
+                // Number of levels of a tree of 4-bit lookahead blocks
+                let mut tree = 1;
+                let mut covered = 4;
+                while covered < self.bits {
+                    covered *= 4;
+                    tree += 1;
+                }
+                2 + 2 * tree
+            }
+        };
+        self.delay * levels
+    }
+
+    #[cfg(feature = "gui")]
+    pub fn load_adder_image(&mut self, ctx: &mut Context) -> GameResult<()> {
+        let path = match self.r#type {
+            AdderType::Half => "/adders/half_adder.png",
+            AdderType::Full => "/adders/full_adder.png",
+            AdderType::RippleCarry => "/adders/ripple_carry.png",
+            AdderType::CarryLookahead => "/adders/carry_lookahead.png",
+        };
+        self.image = Some(Image::from_path(ctx, path)?);
+        Ok(())
+    }
+
+    pub fn update_adder_position(&mut self, position: Point2<f32>) {
+        let (dx, dy) = move_component(&mut self.position, &mut self.hitbox, &mut self.ref_pin_pos, position);
+        move_pins(self.pins_mut(), dx, dy);
+    }
+
+    pub fn adder_pins_hitbox(&self) -> Vec<Hitbox> {
+        pins_hitbox(self.pins())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Signal::{Off, On, Undefined};
+
+    fn signal(bit: usize) -> Signal {
+        if bit == 1 { On } else { Off }
+    }
+
+    // Bits of a number, least significant first
+    fn bus(value: usize, bits: usize) -> PinValue {
+        PinValue::Multiple((0..bits).map(|bit| signal(value >> bit & 1)).collect())
+    }
+
+    fn set_inputs(adder: &mut Adder, a: PinValue, b: PinValue, carry_in: Signal) {
+        adder.get_pin(A_PIN, 1).unwrap().value = a;
+        adder.get_pin(B_PIN, 1).unwrap().value = b;
+        if let Ok(pin) = adder.get_pin(CARRY_IN_PIN, 1) {
+            pin.value = PinValue::Single(carry_in);
+        }
+        adder.get_output();
+    }
+
+    fn outputs(adder: &mut Adder) -> (PinValue, PinValue) {
+        (adder.get_pin(SUM_PIN, 0).unwrap().value.clone(), adder.get_pin(CARRY_OUT_PIN, 0).unwrap().value.clone())
+    }
+
+    #[test]
+    fn single_bit_adders_follow_their_truth_table() {
+        let mut half = Adder::new(AdderType::Half, 8);
+        assert_eq!((half.bits, half.input.len()), (1, 2));
+        let mut full = Adder::new(AdderType::Full, 8);
+        for row in 0..8 {
+            let (a, b, carry_in) = (row >> 2 & 1, row >> 1 & 1, row & 1);
+            set_inputs(&mut full, PinValue::Single(signal(a)), PinValue::Single(signal(b)), signal(carry_in));
+            let total = a + b + carry_in;
+            assert_eq!(outputs(&mut full), (PinValue::Single(signal(total & 1)), PinValue::Single(signal(total >> 1))));
+            if carry_in == 0 {
+                set_inputs(&mut half, PinValue::Single(signal(a)), PinValue::Single(signal(b)), Off);
+                assert_eq!(outputs(&mut half), (PinValue::Single(signal(total & 1)), PinValue::Single(signal(total >> 1))));
+            }
+        }
+    }
+
+    #[test]
+    fn n_bit_adders_add_their_buses() {
+        for r#type in [AdderType::RippleCarry, AdderType::CarryLookahead] {
+            let mut adder = Adder::new(r#type, 4);
+            for (a, b, carry_in) in [(5, 3, 0), (15, 1, 0), (9, 6, 1), (0, 0, 0)] {
+                set_inputs(&mut adder, bus(a, 4), bus(b, 4), signal(carry_in));
+                let total = a + b + carry_in;
+                assert_eq!(outputs(&mut adder), (bus(total & 15, 4), PinValue::Single(signal(total >> 4))));
+            }
+        }
+    }
+
+    #[test]
+    fn undefined_or_mismatched_operands_give_undefined_outputs() {
+        let mut adder = Adder::new(AdderType::RippleCarry, 4);
+        set_inputs(&mut adder, bus(5, 4), bus(3, 4), Undefined);
+        assert_eq!(outputs(&mut adder), (PinValue::Multiple(vec![Undefined; 4]), PinValue::Single(Undefined)));
+        set_inputs(&mut adder, bus(5, 3), bus(3, 4), Off);
+        assert_eq!(outputs(&mut adder).1, PinValue::Single(Undefined));
+    }
+
+    #[test]
+    fn ripple_carry_delays_follow_the_carry() {
+        let mut adder = Adder::new(AdderType::RippleCarry, 4);
+        set_inputs(&mut adder, bus(5, 4), bus(2, 4), Off);
+        assert_eq!(adder.propagation_delay(), 2 * DEFAULT_GATE_DELAY);
+        // The carry generated by bit 0 goes through every bit
+        set_inputs(&mut adder, bus(15, 4), bus(1, 4), Off);
+        assert_eq!(adder.propagation_delay(), 8 * DEFAULT_GATE_DELAY);
+        set_inputs(&mut adder, bus(15, 4), bus(1, 4), Undefined);
+        assert_eq!(adder.propagation_delay(), 8 * DEFAULT_GATE_DELAY);
+    }
+
+    #[test]
+    fn lookahead_delays_only_depend_on_the_width() {
+        let levels = |bits| {
+            let mut adder = Adder::new(AdderType::CarryLookahead, bits);
+            adder.delay = 1;
+            adder.propagation_delay()
+        };
+        assert_eq!((levels(4), levels(16), levels(17), levels(64)), (4, 6, 8, 8));
+        assert_eq!(Adder::new(AdderType::Half, 1).propagation_delay(), DEFAULT_GATE_DELAY);
+        assert_eq!(Adder::new(AdderType::Full, 1).propagation_delay(), 2 * DEFAULT_GATE_DELAY);
+    }
+}
@@ -0,0 +1,192 @@
+//! Command line runner: simulates a saved circuit without opening a window.
+//!
+//! ```text
+//! rusty-sim run circuit.json [NAME=VALUE ...] [--json]
+//! ```
+//!
+//! Every `NAME=VALUE` sets the source called `NAME` to `VALUE` (0 or 1), the circuit is simulated
+//! until it settles and the value of every led is printed, one `NAME VALUE` line per led or as a
+//! JSON document with `--json`. Sources and leds without a name are called `in_<id>` and `out_<id>`.
+//!
+//! ```text
+//! rusty-sim table circuit.json [--markdown]
+//! ```
+//!
+//! Prints the truth table of the circuit, as CSV or as a Markdown table with `--markdown`. The rows
+//! whose simulation failed have undefined outputs and their error is printed.
+//!
+//! ```text
+//! rusty-sim optimize circuit.json [--constant NAME ...] [--output optimized.json]
+//! ```
+//!
+//! Rebuilds a combinational circuit with fewer gates and prints the expression of every led before
+//! and after, the gate counts and whether both circuits have the same truth table. The sources given
+//! with `--constant` keep their saved value. `--output` saves the optimized circuit.
+//!
+//! The exit code is 0 when the simulation went fine, 1 when the command or the circuit file is
+//! wrong and 2 when the simulation found problems, the values are printed anyway by `run`. `optimize`
+//! also returns 2 when the optimized circuit doesn't behave like the original one.
+
+use std::process::ExitCode;
+
+use logic_simulator::circuit::Circuit;
+use logic_simulator::structure::Signal;
+
+const USAGE: &str = "usage: rusty-sim run <circuit.json> [NAME=VALUE ...] [--json]
+       rusty-sim table <circuit.json> [--markdown]
+       rusty-sim optimize <circuit.json> [--constant NAME ...] [--output <file.json>]";
+
+struct RunOptions {
+    path: String,
+    inputs: Vec<(String, Signal)>,
+    json: bool,
+}
+
+// Read the arguments of the `run` command
+fn parse_run(args: &[String]) -> Result<RunOptions, String> {
+    let mut path = None;
+    let mut inputs = Vec::new();
+    let mut json = false;
+    for arg in args {
+        if arg == "--json" {
+            json = true;
+        } else if let Some((name, value)) = arg.split_once('=') {
+            let value = match value {
+                "0" => Signal::Off,
+                "1" => Signal::On,
+                _ => return Err(format!("invalid value {} for {}, only 0 and 1 are valid", value, name)),
+            };
+            inputs.push((name.to_string(), value));
+        } else if path.is_none() {
+            path = Some(arg.clone());
+        } else {
+            return Err(format!("unexpected argument {}", arg));
+        }
+    }
+    let path = path.ok_or(format!("missing circuit file\n{}", USAGE))?;
+    Ok(RunOptions { path, inputs, json })
+}
+
+fn run(options: &RunOptions) -> Result<ExitCode, String> {
+    let mut circuit = Circuit::load(&options.path).map_err(|error| format!("{}: {}", options.path, error))?;
+    for (name, value) in &options.inputs {
+        let cid = circuit.find_source(name).ok_or(format!("no source called {}", name))?;
+        circuit.set_source_value(cid, *value);
+    }
+
+    // The problems found are printed with the values, the first one is also returned
+    let result = circuit.simulate();
+    let leds = circuit.led_values();
+    if options.json {
+        let document = serde_json::json!({
+            "leds": leds.iter()
+                .map(|(name, value)| serde_json::json!({ "name": name, "value": value.to_string() }))
+                .collect::<Vec<_>>(),
+            "errors": circuit.errors.iter().map(|error| error.to_string()).collect::<Vec<_>>(),
+        });
+        println!("{}", serde_json::to_string_pretty(&document).map_err(|error| error.to_string())?);
+    } else {
+        for (name, value) in &leds {
+            println!("{} {}", name, value);
+        }
+        for error in &circuit.errors {
+            eprintln!("error: {}", error);
+        }
+    }
+
+    Ok(match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(_) => ExitCode::from(2),
+    })
+}
+
+fn table(args: &[String]) -> Result<ExitCode, String> {
+    let (path, markdown) = match args {
+        [path] => (path, false),
+        [path, flag] if flag == "--markdown" => (path, true),
+        _ => return Err(USAGE.to_string()),
+    };
+    let circuit = Circuit::load(path).map_err(|error| format!("{}: {}", path, error))?;
+    let table = match circuit.truth_table() {
+        Ok(table) => table,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            return Ok(ExitCode::from(2));
+        }
+    };
+    if markdown {
+        print!("{}", table.to_markdown());
+    } else {
+        print!("{}", table.to_csv());
+    }
+
+    // The rows that failed are in the table with undefined outputs
+    let mut failed = false;
+    for (index, row) in table.rows.iter().enumerate() {
+        if let Some(error) = &row.error {
+            eprintln!("error: row {}: {}", index, error);
+            failed = true;
+        }
+    }
+    Ok(if failed { ExitCode::from(2) } else { ExitCode::SUCCESS })
+}
+
+fn optimize(args: &[String]) -> Result<ExitCode, String> {
+    let mut path = None;
+    let mut constants = Vec::new();
+    let mut output = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--constant" => constants.push(args.next().ok_or(USAGE.to_string())?.clone()),
+            "--output" => output = Some(args.next().ok_or(USAGE.to_string())?.clone()),
+            _ if path.is_none() => path = Some(arg.clone()),
+            _ => return Err(format!("unexpected argument {}", arg)),
+        }
+    }
+    let path = path.ok_or(format!("missing circuit file\n{}", USAGE))?;
+    let circuit = Circuit::load(&path).map_err(|error| format!("{}: {}", path, error))?;
+    let constants = constants.iter()
+        .map(|name| circuit.find_source(name).ok_or(format!("no source called {}", name)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let optimization = match circuit.optimize(&constants) {
+        Ok(optimization) => optimization,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            return Ok(ExitCode::from(2));
+        }
+    };
+    for output in &optimization.outputs {
+        println!("{} = {}", output.name, output.original);
+        println!("{} = {}", output.name, output.optimized);
+    }
+    println!("gates {} -> {}", optimization.original_gates, optimization.optimized_gates);
+    println!("equivalent {}", optimization.equivalent);
+    if let Some(output) = output {
+        optimization.circuit.save(&output).map_err(|error| format!("{}: {}", output, error))?;
+    }
+    Ok(if optimization.equivalent { ExitCode::SUCCESS } else { ExitCode::from(2) })
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.split_first() {
+        Some((command, args)) if command == "run" => parse_run(args).and_then(|options| run(&options)),
+        Some((command, args)) if command == "table" => table(args),
+        Some((command, args)) if command == "optimize" => optimize(args),
+        Some((command, _)) if command == "--help" || command == "-h" => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Some((command, _)) => Err(format!("unknown command {}\n{}", command, USAGE)),
+        None => Err(USAGE.to_string()),
+    };
+    match result {
+        Ok(code) => code,
+        Err(message) => {
+            eprintln!("{}", message);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,294 @@
+#[cfg(feature = "gui")]
+use ggez::graphics::Image;
+use mint::Point2;
+#[cfg(feature = "gui")]
+use ggez::{Context, GameResult};
+use serde::{Deserialize, Serialize};
+
+use crate::logic_gates::DEFAULT_GATE_DELAY;
+use crate::structure::*;
+use crate::error::SimError;
+
+const BLOCK_WIDTH: f32 = 60.0;
+const BLOCK_HEIGHT: f32 = 40.0;
+
+// Control pin (ioc 2) of the tri-state buffers
+pub const ENABLE_PIN: usize = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BufferType {
+    Buffer,
+    TriState,
+    OpenDrain,
+}
+
+/// Buffers copy their input (ioc 1) to their output (ioc 0), bit by bit for buses.
+/// - a tri-state buffer only drives its output while the enable pin (ioc 2) is high, otherwise
+///   the output is high impedance.
+/// - an open drain buffer only drives its output low, a high input leaves the output high impedance
+///   so it needs a pull-up resistor on the net.
+#[derive(Debug, Clone)]
+pub struct Buffer {
+    pub id: usize,
+    pub r#type: BufferType,
+    pub input: Pin,
+    pub control: Vec<Pin>,
+    pub output: Pin,
+    pub position: Point2<f32>,
+    #[cfg(feature = "gui")]
+    pub image: Option<Image>,
+    pub hitbox: Hitbox,
+    pub ref_pin_pos: Point2<f32>,
+    pub delay: u64,     // Propagation delay in abstract time units
+}
+
+impl Buffer {
+    pub fn new(r#type: BufferType, bits: usize) -> Self {
+        let data = || if bits > 1 {
+            PinValue::Multiple(vec![Signal::Undefined; bits])
+        } else {
+            PinValue::Single(Signal::Undefined)
+        };
+
+        // The enable pin is on the top side of tri-state buffers
+        let control = match r#type {
+            BufferType::TriState => vec![Pin::new(ENABLE_PIN, 2, BLOCK_WIDTH / 2.0, 0.0, PinValue::Single(Signal::Undefined))],
+            _ => Vec::new(),
+        };
+
+        Self {
+            id: 0,
+            r#type,
+            input: Pin::new(1, 1, 0.0, BLOCK_HEIGHT / 2.0, data()),
+            control,
+            output: Pin::new(1, 0, BLOCK_WIDTH, BLOCK_HEIGHT / 2.0, data()),
+            position: Point2 { x: 0.0, y: 0.0 },
+            #[cfg(feature = "gui")]
+            image: None,
+            hitbox: Hitbox {
+                rect: Rect { x: 0.0, y: 0.0, w: BLOCK_WIDTH, h: BLOCK_HEIGHT },
+                r#type: HitboxType::Component,
+            },
+            ref_pin_pos: Point2 { x: 0.0, y: BLOCK_HEIGHT / 2.0 },
+            delay: DEFAULT_GATE_DELAY,
+        }
+    }
+
+    fn pins(&self) -> impl Iterator<Item = &Pin> {
+        std::iter::once(&self.input).chain(&self.control).chain(std::iter::once(&self.output))
+    }
+
+    fn pins_mut(&mut self) -> impl Iterator<Item = &mut Pin> {
+        std::iter::once(&mut self.input).chain(&mut self.control).chain(std::iter::once(&mut self.output))
+    }
+
+    pub fn set_id(&mut self, id: usize) {
+        self.id = id;
+        set_pins_cid(self.pins_mut(), id);
+    }
+
+    pub fn get_pin(&mut self, pid: usize, ioc: usize) -> Result<&mut Pin, SimError> {
+        let id = self.id;
+        find_pin(self.pins_mut(), id, pid, ioc)
+    }
+
+    pub fn get_output(&mut self) {
+        // An input without the width of the output can't be buffered
+        let width = self.output.value.width();
+        if self.input.value.width() != width {
+            self.output.value = self.output.value.to_undefined();
+            return;
+        }
+
+        let enable = match self.control.first() {
+            Some(pin) => pin.value.bit(0).read(),
+            None => Signal::On,
+        };
+        let bit = |index: usize| {
+            let signal = self.input.value.bit(index).read();
+            match (self.r#type, enable) {
+                (_, Signal::Off) => Signal::HighZ,
+                (_, Signal::Undefined) => Signal::Undefined,
+                (BufferType::OpenDrain, Signal::On) if signal == Signal::On => Signal::HighZ,
+                _ => signal,
+            }
+        };
+        self.output.value = match self.output.value {
+            PinValue::Single(_) => PinValue::Single(bit(0)),
+            PinValue::Multiple(_) => PinValue::Multiple((0..width).map(bit).collect()),
+        };
+    }
+
+    #[cfg(feature = "gui")]
+    pub fn load_buffer_image(&mut self, ctx: &mut Context) -> GameResult<()> {
+        let path = match self.r#type {
+            BufferType::Buffer => "/buffers/buffer.png",
+            BufferType::TriState => "/buffers/tri_state.png",
+            BufferType::OpenDrain => "/buffers/open_drain.png",
+        };
+        self.image = Some(Image::from_path(ctx, path)?);
+        Ok(())
+    }
+
+    pub fn update_buffer_position(&mut self, position: Point2<f32>) {
+        let (dx, dy) = move_component(&mut self.position, &mut self.hitbox, &mut self.ref_pin_pos, position);
+        move_pins(self.pins_mut(), dx, dy);
+    }
+
+    pub fn buffer_pins_hitbox(&self) -> Vec<Hitbox> {
+        pins_hitbox(self.pins())
+    }
+}
+
+const RESISTOR_WIDTH: f32 = 20.0;
+const RESISTOR_HEIGHT: f32 = 40.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Pull {
+    Up,
+    Down,
+}
+
+/// Pull-up and pull-down resistors. Their only pin (ioc 0) weakly drives the net high or low:
+/// the value is only used for the bits that no other driver drives, so a net with only pull-ups is high.
+#[derive(Debug, Clone)]
+pub struct PullResistor {
+    pub id: usize,
+    pub pull: Pull,
+    pub output: Pin,
+    pub position: Point2<f32>,
+    #[cfg(feature = "gui")]
+    pub image: Option<Image>,
+    pub hitbox: Hitbox,
+    pub ref_pin_pos: Point2<f32>,
+    pub delay: u64,     // Propagation delay in abstract time units
+}
+
+impl PullResistor {
+    pub fn new(pull: Pull, bits: usize) -> Self {
+        let signal = match pull {
+            Pull::Up => Signal::On,
+            Pull::Down => Signal::Off,
+        };
+        let value = if bits > 1 {
+            PinValue::Multiple(vec![signal; bits])
+        } else {
+            PinValue::Single(signal)
+        };
+
+        // The pin is on the bottom side of pull-ups and on the top side of pull-downs
+        let y = match pull {
+            Pull::Up => RESISTOR_HEIGHT,
+            Pull::Down => 0.0,
+        };
+
+        Self {
+            id: 0,
+            pull,
+            output: Pin::new(1, 0, RESISTOR_WIDTH / 2.0, y, value),
+            position: Point2 { x: 0.0, y: 0.0 },
+            #[cfg(feature = "gui")]
+            image: None,
+            hitbox: Hitbox {
+                rect: Rect { x: 0.0, y: 0.0, w: RESISTOR_WIDTH, h: RESISTOR_HEIGHT },
+                r#type: HitboxType::Component,
+            },
+            ref_pin_pos: Point2 { x: RESISTOR_WIDTH / 2.0, y },
+            delay: 0,
+        }
+    }
+
+    pub fn set_id(&mut self, id: usize) {
+        self.id = id;
+        self.output.set_cid(id);
+    }
+
+    pub fn get_pin(&mut self, pid: usize, ioc: usize) -> Result<&mut Pin, SimError> {
+        let id = self.id;
+        find_pin(std::iter::once(&mut self.output), id, pid, ioc)
+    }
+
+    // The pulled value never changes
+    pub fn get_output(&mut self) {}
+
+    #[cfg(feature = "gui")]
+    pub fn load_resistor_image(&mut self, ctx: &mut Context) -> GameResult<()> {
+        let path = match self.pull {
+            Pull::Up => "/resistors/pull_up.png",
+            Pull::Down => "/resistors/pull_down.png",
+        };
+        self.image = Some(Image::from_path(ctx, path)?);
+        Ok(())
+    }
+
+    pub fn update_resistor_position(&mut self, position: Point2<f32>) {
+        let (dx, dy) = move_component(&mut self.position, &mut self.hitbox, &mut self.ref_pin_pos, position);
+        move_pins(std::iter::once(&mut self.output), dx, dy);
+    }
+
+    pub fn resistor_pins_hitbox(&self) -> Vec<Hitbox> {
+        vec![self.output.hitbox.clone()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Signal::{HighZ, Off, On, Undefined};
+
+    fn output(buffer: &mut Buffer, input: Signal, enable: Option<Signal>) -> PinValue {
+        buffer.input.value = PinValue::Single(input);
+        if let Some(enable) = enable {
+            buffer.control[0].value = PinValue::Single(enable);
+        }
+        buffer.get_output();
+        buffer.output.value.clone()
+    }
+
+    #[test]
+    fn buffers_copy_their_input() {
+        let mut buffer = Buffer::new(BufferType::Buffer, 1);
+        assert_eq!(output(&mut buffer, On, None), PinValue::Single(On));
+        // A floating input can't be read
+        assert_eq!(output(&mut buffer, HighZ, None), PinValue::Single(Undefined));
+    }
+
+    #[test]
+    fn tri_state_buffers_float_when_disabled() {
+        let mut buffer = Buffer::new(BufferType::TriState, 1);
+        assert_eq!(output(&mut buffer, On, Some(On)), PinValue::Single(On));
+        assert_eq!(output(&mut buffer, On, Some(Off)), PinValue::Single(HighZ));
+        assert_eq!(output(&mut buffer, On, Some(Undefined)), PinValue::Single(Undefined));
+    }
+
+    #[test]
+    fn open_drain_buffers_only_drive_low() {
+        let mut buffer = Buffer::new(BufferType::OpenDrain, 1);
+        assert_eq!(output(&mut buffer, Off, None), PinValue::Single(Off));
+        assert_eq!(output(&mut buffer, On, None), PinValue::Single(HighZ));
+    }
+
+    #[test]
+    fn bus_buffers_work_bit_by_bit() {
+        let mut buffer = Buffer::new(BufferType::OpenDrain, 3);
+        buffer.input.value = PinValue::Multiple(vec![On, Off, HighZ]);
+        buffer.get_output();
+        assert_eq!(buffer.output.value, PinValue::Multiple(vec![HighZ, Off, Undefined]));
+
+        buffer.input.value = PinValue::Multiple(vec![On, Off]);
+        buffer.get_output();
+        assert_eq!(buffer.output.value, PinValue::Multiple(vec![Undefined; 3]));
+    }
+
+    #[test]
+    fn resistors_pull_their_pin() {
+        assert_eq!(PullResistor::new(Pull::Up, 1).output.value, PinValue::Single(On));
+        assert_eq!(PullResistor::new(Pull::Down, 2).output.value, PinValue::Multiple(vec![Off; 2]));
+        let mut resistor = PullResistor::new(Pull::Up, 1);
+        resistor.set_id(5);
+        assert!(resistor.get_pin(1, 0).is_ok());
+        assert_eq!(resistor.get_pin(1, 1), Err(SimError::UnknownPin { pin: (5, 1, 1) }));
+    }
+}
@@ -1,46 +1,65 @@
+#[cfg(feature = "gui")]
 use ggez::graphics::Image;
-use ggez::mint::Point2;
-use ggez::{Context, graphics::Rect};
-use ggez::GameResult;
+#[cfg(feature = "gui")]
+use ggez::{Context, GameResult};
+use mint::Point2;
+use std::collections::BTreeMap;
 use multimap::MultiMap;
 
 use crate::logic_gates::*;
 use crate::source::*;
 use crate::structure::*;
 use crate::led::*;
+use crate::custom::{CustomBehavior, CustomComponent};
+use crate::subcircuit::SubCircuit;
+use crate::clock::Clock;
+use crate::flip_flop::FlipFlop;
+use crate::latch_register::LatchRegister;
+use crate::multiplexer::{Demultiplexer, Multiplexer};
+use crate::adder::Adder;
+use crate::shift_register::ShiftRegister;
+use crate::buffer::{Buffer, PullResistor};
 use crate::connection_logic::{detect_collisions, group_connected_pins};
+use crate::netlist::{Netlist, PinId};
+use crate::error::SimError;
+use crate::simulation::{Event, EventQueue, MAX_EVALUATIONS, SETTLE_TIME_LIMIT};
 
 #[derive(Debug)]
 pub enum LogicElements {
     Gates(LogicGate),
     Source(Source),
     Leds(Led),
-    Clock,
-    Adders,
-    Multiplexers,
-    Demultiplexers,
-    ShiftRegisters,
-    FlipFlops,
-    LatchRegisters,
+    Custom(CustomComponent),
+    SubCircuit(SubCircuit),
+    Clock(Clock),
+    Adders(Adder),
+    Multiplexers(Multiplexer),
+    Demultiplexers(Demultiplexer),
+    ShiftRegisters(ShiftRegister),
+    FlipFlops(FlipFlop),
+    LatchRegisters(LatchRegister),
+    Buffers(Buffer),
+    Resistors(PullResistor),
 }
 
 impl LogicElements {
-    pub fn set_input(&mut self, signal: Vec<Signal>) {
+    pub fn set_input(&mut self, signal: Vec<Signal>) -> Result<(), SimError> {
         match self {
             // Handle the case when it's a single input gate
-            LogicElements::Gates(logic_gate) => {
-                logic_gate.set_input(signal);  // Call the method for a single input gate
-            }
+            LogicElements::Gates(logic_gate) => logic_gate.set_input(signal),
 
-            // Handle cases where the logic element is a flip flop or latch register
-            LogicElements::FlipFlops | LogicElements::LatchRegisters => {
-                todo!()
+            // Flip flops, latches and registers take the values of their data inputs
+            LogicElements::FlipFlops(flip_flop) => {
+                flip_flop.set_input(signal);
+                Ok(())
             }
-
-            // Fallback case: for any other variant of LogicElements
-            _ => {
-                panic!("set_input is not supported for this LogicElement variant");
+            LogicElements::LatchRegisters(latch_register) => {
+                latch_register.set_input(signal);
+                Ok(())
             }
+
+            // Fallback case: the other variants of LogicElements have no inputs to set
+            _ => Err(SimError::TooManyInputs { cid: self.get_id(), expected: 0, found: signal.len() }),
         }
     }
 
@@ -50,25 +69,75 @@ impl LogicElements {
             LogicElements::Gates(logic_gate) => logic_gate.get_output(),
             LogicElements::Source(_) => (),
             LogicElements::Leds(_) => (),
-            _ => todo!(),
+            LogicElements::Custom(custom) => custom.get_output(),
+            LogicElements::SubCircuit(subcircuit) => subcircuit.get_output(),
+            LogicElements::Clock(_) => (),
+            LogicElements::FlipFlops(flip_flop) => flip_flop.get_output(),
+            LogicElements::Resistors(resistor) => resistor.get_output(),
+            LogicElements::Buffers(buffer) => buffer.get_output(),
+            LogicElements::ShiftRegisters(shift_register) => shift_register.get_output(),
+            LogicElements::Adders(adder) => adder.get_output(),
+            LogicElements::Demultiplexers(demultiplexer) => demultiplexer.get_output(),
+            LogicElements::Multiplexers(multiplexer) => multiplexer.get_output(),
+            LogicElements::LatchRegisters(latch_register) => latch_register.get_output(),
         }
     }
 
-    pub fn get_pin(&mut self, pid:usize, ioc:usize) -> &mut Pin{
+    pub fn get_pin(&mut self, pid:usize, ioc:usize) -> Result<&mut Pin, SimError> {
         match self {
             LogicElements::Gates(logic_gate) => logic_gate.get_pin(pid, ioc),
             LogicElements::Source(source) => source.get_pin(pid, ioc),
             LogicElements::Leds(led) => led.get_pin(pid, ioc),
-            _ => todo!("rest of the componens"),
+            LogicElements::Custom(custom) => custom.get_pin(pid, ioc),
+            LogicElements::SubCircuit(subcircuit) => subcircuit.get_pin(pid, ioc),
+            LogicElements::Clock(clock) => clock.get_pin(pid, ioc),
+            LogicElements::FlipFlops(flip_flop) => flip_flop.get_pin(pid, ioc),
+            LogicElements::Resistors(resistor) => resistor.get_pin(pid, ioc),
+            LogicElements::Buffers(buffer) => buffer.get_pin(pid, ioc),
+            LogicElements::ShiftRegisters(shift_register) => shift_register.get_pin(pid, ioc),
+            LogicElements::Adders(adder) => adder.get_pin(pid, ioc),
+            LogicElements::Demultiplexers(demultiplexer) => demultiplexer.get_pin(pid, ioc),
+            LogicElements::Multiplexers(multiplexer) => multiplexer.get_pin(pid, ioc),
+            LogicElements::LatchRegisters(latch_register) => latch_register.get_pin(pid, ioc),
+        }
+    }
+
+    pub fn set_id(&mut self, id: usize) {
+        match self {
+            LogicElements::Gates(logic_gate) => logic_gate.set_gate_id(id),
+            LogicElements::Source(source) => source.set_id(id),
+            LogicElements::Leds(led) => led.set_id(id),
+            LogicElements::Custom(custom) => custom.set_id(id),
+            LogicElements::SubCircuit(subcircuit) => subcircuit.set_id(id),
+            LogicElements::Clock(clock) => clock.set_id(id),
+            LogicElements::FlipFlops(flip_flop) => flip_flop.set_id(id),
+            LogicElements::Resistors(resistor) => resistor.set_id(id),
+            LogicElements::Buffers(buffer) => buffer.set_id(id),
+            LogicElements::ShiftRegisters(shift_register) => shift_register.set_id(id),
+            LogicElements::Adders(adder) => adder.set_id(id),
+            LogicElements::Demultiplexers(demultiplexer) => demultiplexer.set_id(id),
+            LogicElements::Multiplexers(multiplexer) => multiplexer.set_id(id),
+            LogicElements::LatchRegisters(latch_register) => latch_register.set_id(id),
         }
     }
 
+    #[cfg(feature = "gui")]
     pub fn load_image(&mut self, ctx: &mut Context) -> GameResult<()>{
         match self {
             LogicElements::Gates(logic_gate) => logic_gate.load_gate_image(ctx),
             LogicElements::Source(source) => source.load_source_image(ctx),
             LogicElements::Leds(led) => led.load_led_image(ctx),
-            _ => todo!(),
+            LogicElements::Custom(custom) => custom.load_custom_image(ctx),
+            LogicElements::SubCircuit(subcircuit) => subcircuit.circuit.load_images(ctx),
+            LogicElements::Clock(clock) => clock.load_clock_image(ctx),
+            LogicElements::FlipFlops(flip_flop) => flip_flop.load_flip_flop_image(ctx),
+            LogicElements::Resistors(resistor) => resistor.load_resistor_image(ctx),
+            LogicElements::Buffers(buffer) => buffer.load_buffer_image(ctx),
+            LogicElements::ShiftRegisters(shift_register) => shift_register.load_shift_register_image(ctx),
+            LogicElements::Adders(adder) => adder.load_adder_image(ctx),
+            LogicElements::Demultiplexers(demultiplexer) => demultiplexer.load_demultiplexer_image(ctx),
+            LogicElements::Multiplexers(multiplexer) => multiplexer.load_multiplexer_image(ctx),
+            LogicElements::LatchRegisters(latch_register) => latch_register.load_latch_register_image(ctx),
         }
     }
 
@@ -77,7 +146,17 @@ impl LogicElements {
             LogicElements::Gates(logic_gate) => logic_gate.hitbox.rect,
             LogicElements::Source(source) => source.hitbox.rect,
             LogicElements::Leds(led) => led.hitbox.rect,
-            _ => todo!()
+            LogicElements::Custom(custom) => custom.hitbox.rect,
+            LogicElements::SubCircuit(subcircuit) => subcircuit.hitbox.rect,
+            LogicElements::Clock(clock) => clock.hitbox.rect,
+            LogicElements::FlipFlops(flip_flop) => flip_flop.hitbox.rect,
+            LogicElements::Resistors(resistor) => resistor.hitbox.rect,
+            LogicElements::Buffers(buffer) => buffer.hitbox.rect,
+            LogicElements::ShiftRegisters(shift_register) => shift_register.hitbox.rect,
+            LogicElements::Adders(adder) => adder.hitbox.rect,
+            LogicElements::Demultiplexers(demultiplexer) => demultiplexer.hitbox.rect,
+            LogicElements::Multiplexers(multiplexer) => multiplexer.hitbox.rect,
+            LogicElements::LatchRegisters(latch_register) => latch_register.hitbox.rect,
         }
     }
 
@@ -86,7 +165,17 @@ impl LogicElements {
             LogicElements::Gates(logic_gate) => logic_gate.gate_pins_hitbox(),
             LogicElements::Source(source) => source.source_pin_hitbox(),
             LogicElements::Leds(led) => led.led_pin_hitbox(),
-            _ => todo!()
+            LogicElements::Custom(custom) => custom.custom_pins_hitbox(),
+            LogicElements::SubCircuit(subcircuit) => subcircuit.subcircuit_pins_hitbox(),
+            LogicElements::Clock(clock) => clock.clock_pin_hitbox(),
+            LogicElements::FlipFlops(flip_flop) => flip_flop.flip_flop_pins_hitbox(),
+            LogicElements::Resistors(resistor) => resistor.resistor_pins_hitbox(),
+            LogicElements::Buffers(buffer) => buffer.buffer_pins_hitbox(),
+            LogicElements::ShiftRegisters(shift_register) => shift_register.shift_register_pins_hitbox(),
+            LogicElements::Adders(adder) => adder.adder_pins_hitbox(),
+            LogicElements::Demultiplexers(demultiplexer) => demultiplexer.demultiplexer_pins_hitbox(),
+            LogicElements::Multiplexers(multiplexer) => multiplexer.multiplexer_pins_hitbox(),
+            LogicElements::LatchRegisters(latch_register) => latch_register.latch_register_pins_hitbox(),
         }
     }
 
@@ -95,16 +184,120 @@ impl LogicElements {
             LogicElements::Gates(logic_gate) => logic_gate.update_gate_position(new_position),
             LogicElements::Source(source) => source.update_source_position(new_position),
             LogicElements::Leds(led) => led.update_led_position(new_position),
-            _ => todo!(),
+            LogicElements::Custom(custom) => custom.update_custom_position(new_position),
+            LogicElements::SubCircuit(subcircuit) => subcircuit.update_subcircuit_position(new_position),
+            LogicElements::Clock(clock) => clock.update_clock_position(new_position),
+            LogicElements::FlipFlops(flip_flop) => flip_flop.update_flip_flop_position(new_position),
+            LogicElements::Resistors(resistor) => resistor.update_resistor_position(new_position),
+            LogicElements::Buffers(buffer) => buffer.update_buffer_position(new_position),
+            LogicElements::ShiftRegisters(shift_register) => shift_register.update_shift_register_position(new_position),
+            LogicElements::Adders(adder) => adder.update_adder_position(new_position),
+            LogicElements::Demultiplexers(demultiplexer) => demultiplexer.update_demultiplexer_position(new_position),
+            LogicElements::Multiplexers(multiplexer) => multiplexer.update_multiplexer_position(new_position),
+            LogicElements::LatchRegisters(latch_register) => latch_register.update_latch_register_position(new_position),
         }
     }
 
+    #[cfg(feature = "gui")]
     pub fn get_image(&self, ctx: &mut Context) -> Option<Image>{
         match self {
             LogicElements::Gates(logic_gate) => logic_gate.image.clone(),
             LogicElements::Source(source) => source.image.clone(),
             LogicElements::Leds(led) => led.clone().update_led_image(ctx),
-            _ => todo!(),
+            LogicElements::Custom(custom) => custom.image.clone(),
+            LogicElements::SubCircuit(_) => None,
+            LogicElements::Clock(clock) => clock.image.clone(),
+            LogicElements::FlipFlops(flip_flop) => flip_flop.image.clone(),
+            LogicElements::Resistors(resistor) => resistor.image.clone(),
+            LogicElements::Buffers(buffer) => buffer.image.clone(),
+            LogicElements::ShiftRegisters(shift_register) => shift_register.image.clone(),
+            LogicElements::Adders(adder) => adder.image.clone(),
+            LogicElements::Demultiplexers(demultiplexer) => demultiplexer.image.clone(),
+            LogicElements::Multiplexers(multiplexer) => multiplexer.image.clone(),
+            LogicElements::LatchRegisters(latch_register) => latch_register.image.clone(),
+        }
+    }
+
+    pub fn get_delay(&self) -> u64 {
+        match self {
+            LogicElements::Gates(logic_gate) => logic_gate.delay,
+            LogicElements::Source(source) => source.delay,
+            LogicElements::Leds(led) => led.delay,
+            LogicElements::Custom(custom) => custom.delay,
+            LogicElements::SubCircuit(subcircuit) => subcircuit.delay,
+            LogicElements::Clock(clock) => clock.delay,
+            LogicElements::FlipFlops(flip_flop) => flip_flop.delay,
+            LogicElements::Resistors(resistor) => resistor.delay,
+            LogicElements::Buffers(buffer) => buffer.delay,
+            LogicElements::ShiftRegisters(shift_register) => shift_register.delay,
+            LogicElements::Adders(adder) => adder.delay,
+            LogicElements::Demultiplexers(demultiplexer) => demultiplexer.delay,
+            LogicElements::Multiplexers(multiplexer) => multiplexer.delay,
+            LogicElements::LatchRegisters(latch_register) => latch_register.delay,
+        }
+    }
+
+    pub fn set_delay(&mut self, delay: u64) {
+        match self {
+            LogicElements::Gates(logic_gate) => logic_gate.delay = delay,
+            LogicElements::Source(source) => source.delay = delay,
+            LogicElements::Leds(led) => led.delay = delay,
+            LogicElements::Custom(custom) => custom.delay = delay,
+            LogicElements::SubCircuit(subcircuit) => subcircuit.delay = delay,
+            LogicElements::Clock(clock) => clock.delay = delay,
+            LogicElements::FlipFlops(flip_flop) => flip_flop.delay = delay,
+            LogicElements::Resistors(resistor) => resistor.delay = delay,
+            LogicElements::Buffers(buffer) => buffer.delay = delay,
+            LogicElements::ShiftRegisters(shift_register) => shift_register.delay = delay,
+            LogicElements::Adders(adder) => adder.delay = delay,
+            LogicElements::Demultiplexers(demultiplexer) => demultiplexer.delay = delay,
+            LogicElements::Multiplexers(multiplexer) => multiplexer.delay = delay,
+            LogicElements::LatchRegisters(latch_register) => latch_register.delay = delay,
+        }
+    }
+
+    // Time the outputs take to follow the inputs, the delay of adders depends on their model
+    pub fn propagation_delay(&self) -> u64 {
+        match self {
+            LogicElements::Adders(adder) => adder.propagation_delay(),
+            _ => self.get_delay(),
+        }
+    }
+
+    // Storage elements break combinational loops, blocks are sequential if anything inside them is
+    pub fn is_sequential(&self) -> bool {
+        match self {
+            LogicElements::FlipFlops(_) | LogicElements::LatchRegisters(_) | LogicElements::ShiftRegisters(_) => true,
+            LogicElements::SubCircuit(subcircuit) => subcircuit.circuit.components.values().any(LogicElements::is_sequential),
+            LogicElements::Custom(custom) => match &custom.behavior {
+                CustomBehavior::Circuit(circuit) => circuit.components.values().any(LogicElements::is_sequential),
+                CustomBehavior::TruthTable(_) => false,
+            },
+            _ => false,
+        }
+    }
+
+    // Pull resistors only drive the bits of a net that no other driver drives
+    pub fn is_weak_driver(&self) -> bool {
+        matches!(self, LogicElements::Resistors(_))
+    }
+
+    pub fn get_id(&self) -> usize {
+        match self {
+            LogicElements::Gates(logic_gate) => logic_gate.id,
+            LogicElements::Source(source) => source.id,
+            LogicElements::Leds(led) => led.id,
+            LogicElements::Custom(custom) => custom.id,
+            LogicElements::SubCircuit(subcircuit) => subcircuit.id,
+            LogicElements::Clock(clock) => clock.id,
+            LogicElements::FlipFlops(flip_flop) => flip_flop.id,
+            LogicElements::Resistors(resistor) => resistor.id,
+            LogicElements::Buffers(buffer) => buffer.id,
+            LogicElements::ShiftRegisters(shift_register) => shift_register.id,
+            LogicElements::Adders(adder) => adder.id,
+            LogicElements::Demultiplexers(demultiplexer) => demultiplexer.id,
+            LogicElements::Multiplexers(multiplexer) => multiplexer.id,
+            LogicElements::LatchRegisters(latch_register) => latch_register.id,
         }
     }
 
@@ -113,7 +306,17 @@ impl LogicElements {
             LogicElements::Gates(logic_gate) => logic_gate.position,
             LogicElements::Source(source) => source.position,
             LogicElements::Leds(led) => led.position,
-            _ => todo!(),
+            LogicElements::Custom(custom) => custom.position,
+            LogicElements::SubCircuit(subcircuit) => subcircuit.position,
+            LogicElements::Clock(clock) => clock.position,
+            LogicElements::FlipFlops(flip_flop) => flip_flop.position,
+            LogicElements::Resistors(resistor) => resistor.position,
+            LogicElements::Buffers(buffer) => buffer.position,
+            LogicElements::ShiftRegisters(shift_register) => shift_register.position,
+            LogicElements::Adders(adder) => adder.position,
+            LogicElements::Demultiplexers(demultiplexer) => demultiplexer.position,
+            LogicElements::Multiplexers(multiplexer) => multiplexer.position,
+            LogicElements::LatchRegisters(latch_register) => latch_register.position,
         }
     }
 
@@ -122,7 +325,17 @@ impl LogicElements {
             LogicElements::Gates(logic_gate) => logic_gate.ref_pin_pos,
             LogicElements::Source(source) => source.ref_pin_pos,
             LogicElements::Leds(led) => led.ref_pin_pos,
-            _ => todo!(),
+            LogicElements::Custom(custom) => custom.ref_pin_pos,
+            LogicElements::SubCircuit(subcircuit) => subcircuit.ref_pin_pos,
+            LogicElements::Clock(clock) => clock.ref_pin_pos,
+            LogicElements::FlipFlops(flip_flop) => flip_flop.ref_pin_pos,
+            LogicElements::Resistors(resistor) => resistor.ref_pin_pos,
+            LogicElements::Buffers(buffer) => buffer.ref_pin_pos,
+            LogicElements::ShiftRegisters(shift_register) => shift_register.ref_pin_pos,
+            LogicElements::Adders(adder) => adder.ref_pin_pos,
+            LogicElements::Demultiplexers(demultiplexer) => demultiplexer.ref_pin_pos,
+            LogicElements::Multiplexers(multiplexer) => multiplexer.ref_pin_pos,
+            LogicElements::LatchRegisters(latch_register) => latch_register.ref_pin_pos,
         }
     }
 
@@ -143,28 +356,64 @@ impl Clone for LogicElements {
             LogicElements::Gates(logic_gate) => LogicElements::Gates(logic_gate.clone()),
             LogicElements::Source(source) => LogicElements::Source(source.clone()),
             LogicElements::Leds(led) => LogicElements::Leds(led.clone()),
-            _ => todo!(),
+            LogicElements::Custom(custom) => LogicElements::Custom(custom.clone()),
+            LogicElements::SubCircuit(subcircuit) => LogicElements::SubCircuit(subcircuit.clone()),
+            LogicElements::Clock(clock) => LogicElements::Clock(clock.clone()),
+            LogicElements::FlipFlops(flip_flop) => LogicElements::FlipFlops(flip_flop.clone()),
+            LogicElements::Resistors(resistor) => LogicElements::Resistors(resistor.clone()),
+            LogicElements::Buffers(buffer) => LogicElements::Buffers(buffer.clone()),
+            LogicElements::ShiftRegisters(shift_register) => LogicElements::ShiftRegisters(shift_register.clone()),
+            LogicElements::Adders(adder) => LogicElements::Adders(adder.clone()),
+            LogicElements::Demultiplexers(demultiplexer) => LogicElements::Demultiplexers(demultiplexer.clone()),
+            LogicElements::Multiplexers(multiplexer) => LogicElements::Multiplexers(multiplexer.clone()),
+            LogicElements::LatchRegisters(latch_register) => LogicElements::LatchRegisters(latch_register.clone()),
         }
     }
 }
 
 
+#[derive(Debug, Clone)]
 pub struct Circuit {
-    pub components: Vec<LogicElements>,
+    pub components: BTreeMap<usize, LogicElements>,   // Components by id, ids are never reused
     pub segments: Vec<WireSegment>,
     pub wires: Vec<Wire>,
     pub component_id: usize,  
+    pub time: u64,              // Simulated time, in abstract time units
+    events: EventQueue,
+    netlist: Option<Netlist>,   // Cached connectivity, cleared whenever the geometry changes
+    evaluate_all: bool,         // Every component has to be evaluated on the next simulation
+    changed_outputs: Vec<PinId>,    // Outputs changed outside of the simulation, their nets are propagated on the next one
+    pub errors: Vec<SimError>,  // Problems found by the last simulation
+    pub editor_errors: Vec<SimError>,   // Problems found while editing, kept until they are cleared
+}
+impl Default for Circuit {
+    fn default() -> Self {
+        Self::new()
+    }
 }
+
 impl Circuit {
     // Create a new circuit
     pub fn new() -> Self {
         Self {
-            components: Vec::new(),
+            components: BTreeMap::new(),
             segments: Vec::new(),
             wires: Vec::new(),
             component_id: 1,
+            time: 0,
+            events: EventQueue::new(),
+            netlist: None,
+            evaluate_all: true,
+            changed_outputs: Vec::new(),
+            errors: Vec::new(),
+            editor_errors: Vec::new(),
         }
     }
+
+    // Problems found by the last simulation, then the ones found while editing
+    pub fn all_errors(&self) -> impl Iterator<Item = &SimError> {
+        self.errors.iter().chain(&self.editor_errors)
+    }
     
 
     //* Function to connect pins of different components together
@@ -228,28 +477,376 @@ impl Circuit {
         }
     }
 
-    // Function to add logic elements to the circuit
-    pub fn add_element(&mut self, mut component: LogicElements) {
+    // Function to add logic elements to the circuit, returns the id given to the element
+    pub fn add_element(&mut self, mut component: LogicElements) -> usize {
         // Set the id of the component
-        match &mut component {
-            LogicElements::Gates(logic_gate) => logic_gate.set_gate_id(self.component_id),
-            LogicElements::Source(source) => source.set_id(self.component_id),
-            LogicElements:: Leds(led) => led.set_id(self.component_id),
-            _ => todo!(),
-        }
+        let id = self.component_id;
+        component.set_id(id);
         self.component_id += 1;
-        self.components.push(component);
+        self.insert_element(component);
+        id
+    }
+
+    // Put an element in the circuit with the id it already has, like a deleted element coming back
+    pub fn insert_element(&mut self, component: LogicElements) {
+        let id = component.get_id();
+        self.component_id = self.component_id.max(id + 1);
+        self.components.insert(id, component);
+        self.netlist = None;
+        self.evaluate_all = true;
+    }
+
+    // Function to delete an element, the pins it had are removed from the wires and the wires
+    // left without any connection are removed. The ids of the other elements don't change
+    pub fn remove_element(&mut self, cid: usize) -> Option<LogicElements> {
+        let component = self.components.remove(&cid)?;
+        for wire in &mut self.wires {
+            wire.pins.retain(|pin| pin.0 != cid);
+        }
+        self.wires.retain(|wire| wire.pins.len() > 1 || !wire.segments.is_empty());
+        self.netlist = None;
+        self.evaluate_all = true;
+        Some(component)
+    }
+
+    // Function to delete the wire segment at the given index, if there is one
+    pub fn remove_segment(&mut self, index: usize) -> Option<WireSegment> {
+        if index >= self.segments.len() {
+            return None;
+        }
+        self.netlist = None;
+        Some(self.segments.remove(index))
+    }
+
+    // Put a wire segment back at the given index, after the other ones when there are fewer segments
+    pub fn insert_segment(&mut self, index: usize, segment: WireSegment) {
+        self.segments.insert(index.min(self.segments.len()), segment);
+        self.netlist = None;
+    }
+
+    // Change the propagation delay of a component
+    pub fn set_delay(&mut self, cid: usize, delay: u64) {
+        if let Some(component) = self.components.get_mut(&cid) {
+            component.set_delay(delay);
+            self.evaluate_all = true;
+        }
+    }
+
+    // Change the value of a source
+    pub fn set_source_value(&mut self, cid: usize, value: Signal) {
+        if let Some(LogicElements::Source(source)) = self.components.get_mut(&cid) {
+            source.output.value = PinValue::Single(value);
+            self.changed_outputs.push((cid, 0, 1));
+        }
     }
 
-    // Function to delete an element from the circuit
-    pub fn remove_element(&mut self, id: usize) {
-        self.components.remove(id);
+    // Change the name of a source or a led, the other components have no name
+    pub fn set_name(&mut self, cid: usize, name: Option<String>) {
+        match self.components.get_mut(&cid) {
+            Some(LogicElements::Source(source)) => source.name = name,
+            Some(LogicElements::Leds(led)) => led.name = name,
+            _ => {}
+        }
+    }
+
+    // Id of the source with this name
+    pub fn find_source(&self, name: &str) -> Option<usize> {
+        self.components.iter().find_map(|(&cid, component)| match component {
+            LogicElements::Source(source) if source.label() == name => Some(cid),
+            _ => None,
+        })
+    }
+
+    // Name and value of every led, in the order of their ids
+    pub fn led_values(&self) -> Vec<(String, PinValue)> {
+        self.components.values()
+            .filter_map(|component| match component {
+                LogicElements::Leds(led) => Some((led.label(), led.input.value.clone())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    // Number of sources and leds in the circuit
+    pub fn count_sources_and_leds(&self) -> (usize, usize) {
+        let sources = self.components.values().filter(|c| matches!(c, LogicElements::Source(_))).count();
+        let leds = self.components.values().filter(|c| matches!(c, LogicElements::Leds(_))).count();
+        (sources, leds)
+    }
+
+    //* Use the circuit as a block of a bigger one: the inputs drive the sources and the outputs
+    //* are read from the leds, both in the order of their ids
+    pub fn evaluate_block(&mut self, inputs: &[PinValue]) -> Vec<PinValue> {
+        let sources = self.components.iter_mut().filter_map(|(&cid, component)| match component {
+            LogicElements::Source(source) => Some((cid, source)),
+            _ => None,
+        });
+        for ((cid, source), value) in sources.zip(inputs) {
+            source.output.value = value.clone();
+            self.changed_outputs.push((cid, 0, 1));
+        }
+
+        // The problems found stay in the `errors` of the inner circuit
+        let _ = self.simulate();
+
+        self.components.values()
+            .filter_map(|component| match component {
+                LogicElements::Leds(led) => Some(led.input.value.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    // Load the images of all the components, needed after loading a circuit from a file
+    #[cfg(feature = "gui")]
+    pub fn load_images(&mut self, ctx: &mut Context) -> GameResult<()> {
+        for component in self.components.values_mut() {
+            component.load_image(ctx)?;
+        }
+        Ok(())
+    }
+
+    // Function to add a wire segment to the circuit
+    pub fn add_segment(&mut self, segment: WireSegment) {
+        self.segments.push(segment);
+        self.netlist = None;
+    }
+
+    // Function to move a component
+    pub fn move_element(&mut self, cid: usize, new_position: Point2<f32>) {
+        if let Some(component) = self.components.get_mut(&cid) {
+            component.update_postion(new_position);
+            self.netlist = None;
+        }
+    }
+
+    // Get a component from its id
+    pub fn component_mut(&mut self, cid: usize) -> Result<&mut LogicElements, SimError> {
+        self.components.get_mut(&cid).ok_or(SimError::UnknownComponent { cid })
+    }
+
+    //* Event driven simulation: only the components whose inputs changed are evaluated, their
+    //* outputs change once their propagation delay has passed, and the changes are propagated
+    //* until the circuit settles or the time limit is reached.
+    //* All the problems found are kept in `errors`, the first one is returned
+    pub fn simulate(&mut self) -> Result<(), SimError> {
+        // Clock edges keep coming forever, the circuit settled once only they are left
+        let limit = self.time + SETTLE_TIME_LIMIT;
+        let _ = self.run(|time, events| time <= limit && events.has_activity());
+
+        // Still busy once the time limit passed: something keeps toggling
+        if self.events.has_activity() {
+            if let Some(netlist) = self.netlist.take() {
+                let error = self.oscillation(&netlist);
+                self.report(error);
+                self.netlist = Some(netlist);
+            }
+        }
+        self.result()
     }
 
-    pub fn simulate(&mut self) {
+    // Advance the simulation to the next point in time at which something happens
+    pub fn step(&mut self) -> Result<(), SimError> {
+        let mut next = None;
+        self.run(|time, _| *next.get_or_insert(time) == time)
+    }
+
+    // Advance the simulated time by the given amount, running the clocks
+    pub fn advance(&mut self, duration: u64) -> Result<(), SimError> {
+        let target = self.time + duration;
+        let result = self.run(|time, _| time <= target);
+        self.time = self.time.max(target);
+        result
+    }
+
+    // Advance the simulation up to the next clock edge
+    pub fn step_clock(&mut self) -> Result<(), SimError> {
+        match self.events.next_tick() {
+            Some(tick) => self.run(|time, _| time <= tick),
+            // The clocks have not been scheduled yet
+            None => self.simulate(),
+        }
+    }
+
+    // Record a problem found by the simulation, once
+    fn report(&mut self, error: SimError) {
+        if !self.errors.contains(&error) {
+            self.errors.push(error);
+        }
+    }
+
+    // The first problem found by the last simulation
+    fn result(&self) -> Result<(), SimError> {
+        match self.errors.first() {
+            Some(error) => Err(error.clone()),
+            None => Ok(()),
+        }
+    }
+
+    // The nets driven by the components that still have events waiting
+    fn oscillation(&self, netlist: &Netlist) -> SimError {
+        let components = self.events.pending_components();
+        let mut nets: Vec<usize> = components.iter()
+            .filter_map(|&cid| self.components.get(&cid).map(|component| (cid, component)))
+            .flat_map(|(cid, component)| {
+                component.get_pins_hitbox().into_iter().filter_map(move |hitbox| match hitbox.r#type {
+                    HitboxType::Pin(_, pid, 0) => netlist.net_of((cid, 0, pid)),
+                    _ => None,
+                })
+            })
+            .collect();
+        nets.sort_unstable();
+        nets.dedup();
+        SimError::Oscillation { nets, components }
+    }
+
+    // Process the events in time order as long as `keep_going` accepts their time
+    fn run(&mut self, keep_going: impl FnMut(u64, &EventQueue) -> bool) -> Result<(), SimError> {
+        // The netlist is only rebuilt after the geometry of the circuit changed
+        let (netlist, rebuilt) = match self.netlist.take() {
+            Some(netlist) => (netlist, false),
+            None => (self.build_netlist(), true),
+        };
+
+        // After a change of the structure every net and every loop is checked again, so the errors
+        // found so far are found again if still there. Otherwise the errors of the nets that aren't
+        // propagated again and the loops stay. The errors of the editor are kept
+        let check_all = rebuilt || self.evaluate_all;
+        if check_all {
+            self.errors.clear();
+        } else {
+            self.errors.retain(|error| error.net().is_some() || matches!(error, SimError::CombinationalLoop { .. }));
+        }
+
+        // Loops without storage elements oscillate or settle on an arbitrary value
+        if check_all {
+            let loops = netlist.feedback_loops(|cid| {
+                self.components.get(&cid).is_some_and(|component| !component.is_sequential())
+            });
+            for components in loops {
+                self.report(SimError::CombinationalLoop { components });
+            }
+        }
+
+        // A pin that can't be found stops the simulation
+        if let Err(error) = self.process_events(&netlist, check_all, keep_going) {
+            self.report(error);
+        }
+        self.netlist = Some(netlist);
+        self.result()
+    }
+
+    fn process_events(
+        &mut self,
+        netlist: &Netlist,
+        all_nets: bool,
+        mut keep_going: impl FnMut(u64, &EventQueue) -> bool,
+    ) -> Result<(), SimError> {
+        // Seed the simulation: bring every net up to date with its source pin and schedule
+        // the components whose inputs changed
+        if self.evaluate_all {
+            self.events.clear();
+            for (&cid, component) in self.components.iter_mut() {
+                self.events.schedule_evaluation(self.time, cid);
+
+                // Clocks start from their value at the current time
+                if let LogicElements::Clock(clock) = component {
+                    clock.output.value = PinValue::Single(clock.value_at(self.time));
+                    self.events.schedule_tick(clock.next_edge(self.time), cid);
+                }
+            }
+            self.evaluate_all = false;
+        }
+        // Every net after a change of the structure, otherwise only the nets of the outputs changed
+        // from outside, so that changing a source only wakes up what it drives
+        let changed = std::mem::take(&mut self.changed_outputs);
+        if all_nets {
+            for net in 0..netlist.nets.len() {
+                self.propagate_net(netlist, net)?;
+            }
+        } else {
+            for pin in changed {
+                if let Some(net) = netlist.net_of(pin) {
+                    self.propagate_net(netlist, net)?;
+                }
+            }
+        }
+
+        let mut evaluations = 0;
+        while let Some(time) = self.events.next_time() {
+            if !keep_going(time, &self.events) {
+                break;
+            }
+            if evaluations == MAX_EVALUATIONS {
+                let error = self.oscillation(netlist);
+                self.report(error);
+                break;
+            }
+            let Some((time, event)) = self.events.pop() else { break };
+            self.time = time;
+
+            match event {
+                Event::Evaluate(cid) => {
+                    evaluations += 1;
+                    self.evaluate(cid)?;
+                }
+                Event::Output(cid, pid, value) => {
+                    // The delay passed: update the output and the net it drives
+                    let pin = self.component_mut(cid)?.get_pin(pid, 0)?;
+                    if pin.value != value {
+                        pin.value = value;
+                        if let Some(net) = netlist.net_of((cid, 0, pid)) {
+                            self.propagate_net(netlist, net)?;
+                        }
+                    }
+                }
+                Event::Tick(cid) => {
+                    // Edge of a clock: change its output and wait for the next edge
+                    if let Some(LogicElements::Clock(clock)) = self.components.get(&cid) {
+                        let value = PinValue::Single(clock.value_at(time));
+                        let next_edge = clock.next_edge(time);
+                        self.events.schedule_output(time + clock.delay, cid, 1, value, &clock.output.value);
+                        self.events.schedule_tick(next_edge, cid);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Evaluate a component and schedule its new outputs after its propagation delay
+    fn evaluate(&mut self, cid: usize) -> Result<(), SimError> {
+        let component = self.component_mut(cid)?;
+        let delay = component.propagation_delay();
+
+        // Store the outputs before the evaluation to find out which ones changed
+        let pids: Vec<usize> = component.get_pins_hitbox().iter()
+            .filter_map(|hitbox| match hitbox.r#type {
+                HitboxType::Pin(_, pid, 0) => Some(pid),
+                _ => None,
+            })
+            .collect();
+        let outputs: Vec<(usize, PinValue)> = pids.into_iter()
+            .map(|pid| Ok((pid, component.get_pin(pid, 0)?.value.clone())))
+            .collect::<Result<_, SimError>>()?;
+
+        component.get_output();
+
+        // Put the old values back, the new ones only show up once the delay has passed
+        for (pid, old_value) in outputs {
+            let component = self.components.get_mut(&cid).ok_or(SimError::UnknownComponent { cid })?;
+            let pin = &mut component.get_pin(pid, 0)?.value;
+            let new_value = std::mem::replace(pin, old_value);
+            self.events.schedule_output(self.time + delay, cid, pid, new_value, pin);
+        }
+        Ok(())
+    }
+
+    // Build the netlist from the pins and wire segments that overlap
+    pub fn build_netlist(&self) -> Netlist {
         // get the hitboxes of all the pins and segments
         let mut hitboxes= Vec::new();
-        for component in &self.components {
+        for component in self.components.values() {
             hitboxes.extend(component.get_pins_hitbox());
         } 
         for seg in &self.segments {
@@ -260,81 +857,417 @@ impl Circuit {
         let cell_size = 50.0; 
         let connected_pins= group_connected_pins(&hitboxes, cell_size);
 
-        // Check if all the connections are correct (contain a single source pin)
-        for (index, group) in connected_pins.iter().enumerate() {
-            // Buffer to hold the source pins
-            let mut source_pins: Vec<(usize, usize, usize)> = group.iter()
-            .filter_map(|&i| {
-                if let HitboxType::Pin(a, b, c) = hitboxes[i].r#type {
-                    if c == 0 {
-                        Some((a, b, c))
-                    } else {
-                        None
-                    }
-                } else {
-                    None
-                }
+        Netlist::from_groups(connected_pins.iter().map(|group| {
+            group.iter().filter_map(|&i| match hitboxes[i].r#type {
+                HitboxType::Pin(cid, pid, ioc) => Some((cid, ioc, pid)),
+                _ => None,
             })
-            .collect();
-            if source_pins.len() > 1 {
-                panic!(
-                    "More than one source pin (ioc = 0) found in group {index}."
-                );
-            }
-    
-            // We have a single source pin
-            // Propagate the signal of the source pin to the other pin in the connection
-            let source = source_pins.pop();
-    
-            if let Some((cid, ioc, pid)) = source {
-                // Access the source pin using the get_pin method
-                let source_value = self.components[cid-1].get_pin(ioc, pid).value.clone();
-                //println!("value of source pin ({}, {}, {}) is {}", cid, pid, ioc, source_value);
-    
-                // Propagate the source signal to all other pins in the connection
-                for &target_index in group {
-                    if let HitboxType::Pin(target_cid, target_ioc, target_pid) = hitboxes[target_index].r#type {
-                        if target_cid == cid && target_ioc == ioc && target_pid == pid {
-                            // Skip the source pin itself
-                            continue;
-                        }
-        
-                        // Get the target pin and set its value
-                        self.components[target_cid - 1]
-                            .get_pin(target_ioc, target_pid)
-                            .value = source_value.clone();
-                    }
-                }
-            } else {
-                // If no source pin exists, set all pins in the group to undefined
-                println!(
-                    "No source pin found in connection {}. Setting all pins to undefined.",
-                    index
-                );
-        
-                for &target_index in group {
-                    if let HitboxType::Pin(target_cid, target_ioc, target_pid) = hitboxes[target_index].r#type {
-                        self.components[target_cid - 1]
-                            .get_pin(target_ioc, target_pid)
-                            .value = PinValue::Single(Signal::Undefined);
-                    }
+        }))
+    }
+
+    // Get the netlist of the circuit, building it if the geometry changed
+    pub fn netlist(&mut self) -> &Netlist {
+        if self.netlist.is_none() {
+            self.netlist = Some(self.build_netlist());
+        }
+        self.netlist.as_ref().unwrap()
+    }
+
+    // Resolve the value of a net from all its drivers and copy it to all the loads of the net,
+    // the components of the pins that changed value get scheduled for evaluation
+    fn propagate_net(&mut self, netlist: &Netlist, net: usize) -> Result<(), SimError> {
+        // The problems of the net are found again if still there
+        self.errors.retain(|error| error.net() != Some(net));
+        let drivers = netlist.drivers(net);
+        // Pull resistors are weak drivers, resolved apart from the others
+        let mut strong: Option<PinValue> = None;
+        let mut weak: Option<PinValue> = None;
+        for &(cid, ioc, pid) in drivers {
+            let component = self.component_mut(cid)?;
+            let driven = component.get_pin(pid, ioc)?.value.clone();
+            let value = if component.is_weak_driver() { &mut weak } else { &mut strong };
+            *value = Some(match value.take() {
+                None => driven,
+                Some(value) if value.width() == driven.width() => value.resolve(&driven),
+                // Drivers of different widths can't be resolved
+                Some(value) => {
+                    let error = SimError::WidthMismatch {
+                        net,
+                        driver: drivers[0],
+                        load: (cid, ioc, pid),
+                        expected: value.width(),
+                        found: driven.width(),
+                    };
+                    self.report(error);
+                    value.to_undefined()
                 }
+            });
+        }
+
+        // The weak drivers only set the bits that no strong driver drives
+        let value = match (strong, weak) {
+            (Some(strong), Some(weak)) if strong.width() == weak.width() => Some(strong.with_weak(&weak)),
+            (Some(strong), Some(_)) => Some(strong.to_undefined()),
+            (strong, weak) => strong.or(weak),
+        };
+
+        // Drivers fighting each other are reported with all the pins driving the net
+        if let Some(value) = &value {
+            if (0..value.width()).any(|index| value.bit(index) == Signal::Conflict) {
+                self.report(SimError::MultipleDrivers { net, pins: drivers.to_vec() });
             }
-            // After propagating the signals get the output of each component if the componenet
-            // needs to evaluate the output
-            for component in &mut self.components {
-                // Call get_output for all other components
-                component.get_output();
+        }
+
+        for &(cid, ioc, pid) in netlist.loads(net) {
+            let pin = self.component_mut(cid)?.get_pin(pid, ioc)?;
+            // Without a source pin the net is floating, and the pins that can't take the value
+            // because of its width are undefined
+            let new_value = match &value {
+                Some(value) if value.width() == pin.value.width() => value.clone(),
+                Some(value) => {
+                    let error = SimError::WidthMismatch {
+                        net,
+                        driver: drivers[0],
+                        load: (cid, ioc, pid),
+                        expected: value.width(),
+                        found: pin.value.width(),
+                    };
+                    let new_value = pin.value.to_undefined();
+                    self.report(error);
+                    new_value
+                }
+                None => pin.value.filled(Signal::HighZ),
+            };
+
+            let pin = self.component_mut(cid)?.get_pin(pid, ioc)?;
+            if pin.value != new_value {
+                pin.value = new_value;
+                self.events.schedule_evaluation(self.time, cid);
             }
         }
+        Ok(())
     }
 
     // TODO: function need to be modified to account for possible components with more than 1 output
     pub fn display_outputs(&self){
-        for (index, component) in self.components.iter().enumerate(){
-            let mut out = self.components[index].clone();
-            println!("output is {:?}", out.get_pin(0, 1).value)
+        for component in self.components.values() {
+            let mut out = component.clone();
+            if let Ok(pin) = out.get_pin(0, 1) {
+                println!("output is {:?}", pin.value)
+            }
         }
     }
 }
 
+
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::netlist::PinId;
+
+    // Center of the hitbox of a pin
+    pub(crate) fn pin_center(circuit: &Circuit, (cid, ioc, pid): PinId) -> Point2<f32> {
+        let hitbox = circuit.components[&cid].get_pins_hitbox().into_iter()
+            .find(|hitbox| matches!(hitbox.r#type, HitboxType::Pin(_, p, i) if p == pid && i == ioc))
+            .expect("unknown pin");
+        Point2 { x: hitbox.rect.x + hitbox.rect.w / 2.0, y: hitbox.rect.y + hitbox.rect.h / 2.0 }
+    }
+
+    // Move the component of the pin `to` so that this pin lands on the pin `from`, connecting them
+    pub(crate) fn attach(circuit: &mut Circuit, from: PinId, to: PinId) {
+        let target = pin_center(circuit, from);
+        let current = pin_center(circuit, to);
+        let position = circuit.components[&to.0].get_position();
+        circuit.move_element(to.0, Point2 { x: position.x + target.x - current.x, y: position.y + target.y - current.y });
+    }
+
+    // A source, a chain of not gates with the given delays and a led, returns their ids
+    pub(crate) fn not_chain(value: usize, delays: &[u64]) -> (Circuit, usize, Vec<usize>, usize) {
+        let mut circuit = Circuit::new();
+        let source = circuit.add_element(LogicElements::Source(Source::new(value).unwrap()));
+        let mut gates = Vec::new();
+        let mut previous = (source, 0, 1);
+        for &delay in delays {
+            let gate = circuit.add_element(LogicElements::Gates(LogicGate::new_gate(2, 1, false, 1).unwrap()));
+            circuit.set_delay(gate, delay);
+            attach(&mut circuit, previous, (gate, 1, 1));
+            previous = (gate, 0, 1);
+            gates.push(gate);
+        }
+        let led = circuit.add_element(LogicElements::Leds(Led::new()));
+        attach(&mut circuit, previous, (led, 1, 1));
+        (circuit, source, gates, led)
+    }
+
+    fn led_value(circuit: &Circuit, led: usize) -> PinValue {
+        match &circuit.components[&led] {
+            LogicElements::Leds(led) => led.input.value.clone(),
+            _ => panic!("not a led"),
+        }
+    }
+
+    #[test]
+    fn simulation_settles_through_a_chain_of_gates() {
+        let (mut circuit, source, _, led) = not_chain(1, &[1, 1, 1]);
+        circuit.simulate().unwrap();
+        assert_eq!(led_value(&circuit, led), PinValue::Single(Signal::Off));
+
+        circuit.set_source_value(source, Signal::Off);
+        circuit.simulate().unwrap();
+        assert_eq!(led_value(&circuit, led), PinValue::Single(Signal::On));
+        assert!(!circuit.events.has_activity());
+    }
+
+    #[test]
+    fn simulation_without_changes_does_nothing() {
+        let (mut circuit, _, _, led) = not_chain(0, &[1, 1]);
+        circuit.simulate().unwrap();
+        let time = circuit.time;
+        circuit.simulate().unwrap();
+        assert_eq!(circuit.time, time);
+        assert_eq!(led_value(&circuit, led), PinValue::Single(Signal::Off));
+    }
+
+    #[test]
+    fn toggling_a_source_only_evaluates_what_it_drives() {
+        let (mut circuit, source, _, led) = not_chain(0, &[1]);
+        // A second chain, far from the first one
+        let other_source = circuit.add_element(LogicElements::Source(Source::new(0).unwrap()));
+        circuit.move_element(other_source, Point2 { x: 0.0, y: 600.0 });
+        let other_gate = circuit.add_element(LogicElements::Gates(LogicGate::new_gate(2, 1, false, 1).unwrap()));
+        attach(&mut circuit, (other_source, 0, 1), (other_gate, 1, 1));
+        let other_led = circuit.add_element(LogicElements::Leds(Led::new()));
+        attach(&mut circuit, (other_gate, 0, 1), (other_led, 1, 1));
+        circuit.simulate().unwrap();
+        assert_eq!(led_value(&circuit, other_led), PinValue::Single(Signal::On));
+
+        // A wrong output on the other chain is only fixed if its gate is evaluated again
+        if let Some(LogicElements::Gates(gate)) = circuit.components.get_mut(&other_gate) {
+            gate.output.value = PinValue::Single(Signal::Off);
+        }
+        circuit.set_source_value(source, Signal::On);
+        circuit.simulate().unwrap();
+        assert_eq!(led_value(&circuit, led), PinValue::Single(Signal::Off));
+        match &circuit.components[&other_gate] {
+            LogicElements::Gates(gate) => assert_eq!(gate.output.value, PinValue::Single(Signal::Off)),
+            _ => panic!("not a gate"),
+        }
+        assert_eq!(led_value(&circuit, other_led), PinValue::Single(Signal::On));
+    }
+
+    #[test]
+    fn outputs_change_once_the_delays_passed() {
+        let (mut circuit, source, _, led) = not_chain(0, &[3, 4]);
+        circuit.simulate().unwrap();
+        assert_eq!(led_value(&circuit, led), PinValue::Single(Signal::Off));
+
+        let start = circuit.time;
+        circuit.set_source_value(source, Signal::On);
+        circuit.advance(6).unwrap();
+        assert_eq!(led_value(&circuit, led), PinValue::Single(Signal::Off));
+        circuit.advance(1).unwrap();
+        assert_eq!(led_value(&circuit, led), PinValue::Single(Signal::On));
+        assert_eq!(circuit.time, start + 7);
+    }
+
+    #[test]
+    fn step_stops_at_every_change() {
+        let (mut circuit, source, _, _) = not_chain(0, &[2, 5]);
+        circuit.simulate().unwrap();
+        let start = circuit.time;
+        circuit.set_source_value(source, Signal::On);
+
+        let mut times = Vec::new();
+        while circuit.events.has_activity() || times.is_empty() {
+            circuit.step().unwrap();
+            times.push(circuit.time - start);
+        }
+        assert_eq!(times.first(), Some(&0));
+        assert_eq!(times.last(), Some(&7));
+        assert!(times.contains(&2));
+    }
+
+    #[test]
+    fn zero_delay_components_change_at_once() {
+        let (mut circuit, source, _, led) = not_chain(0, &[0]);
+        circuit.simulate().unwrap();
+        let start = circuit.time;
+        circuit.set_source_value(source, Signal::On);
+        circuit.simulate().unwrap();
+        assert_eq!(circuit.time, start);
+        assert_eq!(led_value(&circuit, led), PinValue::Single(Signal::Off));
+    }
+
+    #[test]
+    fn drivers_of_the_same_net_are_resolved() {
+        let mut circuit = Circuit::new();
+        let on = circuit.add_element(LogicElements::Source(Source::new(1).unwrap()));
+        let other = circuit.add_element(LogicElements::Source(Source::new(1).unwrap()));
+        let led = circuit.add_element(LogicElements::Leds(Led::new()));
+        attach(&mut circuit, (on, 0, 1), (other, 0, 1));
+        attach(&mut circuit, (on, 0, 1), (led, 1, 1));
+        circuit.simulate().unwrap();
+        assert_eq!(led_value(&circuit, led), PinValue::Single(Signal::On));
+
+        circuit.set_source_value(other, Signal::Off);
+        let net = circuit.netlist().net_of((led, 1, 1)).unwrap();
+        let mut drivers = vec![(on, 0, 1), (other, 0, 1)];
+        drivers.sort_unstable();
+        let error = circuit.simulate().unwrap_err();
+        assert_eq!(led_value(&circuit, led), PinValue::Single(Signal::Conflict));
+        match error {
+            SimError::MultipleDrivers { net: found, mut pins } => {
+                pins.sort_unstable();
+                assert_eq!((found, pins), (net, drivers));
+            }
+            error => panic!("unexpected error {}", error),
+        }
+    }
+
+    #[test]
+    fn pull_ups_set_the_nets_left_floating() {
+        use crate::buffer::{BufferType, Pull};
+
+        let mut circuit = Circuit::new();
+        let source = circuit.add_element(LogicElements::Source(Source::new(1).unwrap()));
+        let buffer = circuit.add_element(LogicElements::Buffers(Buffer::new(BufferType::OpenDrain, 1)));
+        let pull_up = circuit.add_element(LogicElements::Resistors(PullResistor::new(Pull::Up, 1)));
+        let led = circuit.add_element(LogicElements::Leds(Led::new()));
+        attach(&mut circuit, (source, 0, 1), (buffer, 1, 1));
+        attach(&mut circuit, (buffer, 0, 1), (pull_up, 0, 1));
+        attach(&mut circuit, (buffer, 0, 1), (led, 1, 1));
+        circuit.simulate().unwrap();
+        assert_eq!(led_value(&circuit, led), PinValue::Single(Signal::On));
+
+        // The open drain buffer wins over the pull-up
+        circuit.set_source_value(source, Signal::Off);
+        circuit.simulate().unwrap();
+        assert_eq!(led_value(&circuit, led), PinValue::Single(Signal::Off));
+    }
+
+    #[test]
+    fn nets_without_drivers_float() {
+        let mut circuit = Circuit::new();
+        let gate = circuit.add_element(LogicElements::Gates(LogicGate::new_gate(2, 1, false, 1).unwrap()));
+        let led = circuit.add_element(LogicElements::Leds(Led::new()));
+        attach(&mut circuit, (gate, 1, 1), (led, 1, 1));
+        circuit.simulate().unwrap();
+        assert_eq!(led_value(&circuit, led), PinValue::Single(Signal::HighZ));
+    }
+
+    // An or gate followed by a not gate whose output goes back to the second input of the or gate
+    // through a wire, the first input is driven by a source. A high source keeps the loop stable
+    pub(crate) fn or_not_ring(value: usize) -> (Circuit, usize, [usize; 2]) {
+        let mut circuit = Circuit::new();
+        let or = circuit.add_element(LogicElements::Gates(LogicGate::new_gate(1, 2, false, 1).unwrap()));
+        let not = circuit.add_element(LogicElements::Gates(LogicGate::new_gate(2, 1, false, 1).unwrap()));
+        attach(&mut circuit, (or, 0, 1), (not, 1, 1));
+        let output = pin_center(&circuit, (not, 0, 1));
+        let input = pin_center(&circuit, (or, 1, 2));
+        let corners = [output, Point2 { x: output.x, y: 100.0 }, Point2 { x: input.x, y: 100.0 }, input];
+        for corner in corners.windows(2) {
+            circuit.add_segment(WireSegment::new(corner[0], corner[1]));
+        }
+        let source = circuit.add_element(LogicElements::Source(Source::new(value).unwrap()));
+        attach(&mut circuit, (or, 1, 1), (source, 0, 1));
+        (circuit, source, [or, not])
+    }
+
+    #[test]
+    fn editor_errors_survive_the_simulation() {
+        let (mut circuit, _, _) = or_not_ring(1);
+        let error = SimError::UnknownComponentType { name: "Mux".to_string() };
+        circuit.editor_errors.push(error.clone());
+        let _ = circuit.simulate();
+        let _ = circuit.simulate();
+        assert_eq!(circuit.editor_errors, vec![error.clone()]);
+        assert_eq!(circuit.errors.len(), 1);
+        assert_eq!(circuit.all_errors().last(), Some(&error));
+    }
+
+    #[test]
+    fn combinational_loops_are_reported() {
+        let (mut circuit, _, gates) = or_not_ring(1);
+        let error = circuit.simulate().unwrap_err();
+        assert_eq!(error, SimError::CombinationalLoop { components: gates.to_vec() });
+        // The loop settles, it is the only problem
+        assert_eq!(circuit.errors, vec![error]);
+    }
+
+    #[test]
+    fn circuits_that_keep_toggling_are_reported() {
+        let (mut circuit, source, gates) = or_not_ring(1);
+        let _ = circuit.simulate();
+        circuit.set_source_value(source, Signal::Off);
+        let _ = circuit.simulate();
+
+        // The error names the components caught changing when the time limit was reached, and
+        // the nets they drive
+        let netlist = circuit.netlist().clone();
+        let oscillation = circuit.errors.iter().find_map(|error| match error {
+            SimError::Oscillation { nets, components } => Some((nets.clone(), components.clone())),
+            _ => None,
+        });
+        let (nets, components) = oscillation.expect("no oscillation reported");
+        assert!(!components.is_empty() && components.iter().all(|cid| gates.contains(cid)));
+        let driven: Vec<usize> = components.iter().filter_map(|&cid| netlist.net_of((cid, 0, 1))).collect();
+        assert_eq!(nets, driven);
+    }
+
+    // Load new operands in the registers feeding an adder, as if they had just been clocked
+    fn load_operands(circuit: &mut Circuit, registers: [usize; 2], operands: [[Signal; 4]; 2]) {
+        for (register, operand) in registers.into_iter().zip(operands) {
+            if let Some(LogicElements::LatchRegisters(register)) = circuit.components.get_mut(&register) {
+                register.state = operand.to_vec();
+            }
+            circuit.events.schedule_evaluation(circuit.time, register);
+        }
+    }
+
+    #[test]
+    fn late_outputs_dont_overwrite_newer_ones() {
+        use crate::adder::{AdderType, A_PIN, B_PIN, CARRY_IN_PIN, CARRY_OUT_PIN, SUM_PIN};
+        use crate::latch_register::LatchRegisterType;
+        use Signal::{Off, On};
+
+        let mut circuit = Circuit::new();
+        let adder = circuit.add_element(LogicElements::Adders(Adder::new(AdderType::RippleCarry, 4)));
+        let registers = [A_PIN, B_PIN].map(|pid| {
+            let register = circuit.add_element(LogicElements::LatchRegisters(LatchRegister::new(LatchRegisterType::Register, 4)));
+            attach(&mut circuit, (adder, 1, pid), (register, 0, 1));
+            register
+        });
+        let carry_in = circuit.add_element(LogicElements::Source(Source::new(0).unwrap()));
+        attach(&mut circuit, (adder, 1, CARRY_IN_PIN), (carry_in, 0, 1));
+        circuit.simulate().unwrap();
+
+        // 1111 + 0001: the carry ripples through the 4 bits
+        load_operands(&mut circuit, registers, [[On, On, On, On], [On, Off, Off, Off]]);
+        circuit.advance(2).unwrap();
+        // 0001 + 0001 before the first sum is out: the carry only goes through one bit, the new
+        // outputs come before the ones computed for the previous operands
+        load_operands(&mut circuit, registers, [[On, Off, Off, Off], [On, Off, Off, Off]]);
+        circuit.simulate().unwrap();
+
+        let LogicElements::Adders(adder) = &mut circuit.components.get_mut(&adder).unwrap() else { panic!("not an adder") };
+        assert_eq!(adder.get_pin(SUM_PIN, 0).unwrap().value, PinValue::Multiple(vec![Off, On, Off, Off]));
+        assert_eq!(adder.get_pin(CARRY_OUT_PIN, 0).unwrap().value, PinValue::Single(Off));
+    }
+
+    #[test]
+    fn netlist_follows_the_geometry() {
+        let (mut circuit, source, gates, led) = not_chain(0, &[1]);
+        let netlist = circuit.netlist().clone();
+        assert_eq!(netlist.nets.len(), 2);
+        let net = netlist.net_of((source, 0, 1)).unwrap();
+        assert_eq!(netlist.loads(net), &[(gates[0], 1, 1)]);
+        assert_eq!(netlist.net_of((led, 1, 1)), netlist.net_of((gates[0], 0, 1)));
+
+        // Moving the led away disconnects it, the cached netlist is rebuilt
+        circuit.move_element(led, Point2 { x: 1000.0, y: 1000.0 });
+        let netlist = circuit.netlist();
+        assert_eq!(netlist.nets.len(), 3);
+        assert_ne!(netlist.net_of((led, 1, 1)), netlist.net_of((gates[0], 0, 1)));
+    }
+}
@@ -0,0 +1,480 @@
+//! Circuit file format
+//!
+//! Circuits are saved as JSON documents. The `version` field is bumped every time the
+//! schema changes in a way older versions of the simulator can't read.
+//!
+//! Version 1:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "components": [
+//!     { "id": 1, "kind": "source", "value": 1, "position": { "x": 100.0, "y": 40.0 }, "delay": 0 },
+//!     { "id": 2, "kind": "gate", "gate": "and", "inputs": 2, "bits": null, "position": { "x": 200.0, "y": 40.0 }, "delay": 1 },
+//!     { "id": 3, "kind": "led", "position": { "x": 300.0, "y": 40.0 }, "delay": 0 }
+//!   ],
+//!   "segments": [
+//!     { "start": { "x": 170.0, "y": 70.0 }, "end": { "x": 210.0, "y": 70.0 } }
+//!   ],
+//!   "wires": [
+//!     { "pins": [[1, 0, 1], [2, 1, 1]], "segments": [] }
+//!   ]
+//! }
+//! ```
+//!
+//! - `components` are listed by `id`, the id of the component in the simulator. Ids start at 1
+//!   and each one is used once, deleted components leave gaps that are kept when saving, so
+//!   unnamed sources and leds keep their `in_<id>` and `out_<id>` names.
+//! - `kind` is one of `gate`, `source`, `led`, `clock`, `flipflop`, `latchregister`,
+//!   `multiplexer`, `demultiplexer`, `adder`, `shiftregister`, `buffer`, `resistor`, `custom`
+//!   and `subcircuit`:
+//!   - `gate` has the gate type (`and`, `or`, `not`, `nand`, `nor`, `xor`, `xnor`), the number
+//!     of `inputs` and the number of `bits` of each pin, `null` for single bit gates.
+//!   - `source` has its `value`, 0 for a low source and 1 for a high source.
+//!   - `source` and `led` can have a `name`, used by the command line runner. Without one they
+//!     are called `in_<id>` and `out_<id>`.
+//!   - `clock` has its `period`, its `duty` cycle in percent and its `phase`, all the
+//!     times are in simulation time units.
+//!   - `flipflop` has the `flip_flop` type (`d`, `t`, `jk`, `sr`), its state isn't saved.
+//!   - `latchregister` has the `latch_register` type (`sr`, `d`, `register`) and the number of
+//!     `bits` of a register, 1 for latches.
+//!   - `multiplexer` and `demultiplexer` have the number of `select` lines and the number of
+//!     `bits` of the data pins.
+//!   - `adder` has the `adder` type (`half`, `full`, `ripple_carry`, `carry_lookahead`) and the
+//!     number of `bits` of the operands, 1 for half and full adders. Its `delay` is the delay
+//!     of a single gate level.
+//!   - `shiftregister` has its `mode` (`siso`, `sipo`, `piso`, `pipo`), its shift `direction`
+//!     (`left`, `right`) and its number of `bits`, the stored bits aren't saved.
+//!   - `buffer` has the `buffer` type (`buffer`, `tri_state`, `open_drain`) and the number of
+//!     `bits` of its input and output.
+//!   - `resistor` has its `pull` direction (`up`, `down`) and its number of `bits`.
+//!   - `custom` embeds the whole `definition` of the component (see `custom`), so the file
+//!     doesn't depend on other files.
+//!   - `subcircuit` has the `name` of the block and the inner `circuit`, written with this
+//!     same schema.
+//! - `position` is the top left corner of the component and `delay` its propagation delay.
+//! - `segments` are the wire segments drawn on the canvas, from `start` to `end`.
+//! - `wires` are the logical connections, pins are written as `[cid, ioc, pid]`.
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use mint::Point2;
+use serde::{Deserialize, Serialize};
+
+use crate::circuit::{Circuit, LogicElements};
+use crate::clock::Clock;
+use crate::flip_flop::{FlipFlop, FlipFlopType};
+use crate::latch_register::{LatchRegister, LatchRegisterType};
+use crate::multiplexer::{Demultiplexer, Multiplexer};
+use crate::adder::{Adder, AdderType};
+use crate::shift_register::{ShiftDirection, ShiftRegister, ShiftRegisterMode};
+use crate::buffer::{Buffer, BufferType, Pull, PullResistor};
+use crate::custom::{CustomComponent, CustomDefinition};
+use crate::subcircuit::SubCircuit;
+use crate::led::Led;
+use crate::logic_gates::LogicGate;
+use crate::source::Source;
+use crate::structure::*;
+
+/// Version of the schema written by `Circuit::save`
+pub const FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug)]
+pub enum FileError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    UnsupportedVersion(u32),
+    Invalid(String),
+}
+
+impl fmt::Display for FileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FileError::Io(err) => write!(f, "{}", err),
+            FileError::Json(err) => write!(f, "invalid circuit file: {}", err),
+            FileError::UnsupportedVersion(version) => {
+                write!(f, "unsupported circuit file version {} (expected {})", version, FORMAT_VERSION)
+            }
+            FileError::Invalid(reason) => write!(f, "invalid circuit file: {}", reason),
+        }
+    }
+}
+
+impl From<std::io::Error> for FileError {
+    fn from(err: std::io::Error) -> Self {
+        FileError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for FileError {
+    fn from(err: serde_json::Error) -> Self {
+        FileError::Json(err)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct PositionRecord {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl From<Point2<f32>> for PositionRecord {
+    fn from(point: Point2<f32>) -> Self {
+        PositionRecord { x: point.x, y: point.y }
+    }
+}
+
+impl From<PositionRecord> for Point2<f32> {
+    fn from(position: PositionRecord) -> Self {
+        Point2 { x: position.x, y: position.y }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComponentRecord {
+    pub id: usize,
+    #[serde(flatten)]
+    pub kind: ComponentKind,
+    pub position: PositionRecord,
+    pub delay: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum ComponentKind {
+    Gate { gate: LogicGates, inputs: usize, bits: Option<usize> },
+    Source {
+        value: usize,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+    },
+    Led {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+    },
+    Clock { period: u64, duty: u64, phase: u64 },
+    FlipFlop { flip_flop: FlipFlopType },
+    LatchRegister { latch_register: LatchRegisterType, bits: usize },
+    Multiplexer { select: usize, bits: usize },
+    Demultiplexer { select: usize, bits: usize },
+    Adder { adder: AdderType, bits: usize },
+    ShiftRegister { mode: ShiftRegisterMode, direction: ShiftDirection, bits: usize },
+    Buffer { buffer: BufferType, bits: usize },
+    Resistor { pull: Pull, bits: usize },
+    Custom { definition: Box<CustomDefinition> },
+    SubCircuit { name: String, circuit: Box<CircuitFile> },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SegmentRecord {
+    pub start: PositionRecord,
+    pub end: PositionRecord,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WireRecord {
+    pub pins: Vec<(usize, usize, usize)>,
+    pub segments: Vec<SegmentRecord>,
+}
+
+/// Content of a circuit file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CircuitFile {
+    pub version: u32,
+    pub components: Vec<ComponentRecord>,
+    pub segments: Vec<SegmentRecord>,
+    pub wires: Vec<WireRecord>,
+}
+
+impl From<&WireSegment> for SegmentRecord {
+    fn from(segment: &WireSegment) -> Self {
+        SegmentRecord { start: segment.start.into(), end: segment.end.into() }
+    }
+}
+
+impl From<&SegmentRecord> for WireSegment {
+    fn from(record: &SegmentRecord) -> Self {
+        WireSegment::new(record.start.into(), record.end.into())
+    }
+}
+
+impl ComponentRecord {
+    fn from_element(id: usize, element: &LogicElements) -> Result<Self, FileError> {
+        let kind = match element {
+            LogicElements::Gates(logic_gate) => ComponentKind::Gate {
+                gate: logic_gate.r#type.clone(),
+                inputs: logic_gate.num_input,
+                bits: match &logic_gate.output.value {
+                    PinValue::Single(_) => None,
+                    PinValue::Multiple(signals) => Some(signals.len()),
+                },
+            },
+            LogicElements::Source(source) => ComponentKind::Source {
+                value: match source.output.value {
+                    PinValue::Single(Signal::On) => 1,
+                    _ => 0,
+                },
+                name: source.name.clone(),
+            },
+            LogicElements::Leds(led) => ComponentKind::Led { name: led.name.clone() },
+            LogicElements::Clock(clock) => ComponentKind::Clock {
+                period: clock.period,
+                duty: clock.duty,
+                phase: clock.phase,
+            },
+            LogicElements::FlipFlops(flip_flop) => ComponentKind::FlipFlop { flip_flop: flip_flop.r#type },
+            LogicElements::LatchRegisters(latch_register) => ComponentKind::LatchRegister {
+                latch_register: latch_register.r#type,
+                bits: latch_register.bits,
+            },
+            LogicElements::Multiplexers(multiplexer) => ComponentKind::Multiplexer {
+                select: multiplexer.select_bits,
+                bits: multiplexer.bits,
+            },
+            LogicElements::Demultiplexers(demultiplexer) => ComponentKind::Demultiplexer {
+                select: demultiplexer.select_bits,
+                bits: demultiplexer.bits,
+            },
+            LogicElements::Adders(adder) => ComponentKind::Adder { adder: adder.r#type, bits: adder.bits },
+            LogicElements::ShiftRegisters(shift_register) => ComponentKind::ShiftRegister {
+                mode: shift_register.mode,
+                direction: shift_register.direction,
+                bits: shift_register.bits,
+            },
+            LogicElements::Buffers(buffer) => ComponentKind::Buffer {
+                buffer: buffer.r#type,
+                bits: buffer.output.value.width(),
+            },
+            LogicElements::Resistors(resistor) => ComponentKind::Resistor {
+                pull: resistor.pull,
+                bits: resistor.output.value.width(),
+            },
+            LogicElements::Custom(custom) => ComponentKind::Custom {
+                definition: Box::new(custom.definition.clone()),
+            },
+            LogicElements::SubCircuit(subcircuit) => ComponentKind::SubCircuit {
+                name: subcircuit.name.clone(),
+                circuit: Box::new(CircuitFile::from_circuit(&subcircuit.circuit)?),
+            },
+        };
+
+        Ok(ComponentRecord {
+            id,
+            kind,
+            position: element.get_position().into(),
+            delay: element.get_delay(),
+        })
+    }
+
+    fn to_element(&self) -> Result<LogicElements, FileError> {
+        let mut element = match &self.kind {
+            ComponentKind::Gate { gate, inputs, bits } => {
+                let bus = bits.is_some();
+                let gate = LogicGate::new_gate(gate.index(), *inputs, bus, bits.unwrap_or(1))
+                    .map_err(|error| FileError::Invalid(format!("component {}: {}", self.id, error)))?;
+                LogicElements::Gates(gate)
+            }
+            ComponentKind::Source { value, name } => {
+                let mut source = Source::new(*value)
+                    .map_err(|error| FileError::Invalid(format!("component {}: {}", self.id, error)))?;
+                source.name = name.clone();
+                LogicElements::Source(source)
+            }
+            ComponentKind::Led { name } => {
+                let mut led = Led::new();
+                led.name = name.clone();
+                LogicElements::Leds(led)
+            }
+            ComponentKind::Clock { period, duty, phase } => LogicElements::Clock(Clock::new(*period, *duty, *phase)),
+            ComponentKind::FlipFlop { flip_flop } => LogicElements::FlipFlops(FlipFlop::new(*flip_flop)),
+            ComponentKind::LatchRegister { latch_register, bits } => {
+                LogicElements::LatchRegisters(LatchRegister::new(*latch_register, *bits))
+            }
+            ComponentKind::Multiplexer { select, bits } => LogicElements::Multiplexers(Multiplexer::new(*select, *bits)),
+            ComponentKind::Demultiplexer { select, bits } => LogicElements::Demultiplexers(Demultiplexer::new(*select, *bits)),
+            ComponentKind::Adder { adder, bits } => LogicElements::Adders(Adder::new(*adder, *bits)),
+            ComponentKind::ShiftRegister { mode, direction, bits } => {
+                LogicElements::ShiftRegisters(ShiftRegister::new(*mode, *direction, *bits))
+            }
+            ComponentKind::Buffer { buffer, bits } => LogicElements::Buffers(Buffer::new(*buffer, *bits)),
+            ComponentKind::Resistor { pull, bits } => LogicElements::Resistors(PullResistor::new(*pull, *bits)),
+            ComponentKind::Custom { definition } => LogicElements::Custom(CustomComponent::new(definition)?),
+            ComponentKind::SubCircuit { name, circuit } => {
+                LogicElements::SubCircuit(SubCircuit::new(name, circuit.to_circuit()?))
+            }
+        };
+        element.update_postion(self.position.into());
+        element.set_delay(self.delay);
+        Ok(element)
+    }
+}
+
+impl CircuitFile {
+    // Describe a circuit with the current version of the schema
+    pub fn from_circuit(circuit: &Circuit) -> Result<Self, FileError> {
+        let components = circuit.components.iter()
+            .map(|(&cid, element)| ComponentRecord::from_element(cid, element))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(CircuitFile {
+            version: FORMAT_VERSION,
+            components,
+            segments: circuit.segments.iter().map(SegmentRecord::from).collect(),
+            wires: circuit.wires.iter()
+                .map(|wire| WireRecord {
+                    pins: wire.pins.iter()
+                        .filter(|pin| circuit.components.contains_key(&pin.0))
+                        .copied()
+                        .collect(),
+                    segments: wire.segments.iter().map(SegmentRecord::from).collect(),
+                })
+                .collect(),
+        })
+    }
+
+    // Rebuild the circuit, the images of the components still have to be loaded
+    pub fn to_circuit(&self) -> Result<Circuit, FileError> {
+        if self.version != FORMAT_VERSION {
+            return Err(FileError::UnsupportedVersion(self.version));
+        }
+
+        // Components get back their ids, the next new component comes after the last one
+        let mut circuit = Circuit::new();
+        for record in &self.components {
+            if record.id == 0 {
+                return Err(FileError::Invalid("component ids start at 1, found 0".to_string()));
+            }
+            if circuit.components.contains_key(&record.id) {
+                return Err(FileError::Invalid(format!("component id {} is used more than once", record.id)));
+            }
+            let mut element = record.to_element()?;
+            element.set_id(record.id);
+            circuit.insert_element(element);
+        }
+        for segment in &self.segments {
+            circuit.add_segment(segment.into());
+        }
+        circuit.wires = self.wires.iter()
+            .map(|wire| Wire {
+                pins: wire.pins.clone(),
+                segments: wire.segments.iter().map(WireSegment::from).collect(),
+            })
+            .collect();
+        Ok(circuit)
+    }
+}
+
+impl Circuit {
+    pub fn to_json(&self) -> Result<String, FileError> {
+        Ok(serde_json::to_string_pretty(&CircuitFile::from_circuit(self)?)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<Circuit, FileError> {
+        serde_json::from_str::<CircuitFile>(json)?.to_circuit()
+    }
+
+    // Save the circuit to a JSON file
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), FileError> {
+        fs::write(path, self.to_json()?)?;
+        Ok(())
+    }
+
+    // Load a circuit from a JSON file, the images of the components still have to be loaded
+    pub fn load(path: impl AsRef<Path>) -> Result<Circuit, FileError> {
+        Circuit::from_json(&fs::read_to_string(path)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::tests::not_chain;
+
+    #[test]
+    fn circuits_survive_a_save_and_load() {
+        let (mut circuit, source, gates, led) = not_chain(1, &[3]);
+        circuit.set_name(source, Some("a".to_string()));
+        circuit.set_name(led, Some("y".to_string()));
+        circuit.add_element(LogicElements::Clock(Clock::new(10, 50, 2)));
+        circuit.add_element(LogicElements::Adders(Adder::new(AdderType::RippleCarry, 4)));
+        circuit.add_segment(WireSegment::new(Point2 { x: 0.0, y: 0.0 }, Point2 { x: 40.0, y: 0.0 }));
+
+        let json = circuit.to_json().unwrap();
+        let loaded = Circuit::from_json(&json).unwrap();
+        assert_eq!(loaded.to_json().unwrap(), json);
+
+        assert_eq!(loaded.find_source("a"), Some(source));
+        assert_eq!(loaded.components[&gates[0]].get_delay(), 3);
+        assert_eq!(loaded.components[&led].get_position(), circuit.components[&led].get_position());
+        assert_eq!(loaded.segments.len(), 1);
+    }
+
+    #[test]
+    fn loaded_circuits_simulate_like_the_original() {
+        let (mut circuit, _, _, _) = not_chain(1, &[1, 1, 1]);
+        let mut loaded = Circuit::from_json(&circuit.to_json().unwrap()).unwrap();
+        circuit.simulate().unwrap();
+        loaded.simulate().unwrap();
+        assert_eq!(loaded.led_values(), circuit.led_values());
+    }
+
+    #[test]
+    fn files_are_written_to_disk() {
+        let (circuit, _, _, _) = not_chain(0, &[1]);
+        let path = std::env::temp_dir().join(format!("logic_simulator_test_{}.json", std::process::id()));
+        circuit.save(&path).unwrap();
+        let loaded = Circuit::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap().components.len(), circuit.components.len());
+    }
+
+    #[test]
+    fn components_keep_their_ids() {
+        let (mut circuit, source, gates, led) = not_chain(0, &[1, 1]);
+        circuit.remove_element(gates[0]);
+        let json = circuit.to_json().unwrap();
+        let mut loaded = Circuit::from_json(&json).unwrap();
+
+        let ids: Vec<usize> = loaded.components.keys().copied().collect();
+        assert_eq!(ids, vec![source, gates[1], led]);
+        assert_eq!(loaded.find_source(&format!("in_{}", source)), Some(source));
+        // New components don't reuse the ids of the ones in the file
+        let added = loaded.add_element(LogicElements::Leds(Led::new()));
+        assert_eq!(added, circuit.component_id);
+    }
+
+    #[test]
+    fn wires_keep_the_ids_of_their_pins() {
+        let (mut circuit, source, gates, _) = not_chain(0, &[1, 1]);
+        circuit.remove_element(gates[0]);
+        circuit.wires = vec![Wire { pins: vec![(source, 0, 1), (gates[1], 1, 1)], segments: Vec::new() }];
+        let loaded = Circuit::from_json(&circuit.to_json().unwrap()).unwrap();
+        assert_eq!(loaded.wires[0].pins, circuit.wires[0].pins);
+    }
+
+    #[test]
+    fn duplicate_ids_are_rejected() {
+        let json = r#"{
+            "version": 1,
+            "components": [
+                { "id": 4, "kind": "source", "value": 1, "position": { "x": 0.0, "y": 0.0 }, "delay": 0 },
+                { "id": 4, "kind": "led", "position": { "x": 51.0, "y": 6.0 }, "delay": 0 }
+            ],
+            "segments": [],
+            "wires": []
+        }"#;
+        assert!(matches!(Circuit::from_json(json), Err(FileError::Invalid(_))));
+        let json = json.replacen(r#""id": 4"#, r#""id": 0"#, 1);
+        assert!(matches!(Circuit::from_json(&json), Err(FileError::Invalid(_))));
+    }
+
+    #[test]
+    fn unknown_versions_are_rejected() {
+        let json = format!(r#"{{ "version": {}, "components": [], "segments": [], "wires": [] }}"#, FORMAT_VERSION + 1);
+        assert!(matches!(Circuit::from_json(&json), Err(FileError::UnsupportedVersion(_))));
+        let json = r#"{ "version": 0, "components": [], "segments": [], "wires": [] }"#;
+        assert!(matches!(Circuit::from_json(json), Err(FileError::UnsupportedVersion(0))));
+    }
+}
@@ -0,0 +1,202 @@
+use crate::structure::*;
+use crate::error::SimError;
+use mint::Point2;
+#[cfg(feature = "gui")]
+use ggez::graphics::Image;
+#[cfg(feature = "gui")]
+use ggez::{Context, GameResult};
+
+/// Clock source: the output is high for `duty`% of every `period`, starting `phase` time
+/// units after the beginning of the period
+#[derive(Debug, Clone)]
+pub struct Clock {
+    pub id: usize,
+    pub output: Pin,
+    pub period: u64,
+    pub duty: u64,      // Percentage of the period the output stays high
+    pub phase: u64,
+    pub position: Point2<f32>,
+    #[cfg(feature = "gui")]
+    pub image: Option<Image>,
+    pub hitbox: Hitbox,
+    pub ref_pin_pos: Point2<f32>,
+    pub delay: u64,     // Propagation delay in abstract time units
+}
+
+impl Clock {
+    pub fn new(period: u64, duty: u64, phase: u64) -> Self {
+        Self {
+            id: 0,
+            output: Pin {
+                value: PinValue::Single(Signal::Off),
+                cid: 0, pid: 1, ioc: 0,
+                hitbox: Hitbox {
+                    rect: Rect { x: 70.5, y: 34.5, w: 5.0, h: 5.0 },
+                    r#type: HitboxType::Pin(0, 1, 0),
+                },
+            },
+            // A period shorter than 2 can't have both a high and a low part
+            period: period.max(2),
+            duty: duty.clamp(1, 99),
+            phase,
+            position: Point2 { x: 0.0, y: 0.0 },
+            #[cfg(feature = "gui")]
+            image: None,
+            hitbox: Hitbox {
+                rect: Rect { x: 46.0, y: 27.0, w: 20.0, h: 20.0 },
+                r#type: HitboxType::Component,
+            },
+            ref_pin_pos: Point2 { x: 73.0, y: 37.0 },
+            delay: 0,
+        }
+    }
+
+    // How long the output stays high in every period
+    fn high_time(&self) -> u64 {
+        (self.period * self.duty / 100).clamp(1, self.period - 1)
+    }
+
+    // Position of a point in time inside the period, 0 is the rising edge
+    fn offset(&self, time: u64) -> u64 {
+        (time + self.period - self.phase % self.period) % self.period
+    }
+
+    // Value of the output at the given time
+    pub fn value_at(&self, time: u64) -> Signal {
+        if self.offset(time) < self.high_time() {
+            Signal::On
+        } else {
+            Signal::Off
+        }
+    }
+
+    // Time of the first edge after the given time
+    pub fn next_edge(&self, time: u64) -> u64 {
+        let offset = self.offset(time);
+        if offset < self.high_time() {
+            time + self.high_time() - offset
+        } else {
+            time + self.period - offset
+        }
+    }
+
+    pub fn get_pin(&mut self, pid: usize, ioc: usize) -> Result<&mut Pin, SimError> {
+        match ioc {
+            0 => {
+                if self.output.pid == pid {
+                    Ok(&mut self.output)
+                } else {
+                    Err(SimError::UnknownPin { pin: (self.id, ioc, pid) })
+                }
+            }
+            _ => Err(SimError::UnknownPin { pin: (self.id, ioc, pid) }),
+        }
+    }
+
+    pub fn set_id(&mut self, id: usize) {
+        self.id = id;
+
+        // Assign the cid of the output pin to match self.id
+        self.output.cid = self.id;
+        // Asign the cid for the hitbox as well
+        if let HitboxType::Pin(a, _, _) = &mut self.output.hitbox.r#type {
+            *a = id;
+        }
+    }
+
+    #[cfg(feature = "gui")]
+    pub fn load_clock_image(&mut self, ctx: &mut Context) -> GameResult<()> {
+        let image = Image::from_path(ctx, "/clocks/normal/clock.png")?;
+        self.image = Some(image);
+        Ok(())
+    }
+
+    pub fn update_clock_position(&mut self, position: Point2<f32>) {
+        let (dx, dy) = move_component(&mut self.position, &mut self.hitbox, &mut self.ref_pin_pos, position);
+        move_pins([&mut self.output], dx, dy);
+    }
+
+    pub fn clock_pin_hitbox(&self) -> Vec<Hitbox> {
+        pins_hitbox([&self.output])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::{Circuit, LogicElements};
+    use crate::circuit::tests::attach;
+    use crate::led::Led;
+
+    #[test]
+    fn output_follows_the_duty_cycle() {
+        let clock = Clock::new(10, 30, 0);
+        let values: Vec<Signal> = (0..10).map(|time| clock.value_at(time)).collect();
+        assert_eq!(values.iter().filter(|&&value| value == Signal::On).count(), 3);
+        assert_eq!(values[0], Signal::On);
+        assert_eq!(values[3], Signal::Off);
+        assert_eq!(clock.value_at(10), Signal::On);
+    }
+
+    #[test]
+    fn phase_delays_the_rising_edge() {
+        let clock = Clock::new(10, 50, 4);
+        assert_eq!(clock.value_at(3), Signal::Off);
+        assert_eq!(clock.value_at(4), Signal::On);
+        assert_eq!(clock.value_at(8), Signal::On);
+        assert_eq!(clock.value_at(9), Signal::Off);
+    }
+
+    #[test]
+    fn edges_alternate() {
+        let clock = Clock::new(8, 25, 1);
+        assert_eq!(clock.next_edge(0), 1);
+        assert_eq!(clock.next_edge(1), 3);
+        assert_eq!(clock.next_edge(3), 9);
+        assert_eq!(clock.next_edge(5), 9);
+    }
+
+    #[test]
+    fn degenerate_settings_are_clamped() {
+        let clock = Clock::new(0, 100, 0);
+        assert_eq!(clock.period, 2);
+        assert_eq!(clock.duty, 99);
+        assert_eq!(clock.value_at(0), Signal::On);
+        assert_eq!(clock.value_at(1), Signal::Off);
+    }
+
+    #[test]
+    fn clocks_drive_the_circuit_over_time() {
+        let mut circuit = Circuit::new();
+        let clock = circuit.add_element(LogicElements::Clock(Clock::new(10, 50, 0)));
+        let led = circuit.add_element(LogicElements::Leds(Led::new()));
+        attach(&mut circuit, (clock, 0, 1), (led, 1, 1));
+
+        let mut values = Vec::new();
+        circuit.simulate().unwrap();
+        for _ in 0..4 {
+            circuit.step_clock().unwrap();
+            values.push((circuit.time, circuit.led_values()[0].1.clone()));
+        }
+        assert_eq!(values, vec![
+            (5, PinValue::Single(Signal::Off)),
+            (10, PinValue::Single(Signal::On)),
+            (15, PinValue::Single(Signal::Off)),
+            (20, PinValue::Single(Signal::On)),
+        ]);
+    }
+
+    #[test]
+    fn pins_keep_their_place_on_the_clock() {
+        let mut clock = Clock::new(10, 50, 0);
+        let offset = |clock: &Clock| {
+            let pin = &clock.clock_pin_hitbox()[0].rect;
+            (pin.x - clock.hitbox.rect.x, pin.y - clock.hitbox.rect.y, clock.ref_pin_pos.x - clock.position.x)
+        };
+        let before = offset(&clock);
+        clock.update_clock_position(Point2 { x: 120.0, y: -40.0 });
+        clock.update_clock_position(Point2 { x: 30.0, y: 15.0 });
+        assert_eq!(offset(&clock), before);
+        assert_eq!((clock.hitbox.rect.x, clock.hitbox.rect.y), (76.0, 42.0));
+    }
+}
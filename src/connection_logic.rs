@@ -1,5 +1,4 @@
 use std::collections::{HashMap, HashSet, VecDeque};
-use ggez::graphics::Rect;
 use crate::structure::{Hitbox, HitboxType, UnionFind};
 
 // Insert hitbox in the grid 
@@ -0,0 +1,396 @@
+//! Custom components
+//!
+//! A custom component is described by a JSON file:
+//!
+//! ```json
+//! {
+//!   "name": "Half adder",
+//!   "image": "/custom/half_adder.png",
+//!   "size": { "x": 80.0, "y": 60.0 },
+//!   "pins": [
+//!     { "ioc": 1, "pid": 1, "offset": { "x": 5.0, "y": 20.0 } },
+//!     { "ioc": 1, "pid": 2, "offset": { "x": 5.0, "y": 40.0 } },
+//!     { "ioc": 0, "pid": 1, "offset": { "x": 75.0, "y": 20.0 } },
+//!     { "ioc": 0, "pid": 2, "offset": { "x": 75.0, "y": 40.0 } }
+//!   ],
+//!   "behavior": {
+//!     "truth_table": [
+//!       { "inputs": "00", "outputs": "00" },
+//!       { "inputs": "01", "outputs": "10" },
+//!       { "inputs": "10", "outputs": "10" },
+//!       { "inputs": "11", "outputs": "01" }
+//!     ]
+//!   }
+//! }
+//! ```
+//!
+//! - `image` is optional and relative to the resources folder, `size` is the size of the hitbox.
+//! - `pins` place every pin relative to the top left corner of the component. Pins with
+//!   ioc 0 are outputs, all the others are read as inputs.
+//! - The inputs are ordered by (ioc, pid), the outputs by pid.
+//! - `behavior` is either a `truth_table` or an embedded `circuit` (see `circuit_file`):
+//!   - In a truth table every row lists one character for each input (`0`, `1` or `-` for
+//!     any value) and one for each output (`0`, `1` or `x` for undefined). The first row
+//!     matching the inputs gives the outputs, when no row matches the outputs are undefined.
+//!   - In a circuit the inputs drive the sources and the outputs read the leds, both in the
+//!     order of their ids.
+
+use std::fs;
+use std::path::Path;
+
+#[cfg(feature = "gui")]
+use ggez::graphics::Image;
+use mint::Point2;
+#[cfg(feature = "gui")]
+use ggez::{Context, GameResult};
+use serde::{Deserialize, Serialize};
+
+use crate::circuit::{Circuit, LogicElements};
+use crate::circuit_file::{CircuitFile, FileError, PositionRecord};
+use crate::logic_gates::DEFAULT_GATE_DELAY;
+use crate::structure::*;
+use crate::error::SimError;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PinRecord {
+    pub ioc: usize,
+    pub pid: usize,
+    pub offset: PositionRecord,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TruthTableRow {
+    pub inputs: String,
+    pub outputs: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BehaviorRecord {
+    TruthTable(Vec<TruthTableRow>),
+    Circuit(CircuitFile),
+}
+
+fn default_size() -> PositionRecord {
+    PositionRecord { x: 80.0, y: 60.0 }
+}
+
+/// Description of a custom component as read from its JSON file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CustomDefinition {
+    pub name: String,
+    #[serde(default)]
+    pub image: Option<String>,
+    #[serde(default = "default_size")]
+    pub size: PositionRecord,
+    pub pins: Vec<PinRecord>,
+    pub behavior: BehaviorRecord,
+}
+
+impl CustomDefinition {
+    pub fn from_json(json: &str) -> Result<Self, FileError> {
+        let definition: CustomDefinition = serde_json::from_str(json)?;
+        definition.validate()?;
+        Ok(definition)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, FileError> {
+        CustomDefinition::from_json(&fs::read_to_string(path)?)
+    }
+
+    fn count_pins(&self) -> (usize, usize) {
+        let outputs = self.pins.iter().filter(|pin| pin.ioc == 0).count();
+        (self.pins.len() - outputs, outputs)
+    }
+
+    // Check that the pins and the behavior agree with each other
+    fn validate(&self) -> Result<(), FileError> {
+        let invalid = |reason: String| Err(FileError::Invalid(format!("{}: {}", self.name, reason)));
+
+        for (index, pin) in self.pins.iter().enumerate() {
+            if self.pins[..index].iter().any(|other| other.ioc == pin.ioc && other.pid == pin.pid) {
+                return invalid(format!("pin (ioc {}, pid {}) is defined twice", pin.ioc, pin.pid));
+            }
+        }
+
+        let (inputs, outputs) = self.count_pins();
+        match &self.behavior {
+            BehaviorRecord::TruthTable(rows) => {
+                for row in rows {
+                    if row.inputs.chars().count() != inputs || !row.inputs.chars().all(|c| "01-".contains(c)) {
+                        return invalid(format!("row inputs \"{}\" don't match {} inputs", row.inputs, inputs));
+                    }
+                    if row.outputs.chars().count() != outputs || !row.outputs.chars().all(|c| "01x".contains(c)) {
+                        return invalid(format!("row outputs \"{}\" don't match {} outputs", row.outputs, outputs));
+                    }
+                }
+            }
+            BehaviorRecord::Circuit(file) => {
+                let circuit = file.to_circuit()?;
+                let (sources, leds) = circuit.count_sources_and_leds();
+                if sources != inputs || leds != outputs {
+                    return invalid(format!(
+                        "the circuit has {} sources and {} leds for {} inputs and {} outputs",
+                        sources, leds, inputs, outputs,
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum CustomBehavior {
+    TruthTable(Vec<TruthTableRow>),
+    Circuit(Box<Circuit>),
+}
+
+#[derive(Debug, Clone)]
+pub struct CustomComponent {
+    pub id: usize,
+    pub definition: CustomDefinition,
+    pub behavior: CustomBehavior,
+    pub pins: Vec<Pin>,
+    pub position: Point2<f32>,
+    #[cfg(feature = "gui")]
+    pub image: Option<Image>,
+    pub hitbox: Hitbox,
+    pub ref_pin_pos: Point2<f32>,
+    pub delay: u64,     // Propagation delay in abstract time units
+}
+
+impl CustomComponent {
+    pub fn new(definition: &CustomDefinition) -> Result<Self, FileError> {
+        let behavior = match &definition.behavior {
+            BehaviorRecord::TruthTable(rows) => CustomBehavior::TruthTable(rows.clone()),
+            BehaviorRecord::Circuit(file) => CustomBehavior::Circuit(Box::new(file.to_circuit()?)),
+        };
+
+        // Inputs ordered by (ioc, pid) followed by the outputs ordered by pid
+        let mut records = definition.pins.clone();
+        records.sort_by_key(|pin| (pin.ioc == 0, pin.ioc, pin.pid));
+
+        let pins: Vec<Pin> = records.iter()
+            .map(|record| Pin::new(record.pid, record.ioc, record.offset.x, record.offset.y, PinValue::Single(Signal::Undefined)))
+            .collect();
+
+        Ok(Self {
+            id: 0,
+            definition: definition.clone(),
+            behavior,
+            ref_pin_pos: records.first().map(|pin| pin.offset.into()).unwrap_or(Point2 { x: 0.0, y: 0.0 }),
+            pins,
+            position: Point2 { x: 0.0, y: 0.0 },
+            #[cfg(feature = "gui")]
+            image: None,
+            hitbox: Hitbox {
+                rect: Rect { x: 0.0, y: 0.0, w: definition.size.x, h: definition.size.y },
+                r#type: HitboxType::Component,
+            },
+            delay: DEFAULT_GATE_DELAY,
+        })
+    }
+
+    pub fn set_id(&mut self, id: usize) {
+        self.id = id;
+        set_pins_cid(&mut self.pins, id);
+    }
+
+    pub fn get_pin(&mut self, pid: usize, ioc: usize) -> Result<&mut Pin, SimError> {
+        find_pin(&mut self.pins, self.id, pid, ioc)
+    }
+
+    pub fn get_output(&mut self) {
+        let inputs: Vec<PinValue> = self.pins.iter()
+            .filter(|pin| pin.ioc != 0)
+            .map(|pin| pin.value.clone())
+            .collect();
+
+        let outputs = match &mut self.behavior {
+            CustomBehavior::TruthTable(rows) => {
+                let outputs = self.pins.iter().filter(|pin| pin.ioc == 0).count();
+                evaluate_truth_table(rows, &inputs, outputs)
+            }
+            CustomBehavior::Circuit(circuit) => circuit.evaluate_block(&inputs),
+        };
+
+        for (pin, value) in self.pins.iter_mut().filter(|pin| pin.ioc == 0).zip(outputs) {
+            pin.value = value;
+        }
+    }
+
+    #[cfg(feature = "gui")]
+    pub fn load_custom_image(&mut self, ctx: &mut Context) -> GameResult<()> {
+        if let Some(path) = &self.definition.image {
+            self.image = Some(Image::from_path(ctx, path)?);
+        }
+        Ok(())
+    }
+
+    pub fn update_custom_position(&mut self, position: Point2<f32>) {
+        let (dx, dy) = move_component(&mut self.position, &mut self.hitbox, &mut self.ref_pin_pos, position);
+        move_pins(&mut self.pins, dx, dy);
+    }
+
+    pub fn custom_pins_hitbox(&self) -> Vec<Hitbox> {
+        pins_hitbox(&self.pins)
+    }
+}
+
+// Find the first row matching the inputs, when none matches every output is undefined
+fn evaluate_truth_table(rows: &[TruthTableRow], inputs: &[PinValue], outputs: usize) -> Vec<PinValue> {
+    let matches = |row: &TruthTableRow| {
+        row.inputs.chars().zip(inputs).all(|(c, value)| {
+            matches!((c, value), ('-', _) | ('0', PinValue::Single(Signal::Off)) | ('1', PinValue::Single(Signal::On)))
+        })
+    };
+
+    match rows.iter().find(|row| matches(row)) {
+        Some(row) => row.outputs.chars()
+            .map(|c| PinValue::Single(match c {
+                '0' => Signal::Off,
+                '1' => Signal::On,
+                _ => Signal::Undefined,
+            }))
+            .collect(),
+        None => vec![PinValue::Single(Signal::Undefined); outputs],
+    }
+}
+
+/// The custom component definitions loaded so far, new instances are created from here
+#[derive(Debug, Clone, Default)]
+pub struct ComponentLibrary {
+    pub definitions: Vec<CustomDefinition>,
+}
+
+impl ComponentLibrary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Load a definition from a JSON file, a definition with the same name gets replaced
+    pub fn load(&mut self, path: impl AsRef<Path>) -> Result<&CustomDefinition, FileError> {
+        let definition = CustomDefinition::load(path)?;
+        self.definitions.retain(|other| other.name != definition.name);
+        self.definitions.push(definition);
+        Ok(self.definitions.last().unwrap())
+    }
+
+    // Create a new instance of the component with the given name
+    pub fn instantiate(&self, name: &str) -> Result<LogicElements, SimError> {
+        let definition = self.definitions.iter().find(|definition| definition.name == name)
+            .ok_or_else(|| SimError::UnknownDefinition { name: name.to_string() })?;
+        CustomComponent::new(definition)
+            .map(LogicElements::Custom)
+            .map_err(|error| SimError::InvalidDefinition { name: name.to_string(), reason: error.to_string() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HALF_ADDER: &str = r#"{
+        "name": "Half adder",
+        "pins": [
+            { "ioc": 1, "pid": 1, "offset": { "x": 5.0, "y": 20.0 } },
+            { "ioc": 1, "pid": 2, "offset": { "x": 5.0, "y": 40.0 } },
+            { "ioc": 0, "pid": 1, "offset": { "x": 75.0, "y": 20.0 } },
+            { "ioc": 0, "pid": 2, "offset": { "x": 75.0, "y": 40.0 } }
+        ],
+        "behavior": {
+            "truth_table": [
+                { "inputs": "00", "outputs": "00" },
+                { "inputs": "01", "outputs": "10" },
+                { "inputs": "10", "outputs": "10" },
+                { "inputs": "11", "outputs": "01" }
+            ]
+        }
+    }"#;
+
+    fn single(bit: bool) -> PinValue {
+        PinValue::Single(if bit { Signal::On } else { Signal::Off })
+    }
+
+    fn outputs(component: &CustomComponent) -> Vec<PinValue> {
+        component.pins.iter().filter(|pin| pin.ioc == 0).map(|pin| pin.value.clone()).collect()
+    }
+
+    #[test]
+    fn truth_tables_give_the_outputs() {
+        let definition = CustomDefinition::from_json(HALF_ADDER).unwrap();
+        let mut component = CustomComponent::new(&definition).unwrap();
+        for (a, b) in [(false, false), (false, true), (true, false), (true, true)] {
+            component.get_pin(1, 1).unwrap().value = single(a);
+            component.get_pin(2, 1).unwrap().value = single(b);
+            component.get_output();
+            assert_eq!(outputs(&component), vec![single(a ^ b), single(a && b)]);
+        }
+
+        // No row matches an undefined input
+        component.get_pin(1, 1).unwrap().value = PinValue::Single(Signal::Undefined);
+        component.get_output();
+        assert_eq!(outputs(&component), vec![PinValue::Single(Signal::Undefined); 2]);
+    }
+
+    #[test]
+    fn definitions_not_matching_their_pins_are_rejected() {
+        let json = HALF_ADDER.replace(r#""inputs": "11", "outputs": "01""#, r#""inputs": "1", "outputs": "01""#);
+        assert!(matches!(CustomDefinition::from_json(&json), Err(FileError::Invalid(_))));
+
+        let json = HALF_ADDER.replace(r#""ioc": 1, "pid": 2"#, r#""ioc": 1, "pid": 1"#);
+        assert!(matches!(CustomDefinition::from_json(&json), Err(FileError::Invalid(_))));
+    }
+
+    #[test]
+    fn instances_are_created_from_the_library() {
+        let mut library = ComponentLibrary::new();
+        library.definitions.push(CustomDefinition::from_json(HALF_ADDER).unwrap());
+        assert!(matches!(library.instantiate("Half adder"), Ok(LogicElements::Custom(_))));
+        assert_eq!(
+            library.instantiate("Full adder").err(),
+            Some(SimError::UnknownDefinition { name: "Full adder".to_string() }),
+        );
+    }
+
+    #[test]
+    fn invalid_definitions_report_why() {
+        // A definition embedding a circuit the simulator can't read
+        let mut definition = CustomDefinition::from_json(HALF_ADDER).unwrap();
+        definition.name = "Broken".to_string();
+        definition.behavior = BehaviorRecord::Circuit(CircuitFile {
+            version: 0,
+            components: Vec::new(),
+            segments: Vec::new(),
+            wires: Vec::new(),
+        });
+        let mut library = ComponentLibrary::new();
+        library.definitions.push(definition);
+        assert!(matches!(library.instantiate("Broken"), Err(SimError::InvalidDefinition { name, .. }) if name == "Broken"));
+    }
+
+    #[test]
+    fn embedded_circuits_give_the_outputs() {
+        // An inverter made of a source, a not gate and a led
+        let (circuit, _, _, _) = crate::circuit::tests::not_chain(0, &[1]);
+        let definition = CustomDefinition {
+            name: "Inverter".to_string(),
+            image: None,
+            size: default_size(),
+            pins: vec![
+                PinRecord { ioc: 1, pid: 1, offset: PositionRecord { x: 5.0, y: 30.0 } },
+                PinRecord { ioc: 0, pid: 1, offset: PositionRecord { x: 75.0, y: 30.0 } },
+            ],
+            behavior: BehaviorRecord::Circuit(CircuitFile::from_circuit(&circuit).unwrap()),
+        };
+        definition.validate().unwrap();
+
+        let mut component = CustomComponent::new(&definition).unwrap();
+        for bit in [false, true] {
+            component.get_pin(1, 1).unwrap().value = single(bit);
+            component.get_output();
+            assert_eq!(outputs(&component), vec![single(!bit)]);
+        }
+    }
+}
@@ -0,0 +1,146 @@
+use std::fmt;
+
+use crate::netlist::PinId;
+
+// Write a pin as (cid, ioc, pid)
+fn pin(pin: &PinId) -> String {
+    format!("({}, {}, {})", pin.0, pin.1, pin.2)
+}
+
+/// Problems found while building or simulating a circuit
+#[derive(Debug, Clone, PartialEq)]
+pub enum SimError {
+    // Several drivers of a net drive opposite values
+    MultipleDrivers { net: usize, pins: Vec<PinId> },
+    // A component has no pin with this pid and ioc
+    UnknownPin { pin: PinId },
+    // There is no component with this id, it was deleted
+    UnknownComponent { cid: usize },
+    // The driver of a net and one of its loads don't carry the same number of bits
+    WidthMismatch { net: usize, driver: PinId, load: PinId, expected: usize, found: usize },
+    // These components feed back into their own inputs without any storage element in the loop
+    CombinationalLoop { components: Vec<usize> },
+    // The simulation didn't settle, these nets and the components driving them keep changing
+    Oscillation { nets: Vec<usize>, components: Vec<usize> },
+    // A gate can't be built with this gate type and number of inputs
+    InvalidGate { gate_type: u32, inputs: usize },
+    // Sources can only be 0 or 1
+    InvalidSource { value: usize },
+    // Sources only drive a single bit
+    BusSource { cid: usize },
+    // Nothing can be built from this entry of a component selector
+    UnknownComponentType { name: String },
+    // More input values than input pins were given to a component
+    TooManyInputs { cid: usize, expected: usize, found: usize },
+    // A truth table can't be built for that many sources
+    TooManySources { found: usize, limit: usize },
+    // Nothing drives the net of this input pin
+    FloatingInput { pin: PinId },
+    // The output of this component can't be written as a boolean expression of the sources
+    NoExpression { cid: usize },
+    // No custom component definition with this name was loaded
+    UnknownDefinition { name: String },
+    // The custom component definition can't be turned into a component
+    InvalidDefinition { name: String, reason: String },
+}
+
+impl SimError {
+    // Net the error was found on, for the errors found while propagating a net
+    pub fn net(&self) -> Option<usize> {
+        match self {
+            SimError::MultipleDrivers { net, .. } | SimError::WidthMismatch { net, .. } => Some(*net),
+            _ => None,
+        }
+    }
+
+    // Ids of the components involved in the error, to highlight them
+    pub fn components(&self) -> Vec<usize> {
+        let mut components: Vec<usize> = match self {
+            SimError::MultipleDrivers { pins, .. } => pins.iter().map(|pin| pin.0).collect(),
+            SimError::UnknownPin { pin } => vec![pin.0],
+            SimError::UnknownComponent { cid } | SimError::NoExpression { cid } => vec![*cid],
+            SimError::FloatingInput { pin } => vec![pin.0],
+            SimError::WidthMismatch { driver, load, .. } => vec![driver.0, load.0],
+            SimError::CombinationalLoop { components } => components.clone(),
+            SimError::Oscillation { components, .. } => components.clone(),
+            SimError::TooManyInputs { cid, .. } | SimError::BusSource { cid } => vec![*cid],
+            SimError::InvalidGate { .. }
+            | SimError::InvalidSource { .. }
+            | SimError::UnknownComponentType { .. }
+            | SimError::TooManySources { .. }
+            | SimError::UnknownDefinition { .. }
+            | SimError::InvalidDefinition { .. } => Vec::new(),
+        };
+        components.sort_unstable();
+        components.dedup();
+        components
+    }
+}
+
+impl fmt::Display for SimError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SimError::MultipleDrivers { net, pins } => {
+                let pins: Vec<String> = pins.iter().map(pin).collect();
+                write!(f, "conflict on net {}: pins {} drive opposite values", net, pins.join(", "))
+            }
+            SimError::UnknownPin { pin: unknown } => write!(f, "pin {} doesn't exist", pin(unknown)),
+            SimError::UnknownComponent { cid } => write!(f, "component {} doesn't exist", cid),
+            SimError::WidthMismatch { net, driver, load, expected, found } => write!(
+                f,
+                "width mismatch on net {}: pin {} drives {} bits into pin {} which has {} bits",
+                net, pin(driver), expected, pin(load), found,
+            ),
+            SimError::CombinationalLoop { components } => {
+                let components: Vec<String> = components.iter().map(|cid| cid.to_string()).collect();
+                write!(f, "combinational loop through components {}", components.join(", "))
+            }
+            SimError::Oscillation { nets, .. } => {
+                let nets: Vec<String> = nets.iter().map(|net| net.to_string()).collect();
+                write!(f, "the circuit doesn't settle: nets {} keep toggling", nets.join(", "))
+            }
+            SimError::InvalidGate { gate_type, inputs } => {
+                write!(f, "invalid gate: type {} with {} inputs", gate_type, inputs)
+            }
+            SimError::InvalidSource { value } => write!(f, "invalid source value {}, only 0 and 1 are valid", value),
+            SimError::BusSource { cid } => write!(f, "source {} drives a bus, sources only drive a single bit", cid),
+            SimError::UnknownComponentType { name } => write!(f, "unknown component type {}", name),
+            SimError::TooManyInputs { cid, expected, found } => write!(
+                f,
+                "component {} has {} inputs but {} values were given",
+                cid, expected, found,
+            ),
+            SimError::TooManySources { found, limit } => {
+                write!(f, "the circuit has {} sources, truth tables are limited to {}", found, limit)
+            }
+            SimError::FloatingInput { pin: floating } => write!(f, "nothing drives pin {}", pin(floating)),
+            SimError::NoExpression { cid } => {
+                write!(f, "component {} can't be written as a boolean expression", cid)
+            }
+            SimError::UnknownDefinition { name } => write!(f, "no custom component called {} was loaded", name),
+            SimError::InvalidDefinition { name, reason } => write!(f, "custom component {}: {}", name, reason),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errors_list_the_components_involved_once() {
+        let error = SimError::MultipleDrivers { net: 2, pins: vec![(4, 0, 1), (1, 0, 1), (4, 0, 2)] };
+        assert_eq!(error.components(), vec![1, 4]);
+        let error = SimError::WidthMismatch { net: 0, driver: (3, 0, 1), load: (5, 1, 1), expected: 4, found: 1 };
+        assert_eq!(error.components(), vec![3, 5]);
+        assert!(SimError::UnknownComponentType { name: "Gate".to_string() }.components().is_empty());
+    }
+
+    #[test]
+    fn errors_are_readable() {
+        let error = SimError::UnknownPin { pin: (1, 0, 2) };
+        assert_eq!(error.to_string(), "pin (1, 0, 2) doesn't exist");
+        let error = SimError::UnknownComponentType { name: "Triangle".to_string() };
+        assert_eq!(error.to_string(), "unknown component type Triangle");
+    }
+}
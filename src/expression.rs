@@ -0,0 +1,491 @@
+//! Boolean expressions
+//!
+//! ```text
+//! Y = (A & B) | !C
+//! ```
+//!
+//! - variables are names made of letters, digits and `_`, starting with a letter or `_`.
+//! - `0` and `1` are the constants.
+//! - the operators are, from the highest to the lowest precedence: `!` or `~` (not), `&` or `*`
+//!   (and), `^` (xor) and `|` or `+` (or). Parentheses group sub-expressions.
+//! - the `Y =` part names the output, it is optional.
+
+use std::fmt;
+
+/// Boolean expression, the and, or and xor of several operands are kept in a single node
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Expr {
+    Const(bool),
+    Var(String),
+    Not(Box<Expr>),
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+    Xor(Vec<Expr>),
+}
+
+// Name given to the output when the expression doesn't have one
+pub const DEFAULT_OUTPUT: &str = "Y";
+
+/// Position, counted in characters from 0, and reason of a syntax error
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "column {}: {}", self.position + 1, self.message)
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl Parser {
+    // Next character that isn't a space
+    fn peek(&mut self) -> Option<char> {
+        while self.chars.get(self.position).is_some_and(|c| c.is_whitespace()) {
+            self.position += 1;
+        }
+        self.chars.get(self.position).copied()
+    }
+
+    fn error(&self, message: impl Into<String>) -> ParseError {
+        ParseError { position: self.position, message: message.into() }
+    }
+
+    fn identifier(&mut self) -> Option<String> {
+        if !self.peek().is_some_and(|c| c.is_ascii_alphabetic() || c == '_') {
+            return None;
+        }
+        let start = self.position;
+        while self.chars.get(self.position).is_some_and(|c| c.is_ascii_alphanumeric() || *c == '_') {
+            self.position += 1;
+        }
+        Some(self.chars[start..self.position].iter().collect())
+    }
+
+    // Operands separated by one of the operators, a single operand is returned as it is
+    fn operands(
+        &mut self,
+        operators: &[char],
+        operand: fn(&mut Self) -> Result<Expr, ParseError>,
+        node: fn(Vec<Expr>) -> Expr,
+    ) -> Result<Expr, ParseError> {
+        let mut operands = vec![operand(self)?];
+        while self.peek().is_some_and(|c| operators.contains(&c)) {
+            self.position += 1;
+            operands.push(operand(self)?);
+        }
+        Ok(if operands.len() == 1 { operands.remove(0) } else { node(operands) })
+    }
+
+    fn or(&mut self) -> Result<Expr, ParseError> {
+        self.operands(&['|', '+'], Self::xor, Expr::Or)
+    }
+
+    fn xor(&mut self) -> Result<Expr, ParseError> {
+        self.operands(&['^'], Self::and, Expr::Xor)
+    }
+
+    fn and(&mut self) -> Result<Expr, ParseError> {
+        self.operands(&['&', '*'], Self::unary, Expr::And)
+    }
+
+    fn unary(&mut self) -> Result<Expr, ParseError> {
+        match self.peek() {
+            Some('!' | '~') => {
+                self.position += 1;
+                Ok(Expr::Not(Box::new(self.unary()?)))
+            }
+            Some('(') => {
+                self.position += 1;
+                let expr = self.or()?;
+                if self.peek() != Some(')') {
+                    return Err(self.error("expected )"));
+                }
+                self.position += 1;
+                Ok(expr)
+            }
+            Some('0') => {
+                self.position += 1;
+                Ok(Expr::Const(false))
+            }
+            Some('1') => {
+                self.position += 1;
+                Ok(Expr::Const(true))
+            }
+            Some(c) => match self.identifier() {
+                Some(name) => Ok(Expr::Var(name)),
+                None => Err(self.error(format!("unexpected {}", c))),
+            },
+            None => Err(self.error("unexpected end of the expression")),
+        }
+    }
+}
+
+// Complement of a simplified expression, the complement of an and, or or xor is a single gate
+fn negate(expr: Expr) -> Expr {
+    match expr {
+        Expr::Const(value) => Expr::Const(!value),
+        Expr::Not(inner) => *inner,
+        expr => Expr::Not(Box::new(expr)),
+    }
+}
+
+// Operands of an and (`is_and`) or of an or, none for other nodes
+fn operands_of(expr: &Expr, is_and: bool) -> Option<&Vec<Expr>> {
+    match expr {
+        Expr::And(operands) if is_and => Some(operands),
+        Expr::Or(operands) if !is_and => Some(operands),
+        _ => None,
+    }
+}
+
+// Operands left in an and (`is_and`) or an or once nested nodes of the same kind are merged, the
+// neutral constant and repeated operands dropped. None when the result is the absorbing constant,
+// because of that constant or of an operand with its complement
+fn collapse(operands: Vec<Expr>, is_and: bool) -> Option<Vec<Expr>> {
+    let mut pending = operands;
+    pending.reverse();
+    let mut kept: Vec<Expr> = Vec::new();
+    while let Some(operand) = pending.pop() {
+        if let Some(inner) = operands_of(&operand, is_and) {
+            pending.extend(inner.iter().rev().cloned());
+            continue;
+        }
+        match operand {
+            Expr::Const(value) if value == is_and => {}
+            Expr::Const(_) => return None,
+            operand if kept.contains(&negate(operand.clone())) => return None,
+            operand if !kept.contains(&operand) => kept.push(operand),
+            _ => {}
+        }
+    }
+    Some(kept)
+}
+
+fn build(mut operands: Vec<Expr>, is_and: bool) -> Expr {
+    match operands.len() {
+        0 => Expr::Const(is_and),
+        1 => operands.remove(0),
+        _ if is_and => Expr::And(operands),
+        _ => Expr::Or(operands),
+    }
+}
+
+// Simplified and (`is_and`) or or of simplified operands
+fn and_or(operands: Vec<Expr>, is_and: bool) -> Expr {
+    let Some(kept) = collapse(operands, is_and) else { return Expr::Const(!is_and) };
+
+    // De Morgan: with more complemented operands than others, fewer inverters are needed once
+    // every operand is complemented and the result is inverted by the dual gate
+    let complemented = kept.iter().filter(|operand| matches!(operand, Expr::Not(_))).count();
+    if kept.len() > 1 && complemented * 2 > kept.len() {
+        return match collapse(kept.into_iter().map(negate).collect(), !is_and) {
+            Some(dual) => negate(build(dual, !is_and)),
+            None => Expr::Const(!is_and),
+        };
+    }
+    build(kept, is_and)
+}
+
+// Simplified xor of simplified operands: complements and constants only change the parity, and an
+// operand present twice cancels itself
+fn xor(operands: Vec<Expr>) -> Expr {
+    let mut inverted = false;
+    let mut kept: Vec<Expr> = Vec::new();
+    let mut pending = operands;
+    pending.reverse();
+    while let Some(operand) = pending.pop() {
+        match operand {
+            Expr::Const(value) => inverted ^= value,
+            Expr::Not(inner) => {
+                inverted = !inverted;
+                pending.push(*inner);
+            }
+            Expr::Xor(inner) => pending.extend(inner.into_iter().rev()),
+            operand => match kept.iter().position(|other| *other == operand) {
+                Some(index) => {
+                    kept.remove(index);
+                }
+                None => kept.push(operand),
+            },
+        }
+    }
+    let result = match kept.len() {
+        0 => Expr::Const(false),
+        1 => kept.remove(0),
+        _ => Expr::Xor(kept),
+    };
+    if inverted { negate(result) } else { result }
+}
+
+impl Expr {
+    // Parse an expression without output name
+    pub fn parse(text: &str) -> Result<Expr, ParseError> {
+        let mut parser = Parser { chars: text.chars().collect(), position: 0 };
+        let expr = parser.or()?;
+        match parser.peek() {
+            None => Ok(expr),
+            Some(c) => Err(parser.error(format!("unexpected {}", c))),
+        }
+    }
+
+    // Parse `NAME = EXPRESSION`, the output is called `Y` when there is no name
+    pub fn parse_assignment(text: &str) -> Result<(String, Expr), ParseError> {
+        match text.split_once('=') {
+            Some((name, expr)) => {
+                let output = name.trim();
+                let valid = output.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                    && output.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+                if !valid {
+                    return Err(ParseError { position: 0, message: format!("invalid output name {}", output) });
+                }
+                // Errors are reported at their position in the whole text
+                let offset = name.chars().count() + 1;
+                let expr = Expr::parse(expr).map_err(|error| ParseError { position: error.position + offset, ..error })?;
+                Ok((output.to_string(), expr))
+            }
+            None => Ok((DEFAULT_OUTPUT.to_string(), Expr::parse(text)?)),
+        }
+    }
+
+    // Names of the variables, in the order they first appear in
+    pub fn variables(&self) -> Vec<String> {
+        fn collect(expr: &Expr, variables: &mut Vec<String>) {
+            match expr {
+                Expr::Const(_) => {}
+                Expr::Var(name) => {
+                    if !variables.contains(name) {
+                        variables.push(name.clone());
+                    }
+                }
+                Expr::Not(inner) => collect(inner, variables),
+                Expr::And(operands) | Expr::Or(operands) | Expr::Xor(operands) => {
+                    for operand in operands {
+                        collect(operand, variables);
+                    }
+                }
+            }
+        }
+        let mut variables = Vec::new();
+        collect(self, &mut variables);
+        variables
+    }
+
+    // Value of the expression, `value` gives the value of each variable
+    pub fn evaluate(&self, value: &impl Fn(&str) -> bool) -> bool {
+        match self {
+            Expr::Const(constant) => *constant,
+            Expr::Var(name) => value(name),
+            Expr::Not(inner) => !inner.evaluate(value),
+            Expr::And(operands) => operands.iter().all(|operand| operand.evaluate(value)),
+            Expr::Or(operands) => operands.iter().any(|operand| operand.evaluate(value)),
+            Expr::Xor(operands) => operands.iter().filter(|operand| operand.evaluate(value)).count() % 2 == 1,
+        }
+    }
+
+    //* Equivalent expression needing fewer gates: constants are folded, double negations removed,
+    //* nested operators of the same kind merged, repeated and complementary operands collapsed and
+    //* De Morgan's laws applied when most operands of an and or an or are complemented
+    pub fn simplify(&self) -> Expr {
+        match self {
+            Expr::Const(_) | Expr::Var(_) => self.clone(),
+            Expr::Not(inner) => negate(inner.simplify()),
+            Expr::And(operands) => and_or(operands.iter().map(Expr::simplify).collect(), true),
+            Expr::Or(operands) => and_or(operands.iter().map(Expr::simplify).collect(), false),
+            Expr::Xor(operands) => xor(operands.iter().map(Expr::simplify).collect()),
+        }
+    }
+
+    // Binding strength of the node, to know where parentheses are needed
+    fn precedence(&self) -> u8 {
+        match self {
+            Expr::Or(_) => 0,
+            Expr::Xor(_) => 1,
+            Expr::And(_) => 2,
+            Expr::Not(_) | Expr::Const(_) | Expr::Var(_) => 3,
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Operands binding less strongly than their parent are put between parentheses
+        let operand = |f: &mut fmt::Formatter<'_>, operand: &Expr| {
+            if operand.precedence() <= self.precedence() && operand.precedence() < 3 {
+                write!(f, "({})", operand)
+            } else {
+                write!(f, "{}", operand)
+            }
+        };
+        let operands = |f: &mut fmt::Formatter<'_>, operands: &[Expr], separator: &str| {
+            for (index, expr) in operands.iter().enumerate() {
+                if index > 0 {
+                    write!(f, " {} ", separator)?;
+                }
+                operand(f, expr)?;
+            }
+            Ok(())
+        };
+        match self {
+            Expr::Const(value) => write!(f, "{}", *value as u8),
+            Expr::Var(name) => write!(f, "{}", name),
+            Expr::Not(inner) => {
+                write!(f, "!")?;
+                operand(f, inner)
+            }
+            Expr::And(items) => operands(f, items, "&"),
+            Expr::Or(items) => operands(f, items, "|"),
+            Expr::Xor(items) => operands(f, items, "^"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn var(name: &str) -> Expr {
+        Expr::Var(name.to_string())
+    }
+
+    fn not(expr: Expr) -> Expr {
+        Expr::Not(Box::new(expr))
+    }
+
+    #[test]
+    fn operators_follow_their_precedence() {
+        let expr = Expr::parse("a | b & !c ^ d").unwrap();
+        assert_eq!(expr, Expr::Or(vec![var("a"), Expr::Xor(vec![Expr::And(vec![var("b"), not(var("c"))]), var("d")])]));
+        assert_eq!(Expr::parse("(a | b) & c").unwrap(), Expr::And(vec![Expr::Or(vec![var("a"), var("b")]), var("c")]));
+    }
+
+    #[test]
+    fn operators_have_two_spellings() {
+        assert_eq!(Expr::parse("~a * b + c").unwrap(), Expr::parse("!a & b | c").unwrap());
+    }
+
+    #[test]
+    fn operands_of_the_same_operator_share_a_node() {
+        assert_eq!(Expr::parse("a & b & c").unwrap(), Expr::And(vec![var("a"), var("b"), var("c")]));
+        assert_eq!(Expr::parse("!!a").unwrap(), not(not(var("a"))));
+    }
+
+    #[test]
+    fn names_and_constants_are_read() {
+        assert_eq!(Expr::parse("_x1 | 0 & 1").unwrap(), Expr::Or(vec![var("_x1"), Expr::And(vec![Expr::Const(false), Expr::Const(true)])]));
+    }
+
+    #[test]
+    fn syntax_errors_give_their_position() {
+        let error = |text: &str| Expr::parse(text).unwrap_err();
+        assert_eq!(error("a &").position, 3);
+        assert_eq!(error("a &").message, "unexpected end of the expression");
+        assert_eq!(error("(a | b").message, "expected )");
+        assert_eq!(error("a b").position, 2);
+        assert_eq!(error("a & $").message, "unexpected $");
+        assert_eq!(error("a )").to_string(), "column 3: unexpected )");
+    }
+
+    #[test]
+    fn assignments_name_their_output() {
+        assert_eq!(Expr::parse_assignment("S = a ^ b").unwrap(), ("S".to_string(), Expr::Xor(vec![var("a"), var("b")])));
+        assert_eq!(Expr::parse_assignment("a").unwrap(), (DEFAULT_OUTPUT.to_string(), var("a")));
+        assert!(Expr::parse_assignment("1x = a").is_err());
+        assert!(Expr::parse_assignment(" = a").is_err());
+    }
+
+    #[test]
+    fn assignment_errors_are_placed_in_the_whole_text() {
+        let error = Expr::parse_assignment("Y = a &").unwrap_err();
+        assert_eq!(error.position, 7);
+    }
+
+    #[test]
+    fn parentheses_are_only_written_where_needed() {
+        for text in ["a | b & c", "(a | b) & c", "!(a ^ b) | 1", "a ^ (b | c)", "!!a", "(a & b) & c"] {
+            let expr = Expr::parse(text).unwrap();
+            let written = expr.to_string();
+            assert_eq!(Expr::parse(&written).unwrap(), expr, "{}", written);
+        }
+        assert_eq!(Expr::parse("(a | b) & !c").unwrap().to_string(), "(a | b) & !c");
+        assert_eq!(Expr::parse("a | (b & c)").unwrap().to_string(), "a | b & c");
+    }
+
+    #[test]
+    fn variables_are_listed_once_in_order() {
+        assert_eq!(Expr::parse("b & !a | b ^ c & 1").unwrap().variables(), vec!["b", "a", "c"]);
+        assert!(Expr::parse("1 | 0").unwrap().variables().is_empty());
+    }
+
+    #[test]
+    fn expressions_are_evaluated() {
+        let expr = Expr::parse("a & !b | c ^ 1").unwrap();
+        assert!(expr.evaluate(&|name| name == "a"));
+        assert!(!expr.evaluate(&|name| name != "a"));
+        assert!(Expr::parse("a ^ b ^ c").unwrap().evaluate(&|_| true));
+        assert!(!Expr::parse("a ^ b").unwrap().evaluate(&|_| true));
+    }
+
+    fn simplified(text: &str) -> String {
+        Expr::parse(text).unwrap().simplify().to_string()
+    }
+
+    #[test]
+    fn constants_are_folded() {
+        assert_eq!(simplified("a & 1 | 0"), "a");
+        assert_eq!(simplified("a & 0 | b"), "b");
+        assert_eq!(simplified("a | 1"), "1");
+        assert_eq!(simplified("!1"), "0");
+    }
+
+    #[test]
+    fn double_negations_are_removed() {
+        assert_eq!(simplified("!!a"), "a");
+        assert_eq!(simplified("!!!(a & b)"), "!(a & b)");
+    }
+
+    #[test]
+    fn nested_operators_are_merged_and_repeated_operands_dropped() {
+        assert_eq!(simplified("a & (b & (c & a))"), "a & b & c");
+        assert_eq!(simplified("a | (b | a) | b"), "a | b");
+    }
+
+    #[test]
+    fn complementary_operands_collapse() {
+        assert_eq!(simplified("a & b & !a"), "0");
+        assert_eq!(simplified("!a | b | a"), "1");
+    }
+
+    #[test]
+    fn de_morgan_is_applied_when_most_operands_are_complemented() {
+        assert_eq!(simplified("!a & !b"), "!(a | b)");
+        assert_eq!(simplified("!a | !b | c"), "!(a & b & !c)");
+        assert_eq!(simplified("!a & b"), "!a & b");
+    }
+
+    #[test]
+    fn xors_keep_their_parity() {
+        assert_eq!(simplified("a ^ b ^ a"), "b");
+        assert_eq!(simplified("a ^ 1"), "!a");
+        assert_eq!(simplified("!a ^ !b"), "a ^ b");
+        assert_eq!(simplified("a ^ (b ^ c)"), "a ^ b ^ c");
+    }
+
+    #[test]
+    fn simplified_expressions_keep_their_values() {
+        for text in ["!(a & !b) | (c ^ !a) & 1", "!a & !b & !c | a & (b | !b)", "(a ^ b) ^ !(a | !c)"] {
+            let expr = Expr::parse(text).unwrap();
+            let simplified = expr.simplify();
+            for row in 0..8 {
+                let value = |name: &str| row >> (name.as_bytes()[0] - b'a') & 1 == 1;
+                assert_eq!(simplified.evaluate(&value), expr.evaluate(&value), "{} on row {}", text, row);
+            }
+        }
+    }
+}
@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+
+use crate::buffer::BufferType;
+use crate::circuit::{Circuit, LogicElements};
+use crate::error::SimError;
+use crate::expression::Expr;
+use crate::minimize::BooleanFunction;
+use crate::netlist::{Netlist, PinId};
+use crate::structure::*;
+
+/// Expression of the value read by a led, with the function it computes and its minimal sum of products
+#[derive(Debug, Clone, PartialEq)]
+pub struct LedAnalysis {
+    pub expression: Expr,
+    pub function: BooleanFunction,
+    pub minimized: Expr,
+}
+
+// Walk back from a pin to the sources, through the nets and the gates
+struct Walk<'a> {
+    circuit: &'a Circuit,
+    netlist: &'a Netlist,
+    path: Vec<usize>,               // Components being expressed, to find loops
+    done: HashMap<usize, Expr>,     // Expression of the output of the components already expressed
+}
+
+// A single operand is its own value
+fn operation(mut operands: Vec<Expr>, node: fn(Vec<Expr>) -> Expr) -> Expr {
+    if operands.len() == 1 {
+        operands.remove(0)
+    } else {
+        node(operands)
+    }
+}
+
+impl Walk<'_> {
+    // Expression of the value read by an input pin, from the only driver of its net
+    fn input(&mut self, pin: PinId) -> Result<Expr, SimError> {
+        let net = self.netlist.net_of(pin).ok_or(SimError::FloatingInput { pin })?;
+        match self.netlist.drivers(net) {
+            [] => Err(SimError::FloatingInput { pin }),
+            [driver] => self.output(driver.0),
+            drivers => Err(SimError::MultipleDrivers { net, pins: drivers.to_vec() }),
+        }
+    }
+
+    // Expression of the output of a component
+    fn output(&mut self, cid: usize) -> Result<Expr, SimError> {
+        if let Some(expr) = self.done.get(&cid) {
+            return Ok(expr.clone());
+        }
+        if let Some(start) = self.path.iter().position(|&component| component == cid) {
+            let mut components = self.path[start..].to_vec();
+            components.sort_unstable();
+            return Err(SimError::CombinationalLoop { components });
+        }
+
+        self.path.push(cid);
+        let expr = self.component(cid);
+        self.path.pop();
+        let expr = expr?;
+        self.done.insert(cid, expr.clone());
+        Ok(expr)
+    }
+
+    fn component(&mut self, cid: usize) -> Result<Expr, SimError> {
+        match self.circuit.components.get(&cid) {
+            Some(LogicElements::Source(source)) => Ok(Expr::Var(source.label())),
+            Some(LogicElements::Gates(gate)) if matches!(gate.output.value, PinValue::Single(_)) => {
+                let operands = gate.input.iter()
+                    .map(|pin| self.input((cid, 1, pin.pid)))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let not = |expr: Expr| Expr::Not(Box::new(expr));
+                Ok(match gate.r#type {
+                    LogicGates::And => operation(operands, Expr::And),
+                    LogicGates::Or => operation(operands, Expr::Or),
+                    LogicGates::Xor => operation(operands, Expr::Xor),
+                    LogicGates::Not => not(operation(operands, Expr::And)),
+                    LogicGates::Nand => not(operation(operands, Expr::And)),
+                    LogicGates::Nor => not(operation(operands, Expr::Or)),
+                    LogicGates::Xnor => not(operation(operands, Expr::Xor)),
+                })
+            }
+            // Plain buffers, like the ones bridging wires, copy their input
+            Some(LogicElements::Buffers(buffer))
+                if buffer.r#type == BufferType::Buffer && matches!(buffer.output.value, PinValue::Single(_)) =>
+            {
+                self.input((cid, 1, buffer.input.pid))
+            }
+            Some(_) => Err(SimError::NoExpression { cid }),
+            None => Err(SimError::UnknownComponent { cid }),
+        }
+    }
+}
+
+impl Circuit {
+    //* Boolean expression of the value read by a led, found by walking back through the nets to
+    //* the sources, which are its variables. Only single bit gates and buffers can be crossed
+    pub fn led_expression(&self, cid: usize) -> Result<Expr, SimError> {
+        let pin = match self.components.get(&cid) {
+            Some(LogicElements::Leds(led)) => (cid, 1, led.input.pid),
+            Some(_) => return Err(SimError::NoExpression { cid }),
+            None => return Err(SimError::UnknownComponent { cid }),
+        };
+        let netlist = self.build_netlist();
+        let mut walk = Walk { circuit: self, netlist: &netlist, path: Vec::new(), done: HashMap::new() };
+        walk.input(pin)
+    }
+
+    // Expression of a led, its truth table and its minimal sum of products
+    pub fn analyze_led(&self, cid: usize) -> Result<LedAnalysis, SimError> {
+        let expression = self.led_expression(cid)?;
+        let variables = expression.variables();
+        let function = BooleanFunction::from_expression(&expression, variables)?;
+        let minimized = function.minimize();
+        Ok(LedAnalysis { expression, function, minimized })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::tests::{attach, not_chain, or_not_ring};
+    use crate::led::Led;
+
+    fn led_of(circuit: &Circuit) -> usize {
+        circuit.components.iter()
+            .find_map(|(&cid, component)| matches!(component, LogicElements::Leds(_)).then_some(cid))
+            .unwrap()
+    }
+
+    #[test]
+    fn expressions_go_back_to_the_sources() {
+        let (circuit, source, _, led) = not_chain(0, &[1, 1]);
+        let name = format!("in_{}", source);
+        assert_eq!(circuit.led_expression(led).unwrap(), Expr::parse(&format!("!!{}", name)).unwrap());
+    }
+
+    #[test]
+    fn built_circuits_give_back_their_expression() {
+        // The wires crossing each other go through buffers
+        for text in ["a & !b | c", "!(a ^ b) & (c | !d) | a & b"] {
+            let expr = Expr::parse(text).unwrap();
+            let circuit = Circuit::from_expression("Y", &expr).unwrap();
+            assert_eq!(circuit.led_expression(led_of(&circuit)).unwrap(), expr);
+        }
+    }
+
+    #[test]
+    fn leds_are_analyzed() {
+        let circuit = Circuit::from_expression("Y", &Expr::parse("a & b | a & !b").unwrap()).unwrap();
+        let analysis = circuit.analyze_led(led_of(&circuit)).unwrap();
+        assert_eq!(analysis.function.variables, vec!["a", "b"]);
+        assert_eq!(analysis.function.minterms, vec![2, 3]);
+        assert_eq!(analysis.minimized, Expr::parse("a").unwrap());
+    }
+
+    #[test]
+    fn leds_without_driver_have_no_expression() {
+        let mut circuit = Circuit::new();
+        let led = circuit.add_element(LogicElements::Leds(Led::new()));
+        assert_eq!(circuit.led_expression(led), Err(SimError::FloatingInput { pin: (led, 1, 1) }));
+    }
+
+    #[test]
+    fn only_leds_have_an_expression() {
+        let (circuit, source, _, _) = not_chain(0, &[1]);
+        assert_eq!(circuit.led_expression(source), Err(SimError::NoExpression { cid: source }));
+        assert_eq!(circuit.led_expression(99), Err(SimError::UnknownComponent { cid: 99 }));
+    }
+
+    #[test]
+    fn loops_have_no_expression() {
+        let (mut circuit, _, gates) = or_not_ring(1);
+        let led = circuit.add_element(LogicElements::Leds(Led::new()));
+        attach(&mut circuit, (gates[0], 0, 1), (led, 1, 1));
+        assert_eq!(circuit.led_expression(led), Err(SimError::CombinationalLoop { components: gates.to_vec() }));
+    }
+}
@@ -0,0 +1,271 @@
+#[cfg(feature = "gui")]
+use ggez::graphics::Image;
+use mint::Point2;
+#[cfg(feature = "gui")]
+use ggez::{Context, GameResult};
+use serde::{Deserialize, Serialize};
+
+use crate::logic_gates::DEFAULT_GATE_DELAY;
+use crate::structure::*;
+use crate::error::SimError;
+
+const BLOCK_WIDTH: f32 = 60.0;
+const BLOCK_HEIGHT: f32 = 80.0;
+
+// Control pins (ioc 2)
+pub const CLOCK_PIN: usize = 1;
+pub const SET_PIN: usize = 2;
+pub const RESET_PIN: usize = 3;
+
+// Output pins (ioc 0)
+pub const Q_PIN: usize = 1;
+pub const NOT_Q_PIN: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FlipFlopType {
+    D,
+    T,
+    JK,
+    SR,
+}
+
+impl FlipFlopType {
+    // Names of the data inputs, in the order of their pids
+    pub fn inputs(&self) -> &'static [&'static str] {
+        match self {
+            FlipFlopType::D => &["D"],
+            FlipFlopType::T => &["T"],
+            FlipFlopType::JK => &["J", "K"],
+            FlipFlopType::SR => &["S", "R"],
+        }
+    }
+}
+
+/// Edge triggered flip flop. The data inputs (ioc 1) are sampled on the rising edge of the clock,
+/// the set and reset control pins (ioc 2) are asynchronous and active high.
+/// The outputs are Q and Q̅ (ioc 0), the state is kept between simulations
+#[derive(Debug, Clone)]
+pub struct FlipFlop {
+    pub id: usize,
+    pub r#type: FlipFlopType,
+    pub input: Vec<Pin>,
+    pub control: Vec<Pin>,
+    pub output: Vec<Pin>,
+    pub state: Signal,
+    last_clock: Signal,     // Value of the clock at the last evaluation, to detect the edges
+    pub position: Point2<f32>,
+    #[cfg(feature = "gui")]
+    pub image: Option<Image>,
+    pub hitbox: Hitbox,
+    pub ref_pin_pos: Point2<f32>,
+    pub delay: u64,     // Propagation delay in abstract time units
+}
+
+impl FlipFlop {
+    pub fn new(r#type: FlipFlopType) -> Self {
+        let pin = |pid: usize, ioc: usize, x: f32, y: f32| Pin::new(pid, ioc, x, y, PinValue::Single(Signal::Undefined));
+
+        let mut flip_flop = Self {
+            id: 0,
+            r#type,
+            // Data inputs on the left side, the clock below them
+            input: (1..=r#type.inputs().len()).map(|pid| pin(pid, 1, 0.0, 20.0 * pid as f32)).collect(),
+            // Set on the top side and reset on the bottom side
+            control: vec![
+                pin(CLOCK_PIN, 2, 0.0, 60.0),
+                pin(SET_PIN, 2, BLOCK_WIDTH / 2.0, 0.0),
+                pin(RESET_PIN, 2, BLOCK_WIDTH / 2.0, BLOCK_HEIGHT),
+            ],
+            output: vec![
+                pin(Q_PIN, 0, BLOCK_WIDTH, 20.0),
+                pin(NOT_Q_PIN, 0, BLOCK_WIDTH, 60.0),
+            ],
+            state: Signal::Off,
+            last_clock: Signal::Undefined,
+            position: Point2 { x: 0.0, y: 0.0 },
+            #[cfg(feature = "gui")]
+            image: None,
+            hitbox: Hitbox {
+                rect: Rect { x: 0.0, y: 0.0, w: BLOCK_WIDTH, h: BLOCK_HEIGHT },
+                r#type: HitboxType::Component,
+            },
+            ref_pin_pos: Point2 { x: 0.0, y: 20.0 },
+            delay: DEFAULT_GATE_DELAY,
+        };
+        flip_flop.update_outputs();
+        flip_flop
+    }
+
+    fn pins(&self) -> impl Iterator<Item = &Pin> {
+        self.input.iter().chain(&self.control).chain(&self.output)
+    }
+
+    fn pins_mut(&mut self) -> impl Iterator<Item = &mut Pin> {
+        self.input.iter_mut().chain(&mut self.control).chain(&mut self.output)
+    }
+
+    pub fn set_id(&mut self, id: usize) {
+        self.id = id;
+        set_pins_cid(self.pins_mut(), id);
+    }
+
+    pub fn get_pin(&mut self, pid: usize, ioc: usize) -> Result<&mut Pin, SimError> {
+        let id = self.id;
+        find_pin(self.pins_mut(), id, pid, ioc)
+    }
+
+    // Update the data inputs of the flip flop
+    pub fn set_input(&mut self, signal: Vec<Signal>) {
+        for (pin, value) in self.input.iter_mut().zip(signal) {
+            pin.value = PinValue::Single(value);
+        }
+    }
+
+    // State after a rising edge of the clock
+    fn next_state(&self) -> Signal {
+        let data: Vec<Signal> = self.input.iter().map(Pin::signal).collect();
+        let toggle = !self.state;
+
+        match (self.r#type, data.as_slice()) {
+            (FlipFlopType::D, [d]) => *d,
+            (FlipFlopType::T, [Signal::Off]) => self.state,
+            (FlipFlopType::T, [Signal::On]) => toggle,
+            (FlipFlopType::JK, [Signal::Off, Signal::Off]) => self.state,
+            (FlipFlopType::JK, [Signal::On, Signal::Off]) => Signal::On,
+            (FlipFlopType::JK, [Signal::Off, Signal::On]) => Signal::Off,
+            (FlipFlopType::JK, [Signal::On, Signal::On]) => toggle,
+            (FlipFlopType::SR, [Signal::Off, Signal::Off]) => self.state,
+            (FlipFlopType::SR, [Signal::On, Signal::Off]) => Signal::On,
+            (FlipFlopType::SR, [Signal::Off, Signal::On]) => Signal::Off,
+            // S = R = 1 is forbidden, and any undefined input makes the state unknown
+            _ => Signal::Undefined,
+        }
+    }
+
+    pub fn get_output(&mut self) {
+        let clock = self.control[0].signal();
+        // Unconnected set and reset pins are undefined and never active
+        let set = self.control[1].signal() == Signal::On;
+        let reset = self.control[2].signal() == Signal::On;
+
+        self.state = match (set, reset) {
+            (true, true) => Signal::Undefined,
+            (true, false) => Signal::On,
+            (false, true) => Signal::Off,
+            (false, false) if self.last_clock == Signal::Off && clock == Signal::On => self.next_state(),
+            (false, false) => self.state,
+        };
+        self.last_clock = clock;
+        self.update_outputs();
+    }
+
+    // Show the state on Q and its complement on Q̅
+    fn update_outputs(&mut self) {
+        self.output[0].value = PinValue::Single(self.state);
+        self.output[1].value = PinValue::Single(!self.state);
+    }
+
+    #[cfg(feature = "gui")]
+    pub fn load_flip_flop_image(&mut self, ctx: &mut Context) -> GameResult<()> {
+        let path = match self.r#type {
+            FlipFlopType::D => "/flip_flops/d.png",
+            FlipFlopType::T => "/flip_flops/t.png",
+            FlipFlopType::JK => "/flip_flops/jk.png",
+            FlipFlopType::SR => "/flip_flops/sr.png",
+        };
+        self.image = Some(Image::from_path(ctx, path)?);
+        Ok(())
+    }
+
+    pub fn update_flip_flop_position(&mut self, position: Point2<f32>) {
+        let (dx, dy) = move_component(&mut self.position, &mut self.hitbox, &mut self.ref_pin_pos, position);
+        move_pins(self.pins_mut(), dx, dy);
+    }
+
+    pub fn flip_flop_pins_hitbox(&self) -> Vec<Hitbox> {
+        pins_hitbox(self.pins())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Set the data inputs and the clock, then evaluate the flip flop
+    fn clock(flip_flop: &mut FlipFlop, data: &[Signal], clock: Signal) -> Signal {
+        flip_flop.set_input(data.to_vec());
+        flip_flop.control[0].value = PinValue::Single(clock);
+        flip_flop.get_output();
+        flip_flop.state
+    }
+
+    // A full clock cycle, the data are sampled on its rising edge
+    fn pulse(flip_flop: &mut FlipFlop, data: &[Signal]) -> Signal {
+        clock(flip_flop, data, Signal::Off);
+        clock(flip_flop, data, Signal::On)
+    }
+
+    #[test]
+    fn d_flip_flops_sample_on_the_rising_edge() {
+        let mut flip_flop = FlipFlop::new(FlipFlopType::D);
+        clock(&mut flip_flop, &[Signal::On], Signal::Off);
+        assert_eq!(flip_flop.state, Signal::Off);
+        assert_eq!(clock(&mut flip_flop, &[Signal::On], Signal::On), Signal::On);
+        // No edge while the clock stays high
+        assert_eq!(clock(&mut flip_flop, &[Signal::Off], Signal::On), Signal::On);
+        assert_eq!(clock(&mut flip_flop, &[Signal::Off], Signal::Off), Signal::On);
+        assert_eq!(clock(&mut flip_flop, &[Signal::Off], Signal::On), Signal::Off);
+        assert_eq!(flip_flop.output[0].value, PinValue::Single(Signal::Off));
+        assert_eq!(flip_flop.output[1].value, PinValue::Single(Signal::On));
+    }
+
+    #[test]
+    fn t_and_jk_flip_flops_toggle() {
+        let mut t = FlipFlop::new(FlipFlopType::T);
+        t.state = Signal::Off;
+        assert_eq!(pulse(&mut t, &[Signal::On]), Signal::On);
+        assert_eq!(pulse(&mut t, &[Signal::Off]), Signal::On);
+        assert_eq!(pulse(&mut t, &[Signal::On]), Signal::Off);
+
+        let mut jk = FlipFlop::new(FlipFlopType::JK);
+        assert_eq!(pulse(&mut jk, &[Signal::On, Signal::Off]), Signal::On);
+        assert_eq!(pulse(&mut jk, &[Signal::Off, Signal::Off]), Signal::On);
+        assert_eq!(pulse(&mut jk, &[Signal::On, Signal::On]), Signal::Off);
+        assert_eq!(pulse(&mut jk, &[Signal::Off, Signal::On]), Signal::Off);
+    }
+
+    #[test]
+    fn sr_flip_flops_forbid_setting_and_resetting() {
+        let mut flip_flop = FlipFlop::new(FlipFlopType::SR);
+        assert_eq!(pulse(&mut flip_flop, &[Signal::On, Signal::Off]), Signal::On);
+        assert_eq!(pulse(&mut flip_flop, &[Signal::Off, Signal::On]), Signal::Off);
+        assert_eq!(pulse(&mut flip_flop, &[Signal::On, Signal::On]), Signal::Undefined);
+    }
+
+    #[test]
+    fn set_and_reset_are_asynchronous() {
+        let mut flip_flop = FlipFlop::new(FlipFlopType::D);
+        flip_flop.control[1].value = PinValue::Single(Signal::On);
+        assert_eq!(clock(&mut flip_flop, &[Signal::Off], Signal::Off), Signal::On);
+        flip_flop.control[1].value = PinValue::Single(Signal::Off);
+        flip_flop.control[2].value = PinValue::Single(Signal::On);
+        assert_eq!(clock(&mut flip_flop, &[Signal::On], Signal::Off), Signal::Off);
+        // The reset wins over the clock
+        assert_eq!(clock(&mut flip_flop, &[Signal::On], Signal::On), Signal::Off);
+    }
+
+    #[test]
+    fn pins_follow_the_component() {
+        let mut flip_flop = FlipFlop::new(FlipFlopType::JK);
+        flip_flop.set_id(4);
+        assert_eq!(flip_flop.get_pin(2, 1).unwrap().cid, 4);
+        assert_eq!(flip_flop.get_pin(CLOCK_PIN, 2).unwrap().hitbox.r#type, HitboxType::Pin(4, CLOCK_PIN, 2));
+        assert_eq!(flip_flop.get_pin(3, 1), Err(SimError::UnknownPin { pin: (4, 1, 3) }));
+
+        flip_flop.update_flip_flop_position(Point2 { x: 100.0, y: 50.0 });
+        let q = flip_flop.get_pin(Q_PIN, 0).unwrap().hitbox.rect;
+        assert_eq!((q.x, q.y), (100.0 + BLOCK_WIDTH - 2.5, 50.0 + 20.0 - 2.5));
+        assert_eq!(flip_flop.flip_flop_pins_hitbox().len(), 7);
+    }
+}
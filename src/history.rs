@@ -0,0 +1,269 @@
+use mint::Point2;
+
+use crate::circuit::{Circuit, LogicElements};
+use crate::structure::*;
+
+/// Edits of a circuit that can be undone. Components are identified by their id and segments by
+/// their index, every command stores what is needed to apply it again and to revert it
+#[derive(Debug, Clone)]
+pub enum Command {
+    AddComponent(LogicElements),
+    // The wires are the ones before the removal, they get back the pins of the component
+    RemoveComponent { component: LogicElements, wires: Vec<Wire> },
+    MoveComponent { cid: usize, from: Point2<f32>, to: Point2<f32> },
+    AddSegment { index: usize, segment: WireSegment },
+    RemoveSegment { index: usize, segment: WireSegment },
+    SetDelay { cid: usize, from: u64, to: u64 },
+    SetSourceValue { cid: usize, from: Signal, to: Signal },
+    SetName { cid: usize, from: Option<String>, to: Option<String> },
+}
+
+impl Command {
+    // Command deleting a component of the circuit
+    pub fn remove_component(circuit: &Circuit, cid: usize) -> Option<Command> {
+        Some(Command::RemoveComponent {
+            component: circuit.components.get(&cid)?.clone(),
+            wires: circuit.wires.clone(),
+        })
+    }
+
+    // Command adding a wire segment after the other ones
+    pub fn add_segment(circuit: &Circuit, segment: WireSegment) -> Command {
+        Command::AddSegment { index: circuit.segments.len(), segment }
+    }
+
+    // Command deleting a wire segment of the circuit
+    pub fn remove_segment(circuit: &Circuit, index: usize) -> Option<Command> {
+        Some(Command::RemoveSegment { index, segment: circuit.segments.get(index)?.clone() })
+    }
+
+    pub fn apply(&self, circuit: &mut Circuit) {
+        match self {
+            Command::AddComponent(component) => circuit.insert_element(component.clone()),
+            Command::RemoveComponent { component, .. } => {
+                circuit.remove_element(component.get_id());
+            }
+            Command::MoveComponent { cid, to, .. } => circuit.move_element(*cid, *to),
+            Command::AddSegment { index, segment } => circuit.insert_segment(*index, segment.clone()),
+            Command::RemoveSegment { index, .. } => {
+                circuit.remove_segment(*index);
+            }
+            Command::SetDelay { cid, to, .. } => circuit.set_delay(*cid, *to),
+            Command::SetSourceValue { cid, to, .. } => circuit.set_source_value(*cid, *to),
+            Command::SetName { cid, to, .. } => circuit.set_name(*cid, to.clone()),
+        }
+    }
+
+    // Undo the command, the circuit must be in the state the command left it in
+    pub fn revert(&self, circuit: &mut Circuit) {
+        match self {
+            Command::AddComponent(component) => {
+                circuit.remove_element(component.get_id());
+            }
+            Command::RemoveComponent { component, wires } => {
+                circuit.insert_element(component.clone());
+                circuit.wires = wires.clone();
+            }
+            Command::MoveComponent { cid, from, .. } => circuit.move_element(*cid, *from),
+            Command::AddSegment { index, .. } => {
+                circuit.remove_segment(*index);
+            }
+            Command::RemoveSegment { index, segment } => circuit.insert_segment(*index, segment.clone()),
+            Command::SetDelay { cid, from, .. } => circuit.set_delay(*cid, *from),
+            Command::SetSourceValue { cid, from, .. } => circuit.set_source_value(*cid, *from),
+            Command::SetName { cid, from, .. } => circuit.set_name(*cid, from.clone()),
+        }
+    }
+}
+
+/// Undo and redo stacks of the commands applied to a circuit
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    undo: Vec<Command>,
+    redo: Vec<Command>,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Apply a command to the circuit, the commands undone so far can't be redone anymore
+    pub fn apply(&mut self, circuit: &mut Circuit, mut command: Command) {
+        // A new component gets its id now, so that redoing the command gives it the same id
+        if let Command::AddComponent(component) = &mut command {
+            if component.get_id() == 0 {
+                component.set_id(circuit.component_id);
+            }
+        }
+        command.apply(circuit);
+        self.record(command);
+    }
+
+    // Remember a command that was already applied, like a drag done over several frames
+    pub fn record(&mut self, command: Command) {
+        self.undo.push(command);
+        self.redo.clear();
+    }
+
+    // Revert the last command, returns false when there is nothing to undo
+    pub fn undo(&mut self, circuit: &mut Circuit) -> bool {
+        match self.undo.pop() {
+            Some(command) => {
+                command.revert(circuit);
+                self.redo.push(command);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Apply again the last command undone, returns false when there is nothing to redo
+    pub fn redo(&mut self, circuit: &mut Circuit) -> bool {
+        match self.redo.pop() {
+            Some(command) => {
+                command.apply(circuit);
+                self.undo.push(command);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Forget every command, when the circuit is replaced
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::led::Led;
+    use crate::source::Source;
+
+    // Everything an edit can change, to compare the states of a circuit
+    fn state(circuit: &Circuit) -> String {
+        format!("{:?} {:?} {:?}", circuit.components, circuit.segments, circuit.wires)
+    }
+
+    fn segment(x: f32) -> WireSegment {
+        WireSegment::new(Point2 { x, y: 0.0 }, Point2 { x, y: 50.0 })
+    }
+
+    // A source, a led and two wire segments
+    fn circuit() -> (Circuit, usize, usize) {
+        let mut circuit = Circuit::new();
+        let source = circuit.add_element(LogicElements::Source(Source::new(0).unwrap()));
+        let led = circuit.add_element(LogicElements::Leds(Led::new()));
+        circuit.add_segment(segment(100.0));
+        circuit.add_segment(segment(200.0));
+        (circuit, source, led)
+    }
+
+    // Apply the command, undo it and redo it: the circuit must go back and forth between the states
+    fn round_trip(circuit: &mut Circuit, command: Command) {
+        let mut history = History::new();
+        let before = state(circuit);
+        history.apply(circuit, command);
+        let after = state(circuit);
+        assert_ne!(before, after);
+
+        assert!(history.undo(circuit));
+        assert_eq!(state(circuit), before);
+        assert!(history.redo(circuit));
+        assert_eq!(state(circuit), after);
+        assert!(history.undo(circuit));
+        assert_eq!(state(circuit), before);
+        assert!(!history.undo(circuit));
+    }
+
+    #[test]
+    fn adding_components_round_trips() {
+        let (mut circuit, _, _) = circuit();
+        round_trip(&mut circuit, Command::AddComponent(LogicElements::Leds(Led::new())));
+    }
+
+    #[test]
+    fn added_components_keep_their_id_when_redone() {
+        let (mut circuit, _, _) = circuit();
+        let mut history = History::new();
+        history.apply(&mut circuit, Command::AddComponent(LogicElements::Leds(Led::new())));
+        let ids: Vec<usize> = circuit.components.keys().copied().collect();
+        history.undo(&mut circuit);
+        history.redo(&mut circuit);
+        assert_eq!(circuit.components.keys().copied().collect::<Vec<usize>>(), ids);
+    }
+
+    #[test]
+    fn removing_components_round_trips() {
+        let (mut circuit, source, _) = circuit();
+        let command = Command::remove_component(&circuit, source).unwrap();
+        round_trip(&mut circuit, command);
+        assert!(Command::remove_component(&circuit, 99).is_none());
+    }
+
+    #[test]
+    fn moving_components_round_trips() {
+        let (mut circuit, _, led) = circuit();
+        let from = circuit.components[&led].get_position();
+        round_trip(&mut circuit, Command::MoveComponent { cid: led, from, to: Point2 { x: 40.0, y: 80.0 } });
+    }
+
+    #[test]
+    fn adding_segments_round_trips() {
+        let (mut circuit, _, _) = circuit();
+        let command = Command::add_segment(&circuit, segment(300.0));
+        round_trip(&mut circuit, command);
+    }
+
+    #[test]
+    fn undoing_a_missing_segment_does_nothing() {
+        let mut circuit = Circuit::new();
+        let mut history = History::new();
+        let command = Command::add_segment(&circuit, segment(0.0));
+        history.apply(&mut circuit, command);
+        // The segment went away without going through the history
+        circuit.segments.clear();
+        assert!(history.undo(&mut circuit));
+        assert!(circuit.segments.is_empty());
+    }
+
+    #[test]
+    fn removing_segments_round_trips() {
+        let (mut circuit, _, _) = circuit();
+        let command = Command::remove_segment(&circuit, 0).unwrap();
+        round_trip(&mut circuit, command);
+        assert!(Command::remove_segment(&circuit, 2).is_none());
+    }
+
+    #[test]
+    fn setting_delays_round_trips() {
+        let (mut circuit, _, led) = circuit();
+        let from = circuit.components[&led].get_delay();
+        round_trip(&mut circuit, Command::SetDelay { cid: led, from, to: from + 5 });
+    }
+
+    #[test]
+    fn setting_source_values_round_trips() {
+        let (mut circuit, source, _) = circuit();
+        round_trip(&mut circuit, Command::SetSourceValue { cid: source, from: Signal::Off, to: Signal::On });
+    }
+
+    #[test]
+    fn setting_names_round_trips() {
+        let (mut circuit, source, _) = circuit();
+        round_trip(&mut circuit, Command::SetName { cid: source, from: None, to: Some("A".to_string()) });
+    }
+
+    #[test]
+    fn new_commands_drop_the_undone_ones() {
+        let (mut circuit, source, _) = circuit();
+        let mut history = History::new();
+        history.apply(&mut circuit, Command::SetName { cid: source, from: None, to: Some("A".to_string()) });
+        history.undo(&mut circuit);
+        history.apply(&mut circuit, Command::SetName { cid: source, from: None, to: Some("B".to_string()) });
+        assert!(!history.redo(&mut circuit));
+    }
+}
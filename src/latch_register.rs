@@ -0,0 +1,270 @@
+#[cfg(feature = "gui")]
+use ggez::graphics::Image;
+use mint::Point2;
+#[cfg(feature = "gui")]
+use ggez::{Context, GameResult};
+use serde::{Deserialize, Serialize};
+
+use crate::logic_gates::DEFAULT_GATE_DELAY;
+use crate::structure::*;
+use crate::error::SimError;
+
+const BLOCK_WIDTH: f32 = 60.0;
+const BLOCK_HEIGHT: f32 = 80.0;
+
+// Control pins (ioc 2). Latches only have the enable pin, registers the clock and the load enable
+pub const ENABLE_PIN: usize = 1;
+pub const CLOCK_PIN: usize = 1;
+pub const LOAD_PIN: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LatchRegisterType {
+    SR,
+    D,
+    Register,
+}
+
+/// Level sensitive SR and D latches, and N-bit registers.
+/// - Latches follow their inputs (ioc 1) while the enable pin (ioc 2, pid 1) is high and keep their
+///   state while it is low. The outputs are Q and Q̅ (ioc 0).
+/// - Registers load the bus on their data input (ioc 1) on the rising edge of the clock (ioc 2, pid 1)
+///   when the load enable (ioc 2, pid 2) is high. The output (ioc 0) is a bus of the same width.
+///   A register of a single bit has single bit pins instead of buses.
+#[derive(Debug, Clone)]
+pub struct LatchRegister {
+    pub id: usize,
+    pub r#type: LatchRegisterType,
+    pub bits: usize,
+    pub input: Vec<Pin>,
+    pub control: Vec<Pin>,
+    pub output: Vec<Pin>,
+    pub state: Vec<Signal>,
+    last_clock: Signal,     // Value of the clock at the last evaluation, to detect the edges
+    pub position: Point2<f32>,
+    #[cfg(feature = "gui")]
+    pub image: Option<Image>,
+    pub hitbox: Hitbox,
+    pub ref_pin_pos: Point2<f32>,
+    pub delay: u64,     // Propagation delay in abstract time units
+}
+
+impl LatchRegister {
+    // `bits` is only used by registers, latches always store a single bit
+    pub fn new(r#type: LatchRegisterType, bits: usize) -> Self {
+        let bits = match r#type {
+            LatchRegisterType::Register => bits.max(1),
+            _ => 1,
+        };
+        let single = || PinValue::Single(Signal::Undefined);
+        let bus = || PinValue::from_signals(vec![Signal::Undefined; bits]);
+
+        let (input, control, output) = match r#type {
+            LatchRegisterType::SR | LatchRegisterType::D => {
+                let inputs = if r#type == LatchRegisterType::SR { 2 } else { 1 };
+                (
+                    (1..=inputs).map(|pid| Pin::new(pid, 1, 0.0, 20.0 * pid as f32, single())).collect(),
+                    vec![Pin::new(ENABLE_PIN, 2, 0.0, 60.0, single())],
+                    vec![Pin::new(1, 0, BLOCK_WIDTH, 20.0, single()), Pin::new(2, 0, BLOCK_WIDTH, 60.0, single())],
+                )
+            }
+            LatchRegisterType::Register => (
+                vec![Pin::new(1, 1, 0.0, 20.0, bus())],
+                vec![
+                    Pin::new(CLOCK_PIN, 2, 0.0, 60.0, single()),
+                    Pin::new(LOAD_PIN, 2, BLOCK_WIDTH / 2.0, 0.0, single()),
+                ],
+                vec![Pin::new(1, 0, BLOCK_WIDTH, 20.0, bus())],
+            ),
+        };
+
+        let mut latch_register = Self {
+            id: 0,
+            r#type,
+            bits,
+            input,
+            control,
+            output,
+            state: vec![Signal::Off; bits],
+            last_clock: Signal::Undefined,
+            position: Point2 { x: 0.0, y: 0.0 },
+            #[cfg(feature = "gui")]
+            image: None,
+            hitbox: Hitbox {
+                rect: Rect { x: 0.0, y: 0.0, w: BLOCK_WIDTH, h: BLOCK_HEIGHT },
+                r#type: HitboxType::Component,
+            },
+            ref_pin_pos: Point2 { x: 0.0, y: 20.0 },
+            delay: DEFAULT_GATE_DELAY,
+        };
+        latch_register.update_outputs();
+        latch_register
+    }
+
+    fn pins(&self) -> impl Iterator<Item = &Pin> {
+        self.input.iter().chain(&self.control).chain(&self.output)
+    }
+
+    fn pins_mut(&mut self) -> impl Iterator<Item = &mut Pin> {
+        self.input.iter_mut().chain(&mut self.control).chain(&mut self.output)
+    }
+
+    pub fn set_id(&mut self, id: usize) {
+        self.id = id;
+        set_pins_cid(self.pins_mut(), id);
+    }
+
+    pub fn get_pin(&mut self, pid: usize, ioc: usize) -> Result<&mut Pin, SimError> {
+        let id = self.id;
+        find_pin(self.pins_mut(), id, pid, ioc)
+    }
+
+    // Update the data inputs: one signal for each latch input, or the bits of the register
+    pub fn set_input(&mut self, signal: Vec<Signal>) {
+        match self.r#type {
+            LatchRegisterType::Register => self.input[0].value = PinValue::from_signals(signal),
+            _ => {
+                for (pin, value) in self.input.iter_mut().zip(signal) {
+                    pin.value = PinValue::Single(value);
+                }
+            }
+        }
+    }
+
+    pub fn get_output(&mut self) {
+        match self.r#type {
+            LatchRegisterType::SR | LatchRegisterType::D => {
+                if self.control[0].signal() == Signal::On {
+                    let data: Vec<Signal> = self.input.iter().map(Pin::signal).collect();
+                    self.state[0] = match data.as_slice() {
+                        [d] => *d,
+                        [Signal::Off, Signal::Off] => self.state[0],
+                        [Signal::On, Signal::Off] => Signal::On,
+                        [Signal::Off, Signal::On] => Signal::Off,
+                        // S = R = 1 is forbidden, and any undefined input makes the state unknown
+                        _ => Signal::Undefined,
+                    };
+                }
+            }
+            LatchRegisterType::Register => {
+                let clock = self.control[0].signal();
+                let load = self.control[1].signal();
+                if self.last_clock == Signal::Off && clock == Signal::On && load == Signal::On {
+                    // A bus of the wrong width can't be stored
+                    let data = &self.input[0].value;
+                    self.state = if data.width() == self.bits {
+                        (0..self.bits).map(|bit| data.bit(bit).read()).collect()
+                    } else {
+                        vec![Signal::Undefined; self.bits]
+                    };
+                }
+                self.last_clock = clock;
+            }
+        }
+        self.update_outputs();
+    }
+
+    // Show the state on the outputs, latches also have the complement on Q̅
+    fn update_outputs(&mut self) {
+        match self.r#type {
+            LatchRegisterType::SR | LatchRegisterType::D => {
+                self.output[0].value = PinValue::Single(self.state[0]);
+                self.output[1].value = PinValue::Single(!self.state[0]);
+            }
+            LatchRegisterType::Register => self.output[0].value = PinValue::from_signals(self.state.clone()),
+        }
+    }
+
+    #[cfg(feature = "gui")]
+    pub fn load_latch_register_image(&mut self, ctx: &mut Context) -> GameResult<()> {
+        let path = match self.r#type {
+            LatchRegisterType::SR => "/latches/sr.png",
+            LatchRegisterType::D => "/latches/d.png",
+            LatchRegisterType::Register => "/registers/register.png",
+        };
+        self.image = Some(Image::from_path(ctx, path)?);
+        Ok(())
+    }
+
+    pub fn update_latch_register_position(&mut self, position: Point2<f32>) {
+        let (dx, dy) = move_component(&mut self.position, &mut self.hitbox, &mut self.ref_pin_pos, position);
+        move_pins(self.pins_mut(), dx, dy);
+    }
+
+    pub fn latch_register_pins_hitbox(&self) -> Vec<Hitbox> {
+        pins_hitbox(self.pins())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn control(latch_register: &mut LatchRegister, pid: usize, signal: Signal) {
+        latch_register.control[pid - 1].value = PinValue::Single(signal);
+        latch_register.get_output();
+    }
+
+    // Rising edge of the clock of a register
+    fn clock(register: &mut LatchRegister) {
+        control(register, CLOCK_PIN, Signal::Off);
+        control(register, CLOCK_PIN, Signal::On);
+    }
+
+    #[test]
+    fn d_latches_follow_their_input_while_enabled() {
+        let mut latch = LatchRegister::new(LatchRegisterType::D, 8);
+        assert_eq!(latch.bits, 1);
+        latch.set_input(vec![Signal::On]);
+        control(&mut latch, ENABLE_PIN, Signal::On);
+        assert_eq!(latch.output[0].value, PinValue::Single(Signal::On));
+        assert_eq!(latch.output[1].value, PinValue::Single(Signal::Off));
+
+        control(&mut latch, ENABLE_PIN, Signal::Off);
+        latch.set_input(vec![Signal::Off]);
+        latch.get_output();
+        assert_eq!(latch.state, vec![Signal::On]);
+    }
+
+    #[test]
+    fn sr_latches_keep_their_state() {
+        let mut latch = LatchRegister::new(LatchRegisterType::SR, 1);
+        latch.set_input(vec![Signal::On, Signal::Off]);
+        control(&mut latch, ENABLE_PIN, Signal::On);
+        latch.set_input(vec![Signal::Off, Signal::Off]);
+        latch.get_output();
+        assert_eq!(latch.state, vec![Signal::On]);
+        latch.set_input(vec![Signal::On, Signal::On]);
+        latch.get_output();
+        assert_eq!(latch.state, vec![Signal::Undefined]);
+    }
+
+    #[test]
+    fn registers_load_on_the_clock_edge_when_enabled() {
+        let mut register = LatchRegister::new(LatchRegisterType::Register, 3);
+        register.set_input(vec![Signal::On, Signal::Off, Signal::On]);
+        clock(&mut register);
+        assert_eq!(register.state, vec![Signal::Off; 3]);
+
+        control(&mut register, LOAD_PIN, Signal::On);
+        clock(&mut register);
+        assert_eq!(register.output[0].value, PinValue::Multiple(vec![Signal::On, Signal::Off, Signal::On]));
+
+        // A bus of the wrong width loads undefined bits
+        register.set_input(vec![Signal::On]);
+        clock(&mut register);
+        assert_eq!(register.state, vec![Signal::Undefined; 3]);
+    }
+
+    #[test]
+    fn single_bit_registers_have_single_bit_pins() {
+        let mut register = LatchRegister::new(LatchRegisterType::Register, 1);
+        assert_eq!(register.input[0].value, PinValue::Single(Signal::Undefined));
+        assert_eq!(register.output[0].value, PinValue::Single(Signal::Off));
+
+        control(&mut register, LOAD_PIN, Signal::On);
+        register.input[0].value = PinValue::Single(Signal::On);
+        clock(&mut register);
+        assert_eq!(register.output[0].value, PinValue::Single(Signal::On));
+    }
+}
@@ -1,24 +1,37 @@
 use std::collections::HashMap;
 
 use crate::structure::*;
-use ggez::mint::Point2;
-use ggez::graphics::{Image, Rect};
+use crate::error::SimError;
+use mint::Point2;
+#[cfg(feature = "gui")]
+use ggez::graphics::Image;
+#[cfg(feature = "gui")]
 use ggez::{Context, GameResult};
 use multimap::MultiMap;
 
 #[derive(Debug, Clone)]
 pub struct Led {
     pub id: usize,
+    pub name: Option<String>,   // Name under which the value is printed by the command line runner
     pub input: Pin,
     pub position: Point2<f32>,
+    #[cfg(feature = "gui")]
     pub image: Option<Image>, 
     pub hitbox: Hitbox,
     pub ref_pin_pos: Point2<f32>,  
+    pub delay: u64,     // Propagation delay in abstract time units
 }
+impl Default for Led {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Led {
     pub fn new() -> Self{
         Self {
             id: 0,
+            name: None,
             input: Pin { 
                 value: PinValue::Single(Signal::Undefined),
                 cid: 0, pid: 1, ioc: 1,
@@ -28,15 +41,22 @@ impl Led {
                     },
                 },
             position: Point2 { x: 0.0, y: 0.0 },
+            #[cfg(feature = "gui")]
             image: None,
             hitbox: Hitbox {
                     rect: Rect { x: 32.0, y: 20.0, w: 20.0, h: 20.0 },
                     r#type: HitboxType::Component
                 },
             ref_pin_pos: Point2{ x: 25.0, y: 30.0},
+            delay: 0,
         }
     }
 
+    // Name of the led, `out_<id>` when it wasn't given one like the ports of the Verilog export
+    pub fn label(&self) -> String {
+        self.name.clone().unwrap_or_else(|| format!("out_{}", self.id))
+    }
+
     pub fn set_id(&mut self, id: usize) {
         self.id = id;
     
@@ -48,6 +68,7 @@ impl Led {
         }
     }
 
+    #[cfg(feature = "gui")]
     pub fn load_led_image(&mut self, ctx: &mut Context) -> GameResult<()> {
         let path = "/leds/normal/led_undefined.png";
         let image = Image::from_path(ctx, path)?; 
@@ -55,17 +76,17 @@ impl Led {
         Ok(())
     }
 
-    pub fn get_pin(&mut self, pid: usize, ioc: usize) -> &mut Pin {
+    pub fn get_pin(&mut self, pid: usize, ioc: usize) -> Result<&mut Pin, SimError> {
         match ioc {
             1 => {
                 // For input pins (ioc == 1), the `id` should be checked against the `output` pin's `pid`.
                 if self.input.pid == pid {
-                    &mut self.input 
+                    Ok(&mut self.input)
                 } else {
-                    panic!("input pin with id {} not found", pid);
+                    Err(SimError::UnknownPin { pin: (self.id, ioc, pid) })
                 }
             }
-            _ => panic!("Invalid ioc value: {}. Only 0 (output) is valid.", ioc),
+            _ => Err(SimError::UnknownPin { pin: (self.id, ioc, pid) }),
         }
     }
 
@@ -95,13 +116,14 @@ impl Led {
         vec![pin.hitbox.clone()]
     }
 
+    #[cfg(feature = "gui")]
     pub fn update_led_image(&mut self, ctx: &mut Context) -> Option<Image> {
         match self.input.value {
             PinValue::Single(signal) => {
                 let path = match signal {
                     Signal::On => "/leds/normal/led_high_green.png",
                     Signal::Off => "/leds/normal/led_low.png",
-                    Signal::Undefined => "/leds/normal/led_undefined.png",
+                    Signal::Undefined | Signal::HighZ | Signal::Conflict => "/leds/normal/led_undefined.png",
                 };
                 
                 // Try loading the image and return Some(image) or None if it fails
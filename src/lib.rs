@@ -0,0 +1,30 @@
+//! Logic circuit simulator: the components, the circuits they are placed in, the event driven
+//! simulation and the circuit files. Used by the editor and by the `rusty-sim` command line runner.
+
+pub mod circuit;
+pub mod logic_gates;
+pub mod source;
+pub mod structure;
+pub mod connection_logic;
+pub mod led;
+pub mod simulation;
+pub mod netlist;
+pub mod circuit_file;
+pub mod verilog;
+pub mod custom;
+pub mod subcircuit;
+pub mod clock;
+pub mod flip_flop;
+pub mod latch_register;
+pub mod multiplexer;
+pub mod adder;
+pub mod shift_register;
+pub mod buffer;
+pub mod error;
+pub mod history;
+pub mod truth_table;
+pub mod expression;
+pub mod synthesis;
+pub mod minimize;
+pub mod extraction;
+pub mod optimize;
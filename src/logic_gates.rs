@@ -1,8 +1,14 @@
 use std::{clone, collections::HashMap, default, vec};
 use crate::structure::*;
-use ggez::{graphics::{Image, Rect}, mint::Point2, Context, GameResult};
+use crate::error::SimError;
+#[cfg(feature = "gui")]
+use ggez::{graphics::Image, Context, GameResult};
+use mint::Point2;
 use multimap::MultiMap;
 
+/// Propagation delay given to newly created gates
+pub const DEFAULT_GATE_DELAY: u64 = 1;
+
 // Logic gate structure
 #[derive(Debug)]
 pub struct LogicGate {
@@ -12,14 +18,30 @@ pub struct LogicGate {
     pub r#type: LogicGates,
     pub id: usize,
     pub position: Point2<f32>, 
+    #[cfg(feature = "gui")]
     pub image: Option<Image>, 
     pub hitbox: Hitbox,
     pub ref_pin_pos: Point2<f32>,
+    pub delay: u64,     // Propagation delay in abstract time units
 }
 
 impl LogicGate {
     // Constructor to build a new logic gate
-    pub fn new_gate(gate_type: u32, num_inputs: usize, bus: bool, bits:usize) -> LogicGate {
+    pub fn new_gate(gate_type: u32, num_inputs: usize, bus: bool, bits:usize) -> Result<LogicGate, SimError> {
+        let r#type = match gate_type {
+            0 => LogicGates::And,
+            1 => LogicGates::Or,
+            2 => LogicGates::Not,
+            3 => LogicGates::Nand,
+            4 => LogicGates::Nor,
+            5 => LogicGates::Xor,
+            6 => LogicGates::Xnor,
+            _ => return Err(SimError::InvalidGate { gate_type, inputs: num_inputs }),
+        };
+        // The not gate requires exactly one input, the others at least one
+        if num_inputs == 0 || (r#type == LogicGates::Not && num_inputs != 1) {
+            return Err(SimError::InvalidGate { gate_type, inputs: num_inputs });
+        }
 
         let gate = if !bus {
             // Create a simple logic gate
@@ -62,21 +84,14 @@ impl LogicGate {
                     }
                 },
                 num_input: num_inputs,
-                r#type: match gate_type {
-                    0 => LogicGates::And,
-                    1 => LogicGates::Or,
-                    2 => LogicGates::Not,
-                    3 => LogicGates::Nand,
-                    4 => LogicGates::Nor,
-                    5 => LogicGates::Xor,
-                    6 => LogicGates::Xnor,
-                    _ => panic!("Invalid gate"),
-                },
+                r#type: r#type.clone(),
                 id: 0,
                 position: Point2 { x: 0.0, y: 0.0 },
+                #[cfg(feature = "gui")]
                 image: None,
                 hitbox: Hitbox{rect: Rect{ x: 0.0, y: 0.0, w: 50.0, h: 50.0 }, r#type: HitboxType::Component},
                 ref_pin_pos: Point2 { x: 6.0, y: 25.0 },
+                delay: DEFAULT_GATE_DELAY,
             }
             
         } else {
@@ -121,179 +136,97 @@ impl LogicGate {
                     }
                 },
                 num_input: num_inputs,
-                r#type: match gate_type {
-                    0 => LogicGates::And,
-                    1 => LogicGates::Or,
-                    2 => LogicGates::Not,
-                    3 => LogicGates::Nand,
-                    4 => LogicGates::Nor,
-                    5 => LogicGates::Xor,
-                    6 => LogicGates::Xnor,
-                    _ => panic!("Invalid input for logic gate"),
-                },
+                r#type: r#type.clone(),
                 id: 0,
                 position: Point2 { x: 0.0, y: 0.0 },
+                #[cfg(feature = "gui")]
                 image: None,
                 hitbox: Hitbox{rect: Rect{ x: 0.0, y: 0.0, w: 50.0, h: 50.0 }, r#type: HitboxType::Component},
                 ref_pin_pos: Point2 { x: 6.0, y: 25.0 },
+                delay: DEFAULT_GATE_DELAY,
             }
         };
-        gate
+        Ok(gate)
     }
 
     // Update the input of the gate
-    pub fn set_input(&mut self, mut signals: Vec<Signal>) {
+    pub fn set_input(&mut self, mut signals: Vec<Signal>) -> Result<(), SimError> {
         // Fill any missing signals with Signal::Undefined
         if signals.len() < self.num_input {
             signals.resize(self.num_input, Signal::Undefined);
         } else if signals.len() > self.num_input {
-            panic!("Too many signals provided for the number of input pins.");
+            return Err(SimError::TooManyInputs { cid: self.id, expected: self.num_input, found: signals.len() });
         }
 
         for (i, signal) in signals.into_iter().enumerate() {
             self.input[i].value = PinValue::Single(signal);
         }
+        Ok(())
     }
 
     // Set input for bus logic gates (each pin has a vector of signals)
-    pub fn set_bus_input(&mut self, mut signals: Vec<Vec<Signal>>, bits: usize) {
+    pub fn set_bus_input(&mut self, mut signals: Vec<Vec<Signal>>, bits: usize) -> Result<(), SimError> {
         // Fill any missing input buses with `Signal::Undefined` of the correct length (number of bits)
         if signals.len() < self.input.len() {
             signals.resize(self.input.len(), vec![Signal::Undefined; bits]);
         } else if signals.len() > self.input.len() {
-            panic!("Too many signal vectors provided for the number of input pins.");
+            return Err(SimError::TooManyInputs { cid: self.id, expected: self.input.len(), found: signals.len() });
         }
 
         for (i, signal_vec) in signals.into_iter().enumerate() {
             // Set each pin's connection to a bus with the provided signal vector
             self.input[i].value = PinValue::Multiple(signal_vec);
         }
+        Ok(())
     }
     
 
-    // Get the output of a gate
-    pub fn get_output(&mut self) {
-        // Track some conditions for each gate
-        let mut all_on = true;      // For AND/NAND
-        let mut all_off = true;     // For NOR
-        let mut any_on = false;     // For OR/XOR
-        let mut on_count = 0;       // For XOR/XNOR
-        let mut any_undefined = false; // To handle Undefined signals
-    
-        // Go through all input pins and check their signals
-        for pin in 0..self.num_input {
-            match self.input[pin].value {
-                //* Check if it's a 1 bit gate
-                PinValue::Single(signal) => {
-                    match signal {
-                        Signal::On => {
-                            // For AND/NAND and NOR
-                            all_off = false;   // Not all off for NOR
-                            on_count += 1;     // For XOR/XNOR
-                            any_on = true;     // For OR/XOR
-                        }
-                        Signal::Off => {
-                            // For AND/NAND: Check if all inputs are off
-                            all_on = false;    // Not all on for AND/NAND
-                            all_off = true;    // For NOR
-                        }
-                        Signal::Undefined => {
-                            any_undefined = true;  // At least one input is Undefined
-                            all_on = false;
-                            all_off = false;
-                        }
-                    }
-                }
-                _ => {
-                    todo!()
-                }
+    // Value of the gate for a single bit, `signals` holds that bit of every input
+    fn evaluate_bit(&self, signals: &[Signal]) -> Signal {
+        let on_count = signals.iter().filter(|signal| **signal == Signal::On).count();  // For OR/XOR/XNOR
+        let any_undefined = signals.contains(&Signal::Undefined);                     // To handle Undefined signals
+        let all_on = on_count == signals.len();                                       // For AND/NAND
+        let all_off = on_count == 0 && !any_undefined;                                // For NOR
+
+        // Choose between the value of the gate when the condition holds, Undefined and the opposite value
+        let output = |condition: bool, value: Signal, opposite: Signal| {
+            if condition {
+                value
+            } else if any_undefined {
+                Signal::Undefined
+            } else {
+                opposite
             }
-        }
-    
-        // Now apply the logic gate
+        };
+
         match self.r#type {
-            LogicGates::And => {
-                if all_on {
-                    self.output.value = PinValue::Single(Signal::On);
-                } else if any_undefined {
-                    self.output.value = PinValue::Single(Signal::Undefined);
-                } else {
-                    self.output.value = PinValue::Single(Signal::Off);
-                }
-            }
-            
-            LogicGates::Or => {
-                if any_on {
-                    self.output.value = PinValue::Single(Signal::On);
-                } else if any_undefined {
-                    self.output.value = PinValue::Single(Signal::Undefined);
-                } else {
-                    self.output.value = PinValue::Single(Signal::Off);
-                }
-            }
-    
-            LogicGates::Not => {
-                // The gate requires only one input
-                if self.num_input != 1 {
-                    panic!("Not gate requires exactly one input");
-                }
-    
-                match self.input[0].value {
-                    PinValue::Single(signal) => {
-                        self.output.value = match signal {
-                            Signal::On => PinValue::Single(Signal::Off),
-                            Signal::Off => PinValue::Single(Signal::On),
-                            Signal::Undefined => PinValue::Single(Signal::Undefined),
-                        };
-                    }
-                    _ => {
-                        panic!("Unexpected connection type for Not gate, expected Simple.");
-                    }
-                }
-            }
-    
-            LogicGates::Nand => {
-                if all_on {
-                    self.output.value = PinValue::Single(Signal::Off);
-                } else if any_undefined {
-                    self.output.value = PinValue::Single(Signal::Undefined);
-                } else {
-                    self.output.value = PinValue::Single(Signal::On);
-                }
-            }
-    
-            LogicGates::Nor => {
-                if all_off {
-                    self.output.value = PinValue::Single(Signal::On);
-                } else if any_undefined {
-                    self.output.value = PinValue::Single(Signal::Undefined);
-                } else {
-                    self.output.value = PinValue::Single(Signal::Off);
-                }
-            }
-    
-            LogicGates::Xor => {
-                if on_count % 2 == 1 {
-                    self.output.value = PinValue::Single(Signal::On);
-                } else if any_undefined {
-                    self.output.value = PinValue::Single(Signal::Undefined);
-                } else {
-                    self.output.value = PinValue::Single(Signal::Off);
-                }
-            }
-    
-            LogicGates::Xnor => {
-                if on_count % 2 == 0 {
-                    self.output.value = PinValue::Single(Signal::On);
-                } else if any_undefined {
-                    self.output.value = PinValue::Single(Signal::Undefined);
-                } else {
-                    self.output.value = PinValue::Single(Signal::Off);
-                }
-            }
-    
-            _ => panic!("Gate type not handled"),
+            LogicGates::And => output(all_on, Signal::On, Signal::Off),
+            LogicGates::Or => output(on_count > 0, Signal::On, Signal::Off),
+            LogicGates::Not => !signals[0],
+            LogicGates::Nand => output(all_on, Signal::Off, Signal::On),
+            LogicGates::Nor => output(all_off, Signal::On, Signal::Off),
+            LogicGates::Xor => output(on_count % 2 == 1, Signal::On, Signal::Off),
+            LogicGates::Xnor => output(on_count % 2 == 0, Signal::On, Signal::Off),
+        }
+    }
+
+    // Get the output of a gate, bus gates are evaluated bit by bit
+    pub fn get_output(&mut self) {
+        // Inputs without the width of the output can't be evaluated
+        let width = self.output.value.width();
+        if self.input.iter().any(|pin| pin.value.width() != width) {
+            self.output.value = self.output.value.to_undefined();
+            return;
         }
+
+        let bit = |index: usize| {
+            let signals: Vec<Signal> = self.input.iter().map(|pin| pin.value.bit(index).read()).collect();
+            self.evaluate_bit(&signals)
+        };
+        self.output.value = match self.output.value {
+            PinValue::Single(_) => PinValue::Single(bit(0)),
+            PinValue::Multiple(_) => PinValue::Multiple((0..width).map(bit).collect()),
+        };
     }
 
     pub fn set_gate_id(&mut self, id: usize) {
@@ -316,56 +249,42 @@ impl LogicGate {
         }
     }
 
-    pub fn get_pin(&mut self, pid: usize, ioc: usize) -> &mut Pin {
+    pub fn get_pin(&mut self, pid: usize, ioc: usize) -> Result<&mut Pin, SimError> {
+        let id = self.id;
         match ioc {
             0 => {
                 // For output pins (ioc == 0), the `id` should be checked against the `output` pin's `pid`.
                 if self.output.pid == pid {
-                    &mut self.output 
+                    Ok(&mut self.output)
                 } else {
-                    panic!("Output pin with id {} not found", pid);
+                    Err(SimError::UnknownPin { pin: (id, ioc, pid) })
                 }
             }
             1 => {
                 // For input pins (ioc == 1), search the `input` vector for a pin with the matching `pid`.
                 self.input.iter_mut().find(|pin| pin.pid == pid)
-                    .expect(&format!("Input pin with id {} not found", pid))
+                    .ok_or(SimError::UnknownPin { pin: (id, ioc, pid) })
             }
-            _ => panic!("Invalid ioc value: {}. Only 0 (output) and 1 (input) are valid.", ioc),
+            _ => Err(SimError::UnknownPin { pin: (id, ioc, pid) }),
+        }
+    }
+
+    // Image of the gate, bus gates don't have images of their own and use the single bit ones
+    pub fn image_path(&self) -> &'static str {
+        match self.r#type {
+            LogicGates::And => "/gates/normal/input2/and.png",
+            LogicGates::Or => "/gates/normal/input2/or.png",
+            LogicGates::Not => "/gates/normal/input2/not.png",
+            LogicGates::Nand => "/gates/normal/input2/nand.png",
+            LogicGates::Nor => "/gates/normal/input2/nor.png",
+            LogicGates::Xor => "/gates/normal/input2/xor.png",
+            LogicGates::Xnor => "/gates/normal/input2/xnor.png",
         }
     }
 
+    #[cfg(feature = "gui")]
     pub fn load_gate_image(&mut self, ctx: &mut Context) -> GameResult<()> {
-        let image_path = match self.r#type {
-            LogicGates::And => match self.output.value {
-                PinValue::Single(_) => "/gates/normal/input2/and.png",
-                PinValue::Multiple(_) => "/path/to/and_multiple.png",
-            },
-            LogicGates::Or => match self.output.value {
-                PinValue::Single(_) => "/gates/normal/input2/or.png",
-                PinValue::Multiple(_) => "/path/to/or_multiple.png",
-            },
-            LogicGates::Not => match self.output.value {
-                PinValue::Single(_) => "/gates/normal/input2/not.png",
-                PinValue::Multiple(_) => "/path/to/not_multiple.png",
-            },
-            LogicGates::Nand => match self.output.value {
-                PinValue::Single(_) => "/gates/normal/input2/nand.png",
-                PinValue::Multiple(_) => "/path/to/nand_multiple.png",
-            },
-            LogicGates::Nor => match self.output.value {
-                PinValue::Single(_) => "/gates/normal/input2/nor.png",
-                PinValue::Multiple(_) => "/path/to/nor_multiple.png",
-            },
-            LogicGates::Xor => match self.output.value {
-                PinValue::Single(_) => "/gates/normal/input2/xor.png",
-                PinValue::Multiple(_) => "/path/to/xor_multiple.png",
-            },
-            LogicGates::Xnor => match self.output.value {
-                PinValue::Single(_) => "/gates/normal/input2/xnor.png",
-                PinValue::Multiple(_) => "/path/to/xnor_multiple.png",
-            },
-        };
+        let image_path = self.image_path();
 
         // Load the gate image
         let image = Image::from_path(ctx, image_path)?;
@@ -432,9 +351,11 @@ impl clone::Clone for LogicGate {
             r#type: self.r#type.clone(),
             id: self.id.clone(),
             position: self.position.clone(),
+            #[cfg(feature = "gui")]
             image: self.image.clone(),
             hitbox: self.hitbox.clone(),
             ref_pin_pos: self.ref_pin_pos.clone(),
+            delay: self.delay,
         }
     }
     
@@ -450,3 +371,86 @@ impl PartialEq for LogicGate {
     }
     
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Signal::{Off, On, Undefined};
+
+    fn bus_gate(gate_type: u32, inputs: &[&[Signal]]) -> LogicGate {
+        let mut gate = LogicGate::new_gate(gate_type, inputs.len(), true, inputs[0].len()).unwrap();
+        gate.set_bus_input(inputs.iter().map(|input| input.to_vec()).collect(), inputs[0].len()).unwrap();
+        gate.get_output();
+        gate
+    }
+
+    #[test]
+    fn gates_follow_their_truth_table() {
+        let outputs: Vec<Vec<Signal>> = (0..7)
+            .filter(|&gate_type| gate_type != 2)
+            .map(|gate_type| {
+                [[Off, Off], [Off, On], [On, Off], [On, On]].iter()
+                    .map(|inputs| {
+                        let mut gate = LogicGate::new_gate(gate_type, 2, false, 1).unwrap();
+                        gate.set_input(inputs.to_vec()).unwrap();
+                        gate.get_output();
+                        gate.output.value.bit(0)
+                    })
+                    .collect()
+            })
+            .collect();
+        assert_eq!(outputs, vec![
+            vec![Off, Off, Off, On],    // And
+            vec![Off, On, On, On],      // Or
+            vec![On, On, On, Off],      // Nand
+            vec![On, Off, Off, Off],    // Nor
+            vec![Off, On, On, Off],     // Xor
+            vec![On, Off, Off, On],     // Xnor
+        ]);
+    }
+
+    #[test]
+    fn undefined_inputs_make_the_output_undefined_unless_it_is_forced() {
+        let mut or = LogicGate::new_gate(1, 2, false, 1).unwrap();
+        or.set_input(vec![On, Undefined]).unwrap();
+        or.get_output();
+        assert_eq!(or.output.value, PinValue::Single(On));
+        or.set_input(vec![Off, Undefined]).unwrap();
+        or.get_output();
+        assert_eq!(or.output.value, PinValue::Single(Undefined));
+    }
+
+    #[test]
+    fn bus_gates_work_bit_by_bit() {
+        let gate = bus_gate(5, &[&[On, On, Off], &[On, Off, Off]]);
+        assert_eq!(gate.output.value, PinValue::Multiple(vec![Off, On, Off]));
+        let gate = bus_gate(2, &[&[On, Undefined]]);
+        assert_eq!(gate.output.value, PinValue::Multiple(vec![Off, Undefined]));
+    }
+
+    #[test]
+    fn bus_gates_need_inputs_of_their_width() {
+        let mut gate = LogicGate::new_gate(1, 2, true, 3).unwrap();
+        gate.set_bus_input(vec![vec![On, On, On], vec![On, On]], 3).unwrap();
+        gate.get_output();
+        assert_eq!(gate.output.value, PinValue::Multiple(vec![Undefined; 3]));
+    }
+
+    #[test]
+    fn invalid_gates_are_rejected() {
+        assert_eq!(LogicGate::new_gate(7, 2, false, 1).err(), Some(SimError::InvalidGate { gate_type: 7, inputs: 2 }));
+        assert_eq!(LogicGate::new_gate(2, 2, false, 1).err(), Some(SimError::InvalidGate { gate_type: 2, inputs: 2 }));
+        assert_eq!(LogicGate::new_gate(0, 0, true, 4).err(), Some(SimError::InvalidGate { gate_type: 0, inputs: 0 }));
+    }
+
+    #[test]
+    fn bus_gates_use_the_single_bit_images() {
+        for gate_type in 0..7 {
+            let inputs = if gate_type == 2 { 1 } else { 2 };
+            let single = LogicGate::new_gate(gate_type, inputs, false, 1).unwrap();
+            let bus = LogicGate::new_gate(gate_type, inputs, true, 8).unwrap();
+            assert_eq!(bus.image_path(), single.image_path());
+            assert!(bus.image_path().starts_with("/gates/"));
+        }
+    }
+}
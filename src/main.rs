@@ -1,37 +1,90 @@
-mod circuit;
-mod logic_gates;
-mod source;
-mod structure;
-mod connection_logic;
-mod led;
-
 use std::vec;
-use led::Led;
-use source::Source;
-use structure::*;
-use connection_logic::*;
-use logic_gates::*;
-use circuit::*;
+use logic_simulator::led::Led;
+use logic_simulator::source::Source;
+use logic_simulator::structure::*;
+use logic_simulator::connection_logic::*;
+use logic_simulator::logic_gates::*;
+use logic_simulator::circuit::*;
+use logic_simulator::custom::ComponentLibrary;
+use logic_simulator::clock::Clock;
+use logic_simulator::flip_flop::{FlipFlop, FlipFlopType};
+use logic_simulator::latch_register::{LatchRegister, LatchRegisterType};
+use logic_simulator::multiplexer::{Demultiplexer, Multiplexer, MAX_SELECT_BITS};
+use logic_simulator::adder::{Adder, AdderType};
+use logic_simulator::shift_register::{ShiftDirection, ShiftRegister, ShiftRegisterMode};
+use logic_simulator::buffer::{Buffer, BufferType, Pull, PullResistor};
+use logic_simulator::history::{Command, History};
+use logic_simulator::truth_table::TruthTable;
+use logic_simulator::error::SimError;
+use logic_simulator::expression::Expr;
+use logic_simulator::extraction::LedAnalysis;
+use logic_simulator::optimize::Optimization;
+use logic_simulator::minimize::{KarnaughMap, MAX_KARNAUGH_VARIABLES};
 
 use ggegui::egui::{vec2, Align, Layout, Vec2};
 use ggegui::{egui, Gui};
 use ggez::event::{self, EventHandler};
 use ggez::graphics::{Canvas, Color, DrawMode, DrawParam, Image, Mesh, Rect};
 use ggez::{Context, ContextBuilder, GameResult, input, mint::Point2, conf::{Conf, WindowSetup}};
+use ggez::input::keyboard::{KeyCode, KeyMods};
 
  
 const UI_BUTTON_SIZE: Vec2 = vec2(150.0, 30.0);
 struct State {
 	gui: Gui,
 	circuit: Circuit,
+	history: History,
     add_element: Vec<bool>,
 	selected_gate: Option<String>,
 	selected_source: Option<String>,
 	input_number: u32,
-	dragging_index: Option<usize>,
+	gate_delay: u64,
+	bus_gate: bool,
+	bus_bits: usize,
+	dragging_id: Option<usize>,
 	drag_offset: Option<Point2<f32>>,
+	drag_start: Option<Point2<f32>>,	// Position of the dragged component before the drag
+	selected_component: Option<usize>,	// Id of the component shown in the properties window
+	property_delay: u64,
+	property_name: String,
 	grid_image: Image,
 	wire_start: Option<Point2<f32>>, 
+	file_path: String,
+	file_errors: Vec<String>,	// Problems of the last save, open or export, shown until the next one
+	library: ComponentLibrary,
+	custom_path: String,
+	custom_error: Option<String>,	// Why the last custom component couldn't be loaded
+	selection_start: Option<Point2<f32>>,
+	selection: Option<Rect>,
+	block_name: String,
+	truth_table: Option<Result<TruthTable, SimError>>,	// Shown in the truth table window
+	table_export_error: Option<String>,	// Why the last export of the truth table failed
+	show_expression: bool,
+	expression: String,
+	expression_error: Option<String>,
+	led_analysis: Option<Result<LedAnalysis, SimError>>,	// Shown in the led expression window
+	show_optimize: bool,
+	constant_sources: Vec<usize>,	// Sources kept at their value by the optimization
+	optimization: Option<Result<Optimization, SimError>>,
+	blocks: Vec<LogicElements>,
+	clock_period: u64,
+	clock_duty: u64,
+	clock_phase: u64,
+	clocks_running: bool,
+	clock_speed: u64,
+	selected_flip_flop: Option<FlipFlopType>,
+	selected_latch_register: Option<LatchRegisterType>,
+	register_bits: usize,
+	selected_mux: Option<String>,
+	select_bits: usize,
+	data_bits: usize,
+	selected_adder: Option<AdderType>,
+	adder_bits: usize,
+	selected_shift_register: Option<ShiftRegisterMode>,
+	shift_direction: ShiftDirection,
+	shift_register_bits: usize,
+	selected_buffer: Option<String>,
+	buffer_bits: usize,
 }
 
 impl State {
@@ -42,14 +95,58 @@ impl State {
 		Self { 
 			gui: Gui::new(ctx),
 			circuit: Circuit::new(), 
-			add_element: vec![false; 7],
+			history: History::new(),
+			add_element: vec![false; 14],
 			selected_gate: None,
 			selected_source: None,
 			input_number: 2,
-			dragging_index: None,
+			bus_gate: false,
+			bus_bits: 8,
+			gate_delay: DEFAULT_GATE_DELAY,
+			dragging_id: None,
 			drag_offset: None,
+			drag_start: None,
+			selected_component: None,
+			property_delay: DEFAULT_GATE_DELAY,
+			property_name: String::new(),
 			grid_image: canvas_grid,
 			wire_start: None,
+			file_path: "circuit.json".to_string(),
+			file_errors: Vec::new(),
+			library: ComponentLibrary::new(),
+			custom_path: "component.json".to_string(),
+			custom_error: None,
+			selection_start: None,
+			selection: None,
+			block_name: "Block".to_string(),
+			truth_table: None,
+			table_export_error: None,
+			show_expression: false,
+			expression: "Y = (A & B) | !C".to_string(),
+			expression_error: None,
+			led_analysis: None,
+			show_optimize: false,
+			constant_sources: Vec::new(),
+			optimization: None,
+			blocks: Vec::new(),
+			clock_period: 20,
+			clock_duty: 50,
+			clock_phase: 0,
+			clocks_running: false,
+			clock_speed: 1,
+			selected_flip_flop: None,
+			selected_latch_register: None,
+			register_bits: 8,
+			selected_mux: None,
+			select_bits: 1,
+			data_bits: 1,
+			selected_adder: None,
+			adder_bits: 4,
+			selected_shift_register: None,
+			shift_direction: ShiftDirection::Right,
+			shift_register_bits: 8,
+			selected_buffer: None,
+			buffer_bits: 1,
 		}
 	}
 
@@ -86,6 +183,17 @@ impl EventHandler for State {
     				}
 				}
 
+				// Button for the clocks
+				if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new("Clocks")).clicked() {
+					self.add_element[8] = !self.add_element[8];
+					// Set all other elements to false
+    				for i in 0..self.add_element.len() {
+        				if i != 8 {
+            				self.add_element[i] = false;
+        				}
+    				}
+				}
+
 				// Button for the leds
 				if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new("Leds")).clicked() {
 					self.add_element[2] = true;
@@ -99,7 +207,7 @@ impl EventHandler for State {
 					if self.add_element[2] {
 						let mut led = LogicElements::Leds(Led::new());
 						let _ = led.load_image(ctx);
-						self.circuit.add_element(led);
+						self.history.apply(&mut self.circuit, Command::AddComponent(led));
 						// put the state back to false
 						self.add_element[2] = !self.add_element[2];
 					}
@@ -127,6 +235,72 @@ impl EventHandler for State {
     				}
 				}
 
+				// Button for the adders
+				if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new("Adders")).clicked() {
+					self.add_element[11] = !self.add_element[11];
+					// Set all other elements to false
+    				for i in 0..self.add_element.len() {
+        				if i != 11 {
+            				self.add_element[i] = false;
+        				}
+    				}
+				}
+
+				// Button for the flip flops
+				if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new("Flip Flops")).clicked() {
+					self.add_element[9] = !self.add_element[9];
+					// Set all other elements to false
+    				for i in 0..self.add_element.len() {
+        				if i != 9 {
+            				self.add_element[i] = false;
+        				}
+    				}
+				}
+
+				// Button for the latches and registers
+				if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new("Latches & Registers")).clicked() {
+					self.add_element[10] = !self.add_element[10];
+					// Set all other elements to false
+    				for i in 0..self.add_element.len() {
+        				if i != 10 {
+            				self.add_element[i] = false;
+        				}
+    				}
+				}
+
+				// Button for the shift registers
+				if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new("Shift Registers")).clicked() {
+					self.add_element[12] = !self.add_element[12];
+					// Set all other elements to false
+    				for i in 0..self.add_element.len() {
+        				if i != 12 {
+            				self.add_element[i] = false;
+        				}
+    				}
+				}
+
+				// Button for the buffers and the pull resistors
+				if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new("Buffers")).clicked() {
+					self.add_element[13] = !self.add_element[13];
+					// Set all other elements to false
+    				for i in 0..self.add_element.len() {
+        				if i != 13 {
+            				self.add_element[i] = false;
+        				}
+    				}
+				}
+
+				// Button for the custom components
+				if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new("Custom Components")).clicked() {
+					self.add_element[7] = !self.add_element[7];
+					// Set all other elements to false
+    				for i in 0..self.add_element.len() {
+        				if i != 7 {
+            				self.add_element[i] = false;
+        				}
+    				}
+				}
+
 				// Edit tool button
 				if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new("Edit tool")).clicked() {
 					self.add_element[5] = !self.add_element[5];
@@ -143,6 +317,89 @@ impl EventHandler for State {
 					self.add_element[6] = !self.add_element[6];
 				}
 
+				// Step button, advances the simulation to the next event
+				// The errors are kept in the circuit and listed below
+				if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new("Step")).clicked() {
+					let _ = self.circuit.step();
+				}
+				ui.label(format!("Time: {}", self.circuit.time));
+
+				// Problems found by the last simulation and by the editor, the components involved are
+				// outlined in red. The ones of the editor stay until they are cleared
+				for error in self.circuit.all_errors() {
+					ui.colored_label(egui::Color32::RED, error.to_string());
+				}
+				if !self.circuit.editor_errors.is_empty() && ui.add_sized(UI_BUTTON_SIZE, egui::Button::new("Clear errors")).clicked() {
+					self.circuit.editor_errors.clear();
+				}
+
+				// Clock controls
+				let run_label = if self.clocks_running { "Pause clocks" } else { "Run clocks" };
+				if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new(run_label)).clicked() {
+					self.clocks_running = !self.clocks_running;
+				}
+				if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new("Next clock edge")).clicked() {
+					let _ = self.circuit.step_clock();
+				}
+				ui.horizontal(|ui| {
+					ui.label("Time units per frame:");
+					ui.add(egui::DragValue::new(&mut self.clock_speed).clamp_range(1..=1000).speed(1));
+				});
+
+				ui.separator();
+
+				// Save and open the circuit file
+				ui.add_sized(UI_BUTTON_SIZE, egui::TextEdit::singleline(&mut self.file_path));
+				if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new("Save")).clicked() {
+					self.file_errors.clear();
+					if let Err(err) = self.circuit.save(&self.file_path) {
+						self.file_errors.push(format!("Failed to save {}: {}", self.file_path, err));
+					}
+				}
+				if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new("Open")).clicked() {
+					self.file_errors.clear();
+					match Circuit::load(&self.file_path) {
+						Ok(mut circuit) => {
+							let _ = circuit.load_images(ctx);
+							self.circuit = circuit;
+							self.history.clear();
+							self.dragging_id = None;
+							self.drag_offset = None;
+							self.selected_component = None;
+						}
+						Err(err) => self.file_errors.push(format!("Failed to open {}: {}", self.file_path, err)),
+					}
+				}
+				if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new("Export Verilog")).clicked() {
+					// The module is named after the file
+					let path = std::path::Path::new(&self.file_path).with_extension("v");
+					let module_name = path.file_stem().and_then(|name| name.to_str()).unwrap_or("circuit");
+					let module = self.circuit.export_verilog(module_name);
+					self.file_errors.clear();
+					if let Err(err) = std::fs::write(&path, &module.source) {
+						self.file_errors.push(format!("Failed to export {}: {}", path.display(), err));
+					}
+					for cid in module.skipped {
+						self.file_errors.push(format!("Component {} can't be exported to Verilog and was left out", cid));
+					}
+				}
+				for error in &self.file_errors {
+					ui.colored_label(egui::Color32::RED, error);
+				}
+				if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new("Truth table")).clicked() {
+					self.truth_table = Some(self.circuit.truth_table());
+					self.table_export_error = None;
+				}
+				if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new("From expression")).clicked() {
+					self.show_expression = true;
+				}
+				if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new("Optimize")).clicked() {
+					self.show_optimize = true;
+					self.optimization = None;
+				}
+
+				ui.separator();
+
 				if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new("Quit")).clicked() {
 					ctx.request_quit();
 				}
@@ -188,11 +445,33 @@ impl EventHandler for State {
 							}
 			
 							ui.separator();
+
+							// Bus section, every input and the output carry the same number of bits
+							ui.checkbox(&mut self.bus_gate, "Bus gate");
+							if self.bus_gate {
+								ui.label("Number of bits:");
+								ui.add(egui::DragValue::new(&mut self.bus_bits)
+									.clamp_range(2..=64)
+									.speed(1));
+							}
+
+							ui.separator();
+
+							// Propagation delay section
+							ui.label("Propagation delay:");
+							ui.add(egui::DragValue::new(&mut self.gate_delay)
+								.clamp_range(0..=1000)
+								.speed(1));
+
+							ui.separator();
 			
 							// Display selected gate and input number
 							if let Some(selected_gate) = &self.selected_gate {
 								ui.label(format!("Selected Gate: {}", selected_gate));
 								ui.label(format!("Number of Inputs: {}", self.input_number));
+								if self.bus_gate {
+									ui.label(format!("Bits: {}", self.bus_bits));
+								}
 							}
 			
 							ui.separator();
@@ -200,21 +479,29 @@ impl EventHandler for State {
 							// Generate button
 							if ui.add_sized(button_size, egui::Button::new("Generate")).clicked() {
 								if let Some(selected_gate) = &self.selected_gate {
-									let gate_type: u32 = match selected_gate.as_str() {
-										"AND Gate" => 0,
-										"OR Gate" => 1,
-										"NOT Gate" => 2,
-										"NAND Gate" => 3,
-										"NOR Gate" => 4,
-										"XOR Gate" => 5,
-										"XNOR Gate" => 6,
-										_ => panic!("Invalid Gate"),
+									let gate_type = match selected_gate.as_str() {
+										"AND Gate" => Ok(0),
+										"OR Gate" => Ok(1),
+										"NOT Gate" => Ok(2),
+										"NAND Gate" => Ok(3),
+										"NOR Gate" => Ok(4),
+										"XOR Gate" => Ok(5),
+										"XNOR Gate" => Ok(6),
+										name => Err(SimError::UnknownComponentType { name: name.to_string() }),
 									};
-									let mut gate = LogicElements::Gates(
-										LogicGate::new_gate(gate_type, self.input_number as usize, false, 1),
-									);
-									let _ = gate.load_image(ctx); // Load the gate image
-									self.circuit.add_element(gate); // Add the gate to the circuit
+									let gate = gate_type.and_then(|gate_type| {
+										LogicGate::new_gate(gate_type, self.input_number as usize, self.bus_gate, self.bus_bits)
+									});
+									match gate {
+										Ok(gate) => {
+											let mut gate = LogicElements::Gates(gate);
+											gate.set_delay(self.gate_delay);
+											let _ = gate.load_image(ctx); // Load the gate image
+											self.history.apply(&mut self.circuit, Command::AddComponent(gate)); // Add the gate to the circuit
+										}
+										// Show why the gate can't be built with the other errors
+										Err(error) => self.circuit.editor_errors.push(error),
+									}
 								}
 							}
 			
@@ -252,14 +539,19 @@ impl EventHandler for State {
 								if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new("Generate")).clicked() {
 									// Generate the selected source
 									let value = match selected_source.as_str() {
-										"High" => 1,
-										"Low" => 0,
-										_ => panic!("Invalid source type"),
+										"High" => Ok(1),
+										"Low" => Ok(0),
+										name => Err(SimError::UnknownComponentType { name: name.to_string() }),
 									};
 			
-									let mut source = LogicElements::Source(Source::new(value));
-									let _ = source.load_image(ctx);
-									self.circuit.add_element(source);
+									match value.and_then(Source::new) {
+										Ok(source) => {
+											let mut source = LogicElements::Source(source);
+											let _ = source.load_image(ctx);
+											self.history.apply(&mut self.circuit, Command::AddComponent(source));
+										}
+										Err(error) => self.circuit.editor_errors.push(error),
+									}
 								}
 							}
 			
@@ -270,6 +562,654 @@ impl EventHandler for State {
 						});
 					});
 			}
+
+			//* Window to choose the multiplexers and demultiplexers
+			if self.add_element[4] {
+				egui::Window::new("Multiplexer Selector")
+					.resizable(false)
+					.default_width(100.0)
+					.show(&gui_ctx, |ui| {
+						ui.with_layout(egui::Layout::top_down(egui::Align::Center), |ui| {
+							if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new("Multiplexer")).clicked() {
+								self.selected_mux = Some("Multiplexer".to_string());
+							}
+							if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new("Demultiplexer")).clicked() {
+								self.selected_mux = Some("Demultiplexer".to_string());
+							}
+
+							ui.separator();
+
+							// Number of select lines and width of the data pins
+							ui.label("Select lines:");
+							ui.add(egui::DragValue::new(&mut self.select_bits)
+								.clamp_range(1..=MAX_SELECT_BITS)
+								.speed(1));
+							ui.label("Data bits:");
+							ui.add(egui::DragValue::new(&mut self.data_bits)
+								.clamp_range(1..=64)
+								.speed(1));
+
+							ui.separator();
+
+							if let Some(selected_mux) = &self.selected_mux {
+								let lines = 1 << self.select_bits;
+								match selected_mux.as_str() {
+									"Multiplexer" => ui.label(format!("Selected: {}:1 Multiplexer", lines)),
+									_ => ui.label(format!("Selected: 1:{} Demultiplexer", lines)),
+								};
+
+								if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new("Generate")).clicked() {
+									let mut element = match selected_mux.as_str() {
+										"Multiplexer" => LogicElements::Multiplexers(Multiplexer::new(self.select_bits, self.data_bits)),
+										_ => LogicElements::Demultiplexers(Demultiplexer::new(self.select_bits, self.data_bits)),
+									};
+									let _ = element.load_image(ctx);
+									self.history.apply(&mut self.circuit, Command::AddComponent(element));
+								}
+							}
+
+							if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new("Close")).clicked() {
+								self.add_element[4] = false;
+							}
+						});
+					});
+			}
+
+			//* Window to choose the shift registers
+			if self.add_element[12] {
+				egui::Window::new("Shift Registers")
+					.resizable(false)
+					.default_width(100.0)
+					.show(&gui_ctx, |ui| {
+						ui.with_layout(egui::Layout::top_down(egui::Align::Center), |ui| {
+							let modes = [
+								("Serial In Serial Out", ShiftRegisterMode::Siso),
+								("Serial In Parallel Out", ShiftRegisterMode::Sipo),
+								("Parallel In Serial Out", ShiftRegisterMode::Piso),
+								("Parallel In Parallel Out", ShiftRegisterMode::Pipo),
+							];
+							for (name, mode) in modes {
+								if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new(name)).clicked() {
+									self.selected_shift_register = Some(mode);
+								}
+							}
+
+							ui.separator();
+
+							ui.label("Shift direction:");
+							ui.horizontal(|ui| {
+								ui.radio_value(&mut self.shift_direction, ShiftDirection::Left, "Left");
+								ui.radio_value(&mut self.shift_direction, ShiftDirection::Right, "Right");
+							});
+							ui.label("Number of bits:");
+							ui.add(egui::DragValue::new(&mut self.shift_register_bits).clamp_range(1..=32).speed(1));
+
+							ui.separator();
+
+							if let Some(mode) = self.selected_shift_register {
+								ui.label(format!("Selected: {:?}", mode).to_uppercase());
+
+								if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new("Generate")).clicked() {
+									let mut shift_register = LogicElements::ShiftRegisters(
+										ShiftRegister::new(mode, self.shift_direction, self.shift_register_bits),
+									);
+									let _ = shift_register.load_image(ctx);
+									self.history.apply(&mut self.circuit, Command::AddComponent(shift_register));
+								}
+							}
+
+							if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new("Close")).clicked() {
+								self.add_element[12] = false;
+							}
+						});
+					});
+			}
+
+			//* Window to choose the buffers and the pull resistors
+			if self.add_element[13] {
+				egui::Window::new("Buffers")
+					.resizable(false)
+					.default_width(100.0)
+					.show(&gui_ctx, |ui| {
+						ui.with_layout(egui::Layout::top_down(egui::Align::Center), |ui| {
+							for name in ["Buffer", "Tri-state Buffer", "Open Drain Buffer", "Pull-up", "Pull-down"] {
+								if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new(name)).clicked() {
+									self.selected_buffer = Some(name.to_string());
+								}
+							}
+
+							ui.separator();
+
+							ui.label("Number of bits:");
+							ui.add(egui::DragValue::new(&mut self.buffer_bits).clamp_range(1..=64).speed(1));
+
+							ui.separator();
+
+							if let Some(selected_buffer) = &self.selected_buffer {
+								ui.label(format!("Selected: {}", selected_buffer).to_uppercase());
+
+								if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new("Generate")).clicked() {
+									let mut element = match selected_buffer.as_str() {
+										"Buffer" => LogicElements::Buffers(Buffer::new(BufferType::Buffer, self.buffer_bits)),
+										"Tri-state Buffer" => LogicElements::Buffers(Buffer::new(BufferType::TriState, self.buffer_bits)),
+										"Open Drain Buffer" => LogicElements::Buffers(Buffer::new(BufferType::OpenDrain, self.buffer_bits)),
+										"Pull-up" => LogicElements::Resistors(PullResistor::new(Pull::Up, self.buffer_bits)),
+										_ => LogicElements::Resistors(PullResistor::new(Pull::Down, self.buffer_bits)),
+									};
+									let _ = element.load_image(ctx);
+									self.history.apply(&mut self.circuit, Command::AddComponent(element));
+								}
+							}
+
+							if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new("Close")).clicked() {
+								self.add_element[13] = false;
+							}
+						});
+					});
+			}
+
+			//* Window to configure and place clocks
+			if self.add_element[8] {
+				egui::Window::new("Clocks")
+					.resizable(false)
+					.default_width(100.0)
+					.show(&gui_ctx, |ui| {
+						ui.with_layout(egui::Layout::top_down(egui::Align::Center), |ui| {
+							ui.label("Period:");
+							ui.add(egui::DragValue::new(&mut self.clock_period).clamp_range(2..=10000).speed(1));
+							ui.label("Duty cycle (%):");
+							ui.add(egui::DragValue::new(&mut self.clock_duty).clamp_range(1..=99).speed(1));
+							ui.label("Phase:");
+							ui.add(egui::DragValue::new(&mut self.clock_phase).clamp_range(0..=10000).speed(1));
+
+							ui.separator();
+
+							if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new("Generate")).clicked() {
+								let mut clock = LogicElements::Clock(Clock::new(self.clock_period, self.clock_duty, self.clock_phase));
+								let _ = clock.load_image(ctx);
+								self.history.apply(&mut self.circuit, Command::AddComponent(clock));
+							}
+							if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new("Close")).clicked() {
+								self.add_element[8] = false;
+							}
+						});
+					});
+			}
+
+			//* Window to choose the adders
+			if self.add_element[11] {
+				egui::Window::new("Adders")
+					.resizable(false)
+					.default_width(100.0)
+					.show(&gui_ctx, |ui| {
+						ui.with_layout(egui::Layout::top_down(egui::Align::Center), |ui| {
+							let adders = [
+								("Half Adder", AdderType::Half),
+								("Full Adder", AdderType::Full),
+								("Ripple Carry Adder", AdderType::RippleCarry),
+								("Carry Lookahead Adder", AdderType::CarryLookahead),
+							];
+							for (name, r#type) in adders {
+								if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new(name)).clicked() {
+									self.selected_adder = Some(r#type);
+								}
+							}
+
+							ui.separator();
+
+							if let Some(r#type) = self.selected_adder {
+								if matches!(r#type, AdderType::RippleCarry | AdderType::CarryLookahead) {
+									ui.label("Number of bits:");
+									ui.add(egui::DragValue::new(&mut self.adder_bits).clamp_range(1..=64).speed(1));
+								}
+
+								// Delay of one gate level, the model of the adder decides how many levels there are
+								ui.label("Gate delay:");
+								ui.add(egui::DragValue::new(&mut self.gate_delay).clamp_range(0..=1000).speed(1));
+
+								if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new("Generate")).clicked() {
+									let mut adder = LogicElements::Adders(Adder::new(r#type, self.adder_bits));
+									adder.set_delay(self.gate_delay);
+									let _ = adder.load_image(ctx);
+									self.history.apply(&mut self.circuit, Command::AddComponent(adder));
+								}
+							}
+
+							if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new("Close")).clicked() {
+								self.add_element[11] = false;
+							}
+						});
+					});
+			}
+
+			//* Window to choose the flip flops
+			if self.add_element[9] {
+				egui::Window::new("Flip Flops")
+					.resizable(false)
+					.default_width(100.0)
+					.show(&gui_ctx, |ui| {
+						ui.with_layout(egui::Layout::top_down(egui::Align::Center), |ui| {
+							let flip_flops = [
+								("D Flip Flop", FlipFlopType::D),
+								("T Flip Flop", FlipFlopType::T),
+								("JK Flip Flop", FlipFlopType::JK),
+								("SR Flip Flop", FlipFlopType::SR),
+							];
+							for (name, r#type) in flip_flops {
+								if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new(name)).clicked() {
+									self.selected_flip_flop = Some(r#type);
+								}
+							}
+
+							ui.separator();
+
+							if let Some(r#type) = self.selected_flip_flop {
+								ui.label(format!("Selected: {:?} Flip Flop", r#type));
+								ui.label(format!("Inputs: {}, clock, set, reset", r#type.inputs().join(", ")));
+
+								if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new("Generate")).clicked() {
+									let mut flip_flop = LogicElements::FlipFlops(FlipFlop::new(r#type));
+									let _ = flip_flop.load_image(ctx);
+									self.history.apply(&mut self.circuit, Command::AddComponent(flip_flop));
+								}
+							}
+
+							if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new("Close")).clicked() {
+								self.add_element[9] = false;
+							}
+						});
+					});
+			}
+
+			//* Window to choose the latches and registers
+			if self.add_element[10] {
+				egui::Window::new("Latches & Registers")
+					.resizable(false)
+					.default_width(100.0)
+					.show(&gui_ctx, |ui| {
+						ui.with_layout(egui::Layout::top_down(egui::Align::Center), |ui| {
+							let elements = [
+								("SR Latch", LatchRegisterType::SR),
+								("D Latch", LatchRegisterType::D),
+								("Register", LatchRegisterType::Register),
+							];
+							for (name, r#type) in elements {
+								if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new(name)).clicked() {
+									self.selected_latch_register = Some(r#type);
+								}
+							}
+
+							ui.separator();
+
+							if let Some(r#type) = self.selected_latch_register {
+								if r#type == LatchRegisterType::Register {
+									ui.label("Number of bits:");
+									ui.add(egui::DragValue::new(&mut self.register_bits).clamp_range(1..=64).speed(1));
+									ui.label("Control pins: clock, load enable");
+								} else {
+									ui.label("Control pins: enable");
+								}
+
+								if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new("Generate")).clicked() {
+									let mut element = LogicElements::LatchRegisters(LatchRegister::new(r#type, self.register_bits));
+									let _ = element.load_image(ctx);
+									self.history.apply(&mut self.circuit, Command::AddComponent(element));
+								}
+							}
+
+							if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new("Close")).clicked() {
+								self.add_element[10] = false;
+							}
+						});
+					});
+			}
+
+			//* Window to load and place custom components
+			if self.add_element[7] {
+				egui::Window::new("Custom Components")
+					.resizable(false)
+					.default_width(100.0)
+					.show(&gui_ctx, |ui| {
+						ui.with_layout(egui::Layout::top_down(egui::Align::Center), |ui| {
+							// Load a definition from its JSON file
+							ui.add_sized(UI_BUTTON_SIZE, egui::TextEdit::singleline(&mut self.custom_path));
+							if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new("Load")).clicked() {
+								self.custom_error = self.library.load(&self.custom_path).err()
+									.map(|err| format!("Failed to load {}: {}", self.custom_path, err));
+							}
+							if let Some(error) = &self.custom_error {
+								ui.colored_label(egui::Color32::RED, error);
+							}
+
+							ui.separator();
+
+							// One button for every component loaded so far
+							let names: Vec<String> = self.library.definitions.iter()
+								.map(|definition| definition.name.clone())
+								.collect();
+							for name in names {
+								if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new(&name)).clicked() {
+									match self.library.instantiate(&name) {
+										Ok(mut component) => {
+											let _ = component.load_image(ctx);
+											self.history.apply(&mut self.circuit, Command::AddComponent(component));
+										}
+										// Show why the component can't be built with the other errors
+										Err(error) => self.circuit.editor_errors.push(error),
+									}
+								}
+							}
+
+							// The blocks made from a selection of the circuit
+							if !self.blocks.is_empty() {
+								ui.separator();
+								ui.label("Blocks");
+							}
+							for block in &self.blocks {
+								if let LogicElements::SubCircuit(subcircuit) = block {
+									if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new(&subcircuit.name)).clicked() {
+										self.history.apply(&mut self.circuit, Command::AddComponent(block.clone()));
+									}
+								}
+							}
+
+							if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new("Close")).clicked() {
+								self.add_element[7] = false;
+							}
+						});
+					});
+			}
+
+			//* Window to change the properties of the last component clicked with the edit tool
+			let selected = self.selected_component.filter(|cid| self.circuit.components.contains_key(cid));
+			if let (true, Some(cid)) = (self.add_element[5], selected) {
+				egui::Window::new("Properties")
+					.resizable(false)
+					.default_width(100.0)
+					.show(&gui_ctx, |ui| {
+						ui.with_layout(egui::Layout::top_down(egui::Align::Center), |ui| {
+							ui.label(format!("Component {}", cid));
+							ui.label("Delay:");
+							ui.add(egui::DragValue::new(&mut self.property_delay).clamp_range(0..=1000).speed(1));
+							let delay = self.circuit.components[&cid].get_delay();
+							if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new("Apply")).clicked() && self.property_delay != delay {
+								let command = Command::SetDelay { cid, from: delay, to: self.property_delay };
+								self.history.apply(&mut self.circuit, command);
+							}
+
+							// Sources and leds can be named, to drive and read them from the command line
+							let name = match &self.circuit.components[&cid] {
+								LogicElements::Source(source) => Some(source.name.clone()),
+								LogicElements::Leds(led) => Some(led.name.clone()),
+								_ => None,
+							};
+							if let Some(name) = name {
+								ui.label("Name:");
+								ui.add_sized(UI_BUTTON_SIZE, egui::TextEdit::singleline(&mut self.property_name));
+								let new_name = Some(self.property_name.trim().to_string()).filter(|name| !name.is_empty());
+								if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new("Rename")).clicked() && new_name != name {
+									self.history.apply(&mut self.circuit, Command::SetName { cid, from: name, to: new_name });
+								}
+							}
+
+							// The expression of a led, from the sources driving it
+							if let LogicElements::Leds(_) = &self.circuit.components[&cid] {
+								if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new("Expression")).clicked() {
+									self.led_analysis = Some(self.circuit.analyze_led(cid));
+								}
+							}
+
+							// Sources can be switched between low and high
+							if let LogicElements::Source(source) = &self.circuit.components[&cid] {
+								let value = source.output.value.bit(0);
+								if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new("Toggle value")).clicked() {
+									let command = Command::SetSourceValue { cid, from: value, to: !value };
+									self.history.apply(&mut self.circuit, command);
+									if let Some(LogicElements::Source(source)) = self.circuit.components.get_mut(&cid) {
+										match source.image_path() {
+											Ok(_) => {
+												let _ = source.load_source_image(ctx);
+											}
+											Err(error) => self.circuit.editor_errors.push(error),
+										}
+									}
+								}
+							}
+
+							if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new("Delete")).clicked() {
+								if let Some(command) = Command::remove_component(&self.circuit, cid) {
+									self.history.apply(&mut self.circuit, command);
+								}
+								self.selected_component = None;
+							}
+
+							if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new("Close")).clicked() {
+								self.selected_component = None;
+							}
+						});
+					});
+			}
+
+			//* Window showing the truth table of the circuit, which can be exported next to the circuit file
+			if let Some(truth_table) = &self.truth_table {
+				let mut open = true;
+				egui::Window::new("Truth table")
+					.open(&mut open)
+					.resizable(true)
+					.show(&gui_ctx, |ui| match truth_table {
+						Ok(table) => {
+							ui.horizontal(|ui| {
+								let exports = [("Export CSV", "csv", table.to_csv()), ("Export Markdown", "md", table.to_markdown())];
+								for (label, extension, content) in exports {
+									if ui.button(label).clicked() {
+										let path = std::path::Path::new(&self.file_path).with_extension(extension);
+										self.table_export_error = std::fs::write(&path, content).err()
+											.map(|err| format!("Failed to export {}: {}", path.display(), err));
+									}
+								}
+								if let Some(error) = &self.table_export_error {
+									ui.colored_label(egui::Color32::RED, error);
+								}
+							});
+							ui.separator();
+							egui::ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+								egui::Grid::new("truth_table").striped(true).show(ui, |ui| {
+									for name in table.header() {
+										ui.strong(name);
+									}
+									ui.end_row();
+									for row in &table.rows {
+										for cell in row.cells() {
+											ui.label(cell);
+										}
+										ui.end_row();
+									}
+								});
+							});
+						}
+						Err(error) => {
+							ui.colored_label(egui::Color32::RED, error.to_string());
+						}
+					});
+				if !open {
+					self.truth_table = None;
+				}
+			}
+
+			//* Window showing the expression of a led, its minimal sum of products and its Karnaugh map
+			if let Some(analysis) = &self.led_analysis {
+				let mut open = true;
+				egui::Window::new("Led expression")
+					.open(&mut open)
+					.resizable(false)
+					.show(&gui_ctx, |ui| match analysis {
+						Ok(analysis) => {
+							ui.label(format!("Expression: {}", analysis.expression));
+							ui.label(format!("Minimized: {}", analysis.minimized));
+							ui.separator();
+							match analysis.function.karnaugh_map() {
+								Some(map) => {
+									egui::Grid::new("karnaugh_map").striped(true).show(ui, |ui| {
+										ui.strong(format!("{} \\ {}", map.row_variables.join(" "), map.column_variables.join(" ")));
+										for &column in &map.columns {
+											ui.strong(KarnaughMap::label(column, map.column_variables.len()));
+										}
+										ui.end_row();
+										for (&row, cells) in map.rows.iter().zip(&map.cells) {
+											ui.strong(KarnaughMap::label(row, map.row_variables.len()));
+											for &cell in cells {
+												ui.label(if cell { "1" } else { "0" });
+											}
+											ui.end_row();
+										}
+									});
+								}
+								None => {
+									ui.label(format!("No Karnaugh map for more than {} inputs", MAX_KARNAUGH_VARIABLES));
+								}
+							}
+						}
+						Err(error) => {
+							ui.colored_label(egui::Color32::RED, error.to_string());
+						}
+					});
+				if !open {
+					self.led_analysis = None;
+				}
+			}
+
+			//* Window to build a new circuit from a boolean expression, it replaces the current one
+			if self.show_expression {
+				egui::Window::new("Expression")
+					.resizable(false)
+					.default_width(200.0)
+					.show(&gui_ctx, |ui| {
+						ui.with_layout(egui::Layout::top_down(egui::Align::Center), |ui| {
+							ui.label("Operators: ! & ^ | and parentheses");
+							ui.add_sized(UI_BUTTON_SIZE, egui::TextEdit::singleline(&mut self.expression));
+							if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new("Build")).clicked() {
+								let circuit = Expr::parse_assignment(&self.expression)
+									.map_err(|err| err.to_string())
+									.and_then(|(output, expr)| Circuit::from_expression(&output, &expr).map_err(|err| err.to_string()));
+								match circuit {
+									Ok(mut circuit) => {
+										let _ = circuit.load_images(ctx);
+										self.circuit = circuit;
+										self.history.clear();
+										self.dragging_id = None;
+										self.drag_offset = None;
+										self.selected_component = None;
+										self.expression_error = None;
+										self.show_expression = false;
+									}
+									Err(err) => self.expression_error = Some(err),
+								}
+							}
+							if let Some(err) = &self.expression_error {
+								ui.colored_label(egui::Color32::RED, err);
+							}
+							if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new("Close")).clicked() {
+								self.show_expression = false;
+								self.expression_error = None;
+							}
+						});
+					});
+			}
+
+			//* Window to rebuild the circuit with fewer gates, to compare it with the optimized one
+			if self.show_optimize {
+				let mut optimized = None;
+				egui::Window::new("Optimize")
+					.resizable(false)
+					.default_width(200.0)
+					.show(&gui_ctx, |ui| {
+						ui.with_layout(egui::Layout::top_down(egui::Align::Center), |ui| {
+							let sources: Vec<(usize, String)> = self.circuit.components.iter()
+								.filter_map(|(&cid, component)| match component {
+									LogicElements::Source(source) => Some((cid, source.label())),
+									_ => None,
+								})
+								.collect();
+							if !sources.is_empty() {
+								ui.label("Constant sources:");
+							}
+							for (cid, name) in sources {
+								let mut constant = self.constant_sources.contains(&cid);
+								if ui.checkbox(&mut constant, name).changed() {
+									if constant {
+										self.constant_sources.push(cid);
+									} else {
+										self.constant_sources.retain(|&source| source != cid);
+									}
+								}
+							}
+							if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new("Optimize")).clicked() {
+								self.optimization = Some(self.circuit.optimize(&self.constant_sources));
+							}
+
+							match &self.optimization {
+								Some(Ok(optimization)) => {
+									ui.separator();
+									for output in &optimization.outputs {
+										ui.label(format!("{} = {}", output.name, output.original));
+										ui.label(format!("{} = {}", output.name, output.optimized));
+									}
+									ui.label(format!("Gates: {} -> {}", optimization.original_gates, optimization.optimized_gates));
+									if optimization.equivalent {
+										ui.label("Same truth table");
+									} else {
+										ui.colored_label(egui::Color32::RED, "Different truth table");
+									}
+									if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new("Open optimized")).clicked() {
+										optimized = Some(optimization.circuit.clone());
+									}
+								}
+								Some(Err(err)) => {
+									ui.colored_label(egui::Color32::RED, err.to_string());
+								}
+								None => {}
+							}
+							if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new("Close")).clicked() {
+								self.show_optimize = false;
+								self.optimization = None;
+							}
+						});
+					});
+
+				// The optimized circuit replaces the current one, like a circuit built from an expression
+				if let Some(mut circuit) = optimized {
+					let _ = circuit.load_images(ctx);
+					self.circuit = circuit;
+					self.history.clear();
+					self.dragging_id = None;
+					self.drag_offset = None;
+					self.selected_component = None;
+					self.constant_sources.clear();
+					self.show_optimize = false;
+					self.optimization = None;
+				}
+			}
+
+			//* Window to turn the selected part of the circuit into a block
+			if let Some(area) = self.selection {
+				egui::Window::new("Selection")
+					.resizable(false)
+					.default_width(100.0)
+					.show(&gui_ctx, |ui| {
+						ui.with_layout(egui::Layout::top_down(egui::Align::Center), |ui| {
+							ui.label("Block name:");
+							ui.add_sized(UI_BUTTON_SIZE, egui::TextEdit::singleline(&mut self.block_name));
+							if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new("Create block")).clicked() {
+								self.blocks.push(self.circuit.collapse(area.into(), &self.block_name));
+								self.selection = None;
+							}
+							if ui.add_sized(UI_BUTTON_SIZE, egui::Button::new("Close")).clicked() {
+								self.selection = None;
+							}
+						});
+					});
+			}
+
 			// debug
 			if ctx.mouse.button_pressed(input::mouse::MouseButton::Right){
 				println!("{:?}", ctx.mouse.position());
@@ -298,54 +1238,113 @@ impl EventHandler for State {
 							let dx = (snapped_mouse_pos.x - start_point.x).abs();
 							let dy = (snapped_mouse_pos.y - start_point.y).abs();
 			
-							let segment = if dx >= dy {
+							let end_point = if dx >= dy {
 								// Horizontal segment
-								WireSegment {
-									start: start_point,
-									end: Point2 { x: snapped_mouse_pos.x, y: start_point.y },
-									hitbox: Hitbox {
-										rect: Rect {
-											x: start_point.x.min(snapped_mouse_pos.x),
-											y: start_point.y - 5.0,
-											w: dx,
-											h: 10.0,
-										},
-										r#type: HitboxType::Wire,
-									},
-								}
+								Point2 { x: snapped_mouse_pos.x, y: start_point.y }
 							} else {
 								// Vertical segment
-								WireSegment {
-									start: start_point,
-									end: Point2 { x: start_point.x, y: snapped_mouse_pos.y },
-									hitbox: Hitbox {
-										rect: Rect {
-											x: start_point.x - 5.0,
-											y: start_point.y.min(snapped_mouse_pos.y),
-											w: 10.0,
-											h: dy,
-										},
-										r#type: HitboxType::Wire,
-									},
-								}
+								Point2 { x: start_point.x, y: snapped_mouse_pos.y }
 							};
+							let segment = WireSegment::new(start_point, end_point);
 			
 							// Store the segment
-							self.circuit.segments.push(segment);
+							let command = Command::add_segment(&self.circuit, segment);
+							self.history.apply(&mut self.circuit, command);
 						}
 					}
 				}
+
+				// Right click on a segment deletes it
+				if ctx.mouse.button_just_pressed(input::mouse::MouseButton::Right) {
+					let segment = self.circuit.segments.iter().position(|segment| segment.hitbox.rect.contains(mouse_pos));
+					if let Some(command) = segment.and_then(|index| Command::remove_segment(&self.circuit, index)) {
+						self.history.apply(&mut self.circuit, command);
+					}
+				}
 			}
 			//* ------------------------------------------------------------------------
 			
+			//* ---------------------Deletion with the edit tool-------------------------
+			if self.add_element[5] {
+				// Right click deletes the component under the mouse, or else the wire segment
+				if ctx.mouse.button_just_pressed(input::mouse::MouseButton::Right) {
+					let mouse_pos = ctx.mouse.position();
+					let component = self.circuit.components.iter()
+						.find(|(_, component)| component.get_hitbox().contains(mouse_pos))
+						.map(|(&cid, _)| cid);
+					let segment = self.circuit.segments.iter().position(|segment| segment.hitbox.rect.contains(mouse_pos));
+					if let Some(command) = component.and_then(|cid| Command::remove_component(&self.circuit, cid)) {
+						self.history.apply(&mut self.circuit, command);
+					} else if let Some(command) = segment.and_then(|index| Command::remove_segment(&self.circuit, index)) {
+						self.history.apply(&mut self.circuit, command);
+					}
+				}
+
+				// Delete or backspace deletes the component shown in the properties window
+				let delete = ctx.keyboard.is_key_just_pressed(KeyCode::Delete) || ctx.keyboard.is_key_just_pressed(KeyCode::Back);
+				if delete && !gui_ctx.wants_keyboard_input() {
+					if let Some(command) = self.selected_component.and_then(|cid| Command::remove_component(&self.circuit, cid)) {
+						self.history.apply(&mut self.circuit, command);
+					}
+					self.selected_component = None;
+				}
+
+				// The dragged component may be gone
+				if self.dragging_id.is_some_and(|cid| !self.circuit.components.contains_key(&cid)) {
+					self.dragging_id = None;
+					self.drag_offset = None;
+					self.drag_start = None;
+				}
+			}
+			//* ------------------------------------------------------------------------
+
+			//* -------------------------------Undo and redo------------------------------
+			// Ctrl+Z undoes the last edit and Ctrl+Y redoes it, unless a text field is being edited
+			if ctx.keyboard.is_mod_active(KeyMods::CTRL) && !gui_ctx.wants_keyboard_input() {
+				let changed = if ctx.keyboard.is_key_just_pressed(KeyCode::Z) {
+					self.history.undo(&mut self.circuit)
+				} else if ctx.keyboard.is_key_just_pressed(KeyCode::Y) {
+					self.history.redo(&mut self.circuit)
+				} else {
+					false
+				};
+				if changed {
+					// Components put back or changed may need a new image
+					let _ = self.circuit.load_images(ctx);
+					self.dragging_id = None;
+					self.drag_offset = None;
+					self.drag_start = None;
+					self.selected_component = None;
+				}
+			}
+			//* ------------------------------------------------------------------------
+
+			//* -------------------Selection with the edit tool-------------------------
+			// Holding shift while dragging draws the selection rectangle
+			if self.add_element[5] && ctx.mouse.button_pressed(input::mouse::MouseButton::Left) &&
+			self.dragging_id.is_none() && ctx.keyboard.is_mod_active(KeyMods::SHIFT) {
+				let mouse_pos = ctx.mouse.position();
+				let start = *self.selection_start.get_or_insert(mouse_pos);
+				self.selection = Some(Rect {
+					x: start.x.min(mouse_pos.x),
+					y: start.y.min(mouse_pos.y),
+					w: (mouse_pos.x - start.x).abs(),
+					h: (mouse_pos.y - start.y).abs(),
+				});
+			}
+			if ctx.mouse.button_just_released(input::mouse::MouseButton::Left) {
+				self.selection_start = None;
+			}
+			//* ------------------------------------------------------------------------
+
 			//* -------------------Logic to drag the component--------------------------
 			if ctx.mouse.button_pressed(input::mouse::MouseButton::Left) && 
-			!self.add_element[3] {
+			!self.add_element[3] && self.selection_start.is_none() {
     			let mouse_pos = ctx.mouse.position();
 
     			// Initiate dragging
-    			if self.dragging_index.is_none() && !self.add_element[2] {
-        			for (i, component) in self.circuit.components.iter().enumerate() {
+    			if self.dragging_id.is_none() && !self.add_element[2] {
+        			for (&cid, component) in self.circuit.components.iter() {
             			let hitbox = component.get_hitbox();
             			if hitbox.contains(mouse_pos) {
                 			let component_pos = component.get_position();
@@ -353,28 +1352,37 @@ impl EventHandler for State {
                     			x: mouse_pos.x - component_pos.x,
                     			y: mouse_pos.y - component_pos.y,
                 			});
-                			self.dragging_index = Some(i);
+                			self.dragging_id = Some(cid);
+                			self.drag_start = Some(component_pos);
+                			// The last component clicked is the one shown in the properties window
+                			self.selected_component = Some(cid);
+                			self.property_delay = component.get_delay();
+                			self.property_name = match component {
+                				LogicElements::Source(source) => source.name.clone().unwrap_or_default(),
+                				LogicElements::Leds(led) => led.name.clone().unwrap_or_default(),
+                				_ => String::new(),
+                			};
                 			break;
             			}
         			}
     			}
 
     			// Update position while dragging
-    			if let Some(index) = self.dragging_index {
+    			if let Some(cid) = self.dragging_id {
         			if let Some(offset) = self.drag_offset {
             			let new_position = Point2 {
                 			x: mouse_pos.x - offset.x,
                 			y: mouse_pos.y - offset.y,
             			};
-            		self.circuit.components[index].update_postion(new_position);
+            		self.circuit.move_element(cid, new_position);
         			}
     			}
 			}
 
 			// Snap to grid on mouse release
 			if ctx.mouse.button_just_released(input::mouse::MouseButton::Left) {
-    			if let Some(index) = self.dragging_index {
-        			let component = &self.circuit.components[index];
+    			if let Some(component) = self.dragging_id.and_then(|cid| self.circuit.components.get(&cid)) {
+        			let cid = component.get_id();
 
         			// Get the current position of the reference pin
         			let reference_pin = component.get_refpin_pos();
@@ -395,18 +1403,29 @@ impl EventHandler for State {
             			y: component.get_position().y + displacement.y,
         			};
 
-        			self.circuit.components[index].update_postion(new_position);
+        			self.circuit.move_element(cid, new_position);
+
+        			// The whole drag is a single move in the history
+        			if let Some(start) = self.drag_start {
+        				if start != new_position {
+        					self.history.record(Command::MoveComponent { cid, from: start, to: new_position });
+        				}
+        			}
     			}
 
     			// Clear drag state
-    			self.dragging_index = None;
+    			self.dragging_id = None;
     			self.drag_offset = None;
+    			self.drag_start = None;
 			}
 			//* -------------------------------------------------------------------
 
 			//* -------Simulation---------
 			if self.add_element[6] {
-				self.circuit.simulate();
+				let _ = self.circuit.simulate();
+			}
+			if self.clocks_running {
+				let _ = self.circuit.advance(self.clock_speed);
 			}
 			//* --------------------------
 		});
@@ -419,12 +1438,36 @@ impl EventHandler for State {
 		// Draw the grid
 		canvas.draw(&self.grid_image, DrawParam::default());
         // Draw all the components's images by iterating over the components vec
-		for component in self.circuit.components.iter() {
+		for component in self.circuit.components.values() {
 			if let Some(image) = component.get_image(ctx) {
 				let draw_params = DrawParam::default()
 					.dest(component.get_position())
 					.scale(ggez::glam::Vec2::new(0.5, 0.5)); 
 				canvas.draw(&image, draw_params);
+			} else {
+				// Components without an image are drawn as a box with their pins
+				let body = Mesh::new_rectangle(ctx, DrawMode::stroke(2.0), component.get_hitbox().into(), Color::BLACK)?;
+				canvas.draw(&body, DrawParam::default());
+				for pin_hitbox in component.get_pins_hitbox() {
+					let pin = Mesh::new_rectangle(ctx, DrawMode::fill(), pin_hitbox.rect.into(), Color::BLACK)?;
+					canvas.draw(&pin, DrawParam::default());
+				}
+			}
+
+			// Shift registers show the bits they store
+			if let LogicElements::ShiftRegisters(shift_register) = component {
+				for (cell, signal) in shift_register.cells() {
+					let color = match signal {
+						Signal::On => Color::GREEN,
+						Signal::Off => Color::WHITE,
+						Signal::Undefined | Signal::HighZ => Color::from_rgb(150, 150, 150),
+						Signal::Conflict => Color::RED,
+					};
+					let fill = Mesh::new_rectangle(ctx, DrawMode::fill(), cell.into(), color)?;
+					canvas.draw(&fill, DrawParam::default());
+					let border = Mesh::new_rectangle(ctx, DrawMode::stroke(1.0), cell.into(), Color::BLACK)?;
+					canvas.draw(&border, DrawParam::default());
+				}
 			}
 		}
 
@@ -481,6 +1524,22 @@ impl EventHandler for State {
 			}
 		}*/
 		//---------------------------------------------------------
+
+		// Outline the components involved in the errors
+		for cid in self.circuit.all_errors().flat_map(|error| error.components()) {
+			if let Some(component) = self.circuit.components.get(&cid) {
+				let outline = Mesh::new_rectangle(ctx, DrawMode::stroke(3.0), component.get_hitbox().into(), Color::RED)?;
+				canvas.draw(&outline, DrawParam::default());
+			}
+		}
+
+		// Draw the selection rectangle
+		if let Some(area) = self.selection {
+			if area.w > 0.0 && area.h > 0.0 {
+				let selection_mesh = Mesh::new_rectangle(ctx, DrawMode::stroke(1.0), area, Color::BLUE)?;
+				canvas.draw(&selection_mesh, DrawParam::default());
+			}
+		}
 		
         // Draw the GUI
         canvas.draw(&self.gui, DrawParam::default());
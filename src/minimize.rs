@@ -0,0 +1,342 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::error::SimError;
+use crate::expression::Expr;
+use crate::truth_table::MAX_TRUTH_TABLE_INPUTS;
+
+// Karnaugh maps are drawn for functions of up to this many variables
+pub const MAX_KARNAUGH_VARIABLES: usize = 6;
+
+// Number of choices tried when looking for the smallest cover, the best cover found so far is kept
+// once it is reached, which only happens with many variables
+const COVER_SEARCH_LIMIT: usize = 100_000;
+
+/// Boolean function given by the rows of its truth table where it is true. The first variable is
+/// the most significant bit of the row numbers, like in the truth tables.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BooleanFunction {
+    pub variables: Vec<String>,
+    pub minterms: Vec<usize>,
+}
+
+/// Product of literals: the variables whose bit is set in `mask` don't appear, the others appear
+/// as they are when their bit of `value` is set and complemented otherwise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Implicant {
+    pub value: usize,
+    pub mask: usize,
+}
+
+impl Implicant {
+    pub fn covers(&self, minterm: usize) -> bool {
+        minterm & !self.mask == self.value
+    }
+
+    fn literals(&self, count: usize) -> usize {
+        count - self.mask.count_ones() as usize
+    }
+
+    // The literals in the order of the variables, a product without literal is always true
+    pub fn to_expr(&self, variables: &[String]) -> Expr {
+        let count = variables.len();
+        let mut literals: Vec<Expr> = variables.iter().enumerate()
+            .filter(|(index, _)| self.mask >> (count - 1 - index) & 1 == 0)
+            .map(|(index, name)| match self.value >> (count - 1 - index) & 1 {
+                1 => Expr::Var(name.clone()),
+                _ => Expr::Not(Box::new(Expr::Var(name.clone()))),
+            })
+            .collect();
+        match literals.len() {
+            0 => Expr::Const(true),
+            1 => literals.remove(0),
+            _ => Expr::And(literals),
+        }
+    }
+}
+
+/// Values of a function laid out so that neighbouring cells differ by one variable. The rows are
+/// numbered by the first half of the variables and the columns by the others, both in Gray code order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KarnaughMap {
+    pub row_variables: Vec<String>,
+    pub column_variables: Vec<String>,
+    pub rows: Vec<usize>,
+    pub columns: Vec<usize>,
+    pub cells: Vec<Vec<bool>>,
+}
+
+// Values of `bits` bits in Gray code order
+fn gray_code(bits: usize) -> Vec<usize> {
+    (0..1usize << bits).map(|index| index ^ (index >> 1)).collect()
+}
+
+impl KarnaughMap {
+    // Label of a row or a column, the bits of its variables
+    pub fn label(value: usize, bits: usize) -> String {
+        (0..bits).rev().map(|bit| if value >> bit & 1 == 1 { '1' } else { '0' }).collect()
+    }
+}
+
+struct CoverSearch<'a> {
+    primes: &'a [Implicant],
+    count: usize,               // Number of variables
+    covering: HashMap<usize, Vec<usize>>, // Primes covering each minterm
+    best: Option<Vec<usize>>,
+    steps: usize,
+}
+
+impl CoverSearch<'_> {
+    // Fewer products first, then fewer literals
+    fn cost(&self, chosen: &[usize]) -> (usize, usize) {
+        (chosen.len(), chosen.iter().map(|&prime| self.primes[prime].literals(self.count)).sum())
+    }
+
+    fn search(&mut self, chosen: &mut Vec<usize>, uncovered: &[usize]) {
+        self.steps += 1;
+        if let Some(best) = &self.best {
+            if self.steps > COVER_SEARCH_LIMIT || chosen.len() > best.len() {
+                return;
+            }
+        }
+        // Cover first the minterm that has the fewest primes to choose from
+        let Some(&minterm) = uncovered.iter().min_by_key(|minterm| self.covering[minterm].len()) else {
+            if self.best.as_ref().is_none_or(|best| self.cost(chosen) < self.cost(best)) {
+                self.best = Some(chosen.clone());
+            }
+            return;
+        };
+        if self.best.as_ref().is_some_and(|best| chosen.len() >= best.len()) {
+            return;
+        }
+        for prime in self.covering[&minterm].clone() {
+            let remaining: Vec<usize> = uncovered.iter().copied().filter(|&m| !self.primes[prime].covers(m)).collect();
+            chosen.push(prime);
+            self.search(chosen, &remaining);
+            chosen.pop();
+        }
+    }
+}
+
+impl BooleanFunction {
+    // Evaluate an expression for every combination of the variables
+    pub fn from_expression(expr: &Expr, variables: Vec<String>) -> Result<Self, SimError> {
+        let count = variables.len();
+        if count > MAX_TRUTH_TABLE_INPUTS {
+            return Err(SimError::TooManySources { found: count, limit: MAX_TRUTH_TABLE_INPUTS });
+        }
+        let minterms = (0..1usize << count)
+            .filter(|row| {
+                expr.evaluate(&|name| {
+                    variables.iter().position(|variable| variable == name)
+                        .is_some_and(|index| row >> (count - 1 - index) & 1 == 1)
+                })
+            })
+            .collect();
+        Ok(BooleanFunction { variables, minterms })
+    }
+
+    //* Quine–McCluskey: products that differ by a single variable are merged, again and again,
+    //* the products that can't be merged anymore are the prime implicants
+    pub fn prime_implicants(&self) -> Vec<Implicant> {
+        let count = self.variables.len();
+        let mut current: BTreeSet<Implicant> = self.minterms.iter()
+            .map(|&minterm| Implicant { value: minterm, mask: 0 })
+            .collect();
+        let mut primes = Vec::new();
+        while !current.is_empty() {
+            let mut next = BTreeSet::new();
+            let mut merged = BTreeSet::new();
+            for implicant in &current {
+                for bit in (0..count).map(|bit| 1 << bit) {
+                    // Merged with the product having this variable set, found once from the one without it
+                    let other = Implicant { value: implicant.value | bit, mask: implicant.mask };
+                    if implicant.mask & bit == 0 && implicant.value & bit == 0 && current.contains(&other) {
+                        next.insert(Implicant { value: implicant.value, mask: implicant.mask | bit });
+                        merged.insert(*implicant);
+                        merged.insert(other);
+                    }
+                }
+            }
+            primes.extend(current.difference(&merged).copied());
+            current = next;
+        }
+        primes
+    }
+
+    // Smallest set of prime implicants covering all the minterms
+    pub fn minimal_cover(&self) -> Vec<Implicant> {
+        let primes = self.prime_implicants();
+        let covering: HashMap<usize, Vec<usize>> = self.minterms.iter()
+            .map(|&minterm| (minterm, (0..primes.len()).filter(|&prime| primes[prime].covers(minterm)).collect()))
+            .collect();
+
+        // The only prime covering a minterm is in every cover, the search is left with the rest
+        let mut essential: Vec<usize> = covering.values().filter(|covers| covers.len() == 1).map(|covers| covers[0]).collect();
+        essential.sort_unstable();
+        essential.dedup();
+        let uncovered: Vec<usize> = self.minterms.iter().copied()
+            .filter(|&minterm| !essential.iter().any(|&prime| primes[prime].covers(minterm)))
+            .collect();
+
+        let mut search = CoverSearch { primes: &primes, count: self.variables.len(), covering, best: None, steps: 0 };
+        search.search(&mut essential, &uncovered);
+        let mut cover: Vec<Implicant> = search.best.unwrap_or_default().into_iter().map(|prime| primes[prime]).collect();
+        cover.sort_by_key(|implicant| (implicant.literals(self.variables.len()), std::cmp::Reverse(implicant.value)));
+        cover
+    }
+
+    // Minimal sum of products of the function
+    pub fn minimize(&self) -> Expr {
+        let mut products: Vec<Expr> = self.minimal_cover().iter()
+            .map(|implicant| implicant.to_expr(&self.variables))
+            .collect();
+        match products.len() {
+            0 => Expr::Const(false),
+            1 => products.remove(0),
+            _ => Expr::Or(products),
+        }
+    }
+
+    // The Karnaugh map of the function, only for few variables
+    pub fn karnaugh_map(&self) -> Option<KarnaughMap> {
+        let count = self.variables.len();
+        if count > MAX_KARNAUGH_VARIABLES {
+            return None;
+        }
+        let row_bits = count / 2;
+        let column_bits = count - row_bits;
+        let rows = gray_code(row_bits);
+        let columns = gray_code(column_bits);
+        let cells = rows.iter()
+            .map(|row| columns.iter().map(|column| self.minterms.contains(&(row << column_bits | column))).collect())
+            .collect();
+        Some(KarnaughMap {
+            row_variables: self.variables[..row_bits].to_vec(),
+            column_variables: self.variables[row_bits..].to_vec(),
+            rows,
+            columns,
+            cells,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn function(text: &str, variables: &[&str]) -> BooleanFunction {
+        let variables = variables.iter().map(|name| name.to_string()).collect();
+        BooleanFunction::from_expression(&Expr::parse(text).unwrap(), variables).unwrap()
+    }
+
+    fn minterms(variables: &[&str], minterms: &[usize]) -> BooleanFunction {
+        BooleanFunction { variables: variables.iter().map(|name| name.to_string()).collect(), minterms: minterms.to_vec() }
+    }
+
+    // The sum of products is true on exactly the minterms of the function
+    fn assert_same_function(function: &BooleanFunction, expr: &Expr) {
+        let count = function.variables.len();
+        for row in 0..1usize << count {
+            let value = |name: &str| {
+                let index = function.variables.iter().position(|variable| variable == name).unwrap();
+                row >> (count - 1 - index) & 1 == 1
+            };
+            assert_eq!(expr.evaluate(&value), function.minterms.contains(&row), "{} on row {}", expr, row);
+        }
+    }
+
+    #[test]
+    fn the_first_variable_is_the_most_significant_bit() {
+        assert_eq!(function("a & !b", &["a", "b"]).minterms, vec![2]);
+        assert_eq!(function("a & !b", &["b", "a"]).minterms, vec![1]);
+        assert_eq!(function("a | b", &["a", "b"]).minterms, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn too_many_variables_are_refused() {
+        let variables: Vec<String> = (0..=MAX_TRUTH_TABLE_INPUTS).map(|index| format!("v{}", index)).collect();
+        let result = BooleanFunction::from_expression(&Expr::Const(true), variables);
+        assert!(matches!(result, Err(SimError::TooManySources { .. })));
+    }
+
+    #[test]
+    fn implicants_cover_the_rows_matching_their_literals() {
+        // a and !c, b doesn't appear
+        let implicant = Implicant { value: 0b100, mask: 0b010 };
+        let covered: Vec<usize> = (0..8).filter(|&row| implicant.covers(row)).collect();
+        assert_eq!(covered, vec![4, 6]);
+        let variables = ["a", "b", "c"].map(String::from);
+        assert_eq!(implicant.to_expr(&variables), Expr::parse("a & !c").unwrap());
+        assert_eq!(Implicant { value: 0, mask: 0b111 }.to_expr(&variables), Expr::Const(true));
+        assert_eq!(Implicant { value: 0b010, mask: 0b101 }.to_expr(&variables), Expr::parse("b").unwrap());
+    }
+
+    #[test]
+    fn prime_implicants_cant_be_merged_anymore() {
+        // Every minterm has two primes: a'b', a'c', b'c, bc', ac and ab
+        let mut primes = minterms(&["a", "b", "c"], &[0, 1, 2, 5, 6, 7]).prime_implicants();
+        primes.sort();
+        assert_eq!(primes, vec![
+            Implicant { value: 0, mask: 1 },
+            Implicant { value: 0, mask: 2 },
+            Implicant { value: 1, mask: 4 },
+            Implicant { value: 2, mask: 4 },
+            Implicant { value: 5, mask: 2 },
+            Implicant { value: 6, mask: 1 },
+        ]);
+        assert_eq!(minterms(&["a", "b"], &[0, 1, 2, 3]).prime_implicants(), vec![Implicant { value: 0, mask: 3 }]);
+    }
+
+    #[test]
+    fn covers_use_as_few_products_as_possible() {
+        // Without essential primes, three of the six primes are enough
+        let cyclic = minterms(&["a", "b", "c"], &[0, 1, 2, 5, 6, 7]);
+        assert_eq!(cyclic.minimal_cover().len(), 3);
+        assert_same_function(&cyclic, &cyclic.minimize());
+
+        // a'c' and ac are essential, then a'b' or b'c covers the last minterm
+        let function = minterms(&["a", "b", "c"], &[0, 1, 2, 5, 7]);
+        let cover = function.minimal_cover();
+        assert_eq!(cover.len(), 3);
+        assert!(cover.contains(&Implicant { value: 0, mask: 2 }) && cover.contains(&Implicant { value: 5, mask: 2 }));
+        assert_same_function(&function, &function.minimize());
+    }
+
+    #[test]
+    fn sums_of_products_are_minimal() {
+        assert_eq!(function("a & b | a & !b", &["a", "b"]).minimize(), Expr::parse("a").unwrap());
+        assert_eq!(function("a & b & c | a & b & !c | !a & b & c", &["a", "b", "c"]).minimize(), Expr::parse("a & b | b & c").unwrap());
+        assert_eq!(function("a ^ b", &["a", "b"]).minimize().to_string(), "a & !b | !a & b");
+        assert_eq!(function("a | !a", &["a"]).minimize(), Expr::Const(true));
+        assert_eq!(function("a & !a", &["a"]).minimize(), Expr::Const(false));
+    }
+
+    #[test]
+    fn minimized_functions_keep_their_rows() {
+        for text in ["a ^ b ^ c ^ d", "(a | b) & (c | d) & !(a & d)", "!a & !b & !c | a & b & c | b & !d"] {
+            let function = function(text, &["a", "b", "c", "d"]);
+            assert_same_function(&function, &function.minimize());
+        }
+    }
+
+    #[test]
+    fn karnaugh_maps_follow_the_gray_code() {
+        let map = function("a & !c | b", &["a", "b", "c"]).karnaugh_map().unwrap();
+        assert_eq!(map.row_variables, vec!["a"]);
+        assert_eq!(map.column_variables, vec!["b", "c"]);
+        assert_eq!(map.rows, vec![0, 1]);
+        assert_eq!(map.columns, vec![0, 1, 3, 2]);
+        assert_eq!(map.cells, vec![
+            vec![false, false, true, true],
+            vec![true, false, true, true],
+        ]);
+        assert_eq!(KarnaughMap::label(2, 3), "010");
+    }
+
+    #[test]
+    fn large_functions_have_no_karnaugh_map() {
+        let variables: Vec<String> = (0..=MAX_KARNAUGH_VARIABLES).map(|index| format!("v{}", index)).collect();
+        let function = BooleanFunction { variables, minterms: vec![] };
+        assert!(function.karnaugh_map().is_none());
+    }
+}
@@ -0,0 +1,309 @@
+#[cfg(feature = "gui")]
+use ggez::graphics::Image;
+use mint::Point2;
+#[cfg(feature = "gui")]
+use ggez::{Context, GameResult};
+
+use crate::logic_gates::DEFAULT_GATE_DELAY;
+use crate::structure::*;
+use crate::error::SimError;
+
+// Distance between two pins on the same side of the component
+const PIN_SPACING: f32 = 20.0;
+/// Largest number of select lines, 64 data lines
+pub const MAX_SELECT_BITS: usize = 6;
+
+// Undefined value of a pin carrying `bits` bits, a bus when there is more than one
+fn undefined(bits: usize) -> PinValue {
+    if bits > 1 {
+        PinValue::Multiple(vec![Signal::Undefined; bits])
+    } else {
+        PinValue::Single(Signal::Undefined)
+    }
+}
+
+// Low value of a pin carrying `bits` bits
+fn low(bits: usize) -> PinValue {
+    if bits > 1 {
+        PinValue::Multiple(vec![Signal::Off; bits])
+    } else {
+        PinValue::Single(Signal::Off)
+    }
+}
+
+// Check that a value has the width of the data pins
+fn has_width(value: &PinValue, bits: usize) -> bool {
+    match value {
+        PinValue::Single(_) => bits == 1,
+        PinValue::Multiple(signals) => bits > 1 && signals.len() == bits,
+    }
+}
+
+// Index selected by the select lines, the first one is the least significant bit.
+// Undefined select lines don't select anything
+fn selected(select: &[Pin]) -> Option<usize> {
+    select.iter().enumerate().try_fold(0, |index, (bit, pin)| match pin.value {
+        PinValue::Single(Signal::On) => Some(index | 1 << bit),
+        PinValue::Single(Signal::Off) => Some(index),
+        _ => None,
+    })
+}
+
+// Size of a component with `lines` pins on its long side and `select_bits` select lines at the bottom
+fn block_size(lines: usize, select_bits: usize) -> (f32, f32) {
+    let width = (PIN_SPACING * (select_bits + 1) as f32).max(60.0);
+    let height = PIN_SPACING * (lines + 1) as f32;
+    (width, height)
+}
+
+/// 2^k:1 multiplexer: the data inputs (ioc 1) are on the left side, the select lines (ioc 2) at the
+/// bottom and the output (ioc 0) on the right side. Data pins are buses when `bits` is more than 1
+#[derive(Debug, Clone)]
+pub struct Multiplexer {
+    pub id: usize,
+    pub select_bits: usize,
+    pub bits: usize,
+    pub input: Vec<Pin>,
+    pub select: Vec<Pin>,
+    pub output: Pin,
+    pub position: Point2<f32>,
+    #[cfg(feature = "gui")]
+    pub image: Option<Image>,
+    pub hitbox: Hitbox,
+    pub ref_pin_pos: Point2<f32>,
+    pub delay: u64,     // Propagation delay in abstract time units
+}
+
+impl Multiplexer {
+    pub fn new(select_bits: usize, bits: usize) -> Self {
+        let select_bits = select_bits.clamp(1, MAX_SELECT_BITS);
+        let bits = bits.max(1);
+        let lines = 1 << select_bits;
+        let (width, height) = block_size(lines, select_bits);
+
+        Self {
+            id: 0,
+            select_bits,
+            bits,
+            input: (1..=lines).map(|pid| Pin::new(pid, 1, 0.0, PIN_SPACING * pid as f32, undefined(bits))).collect(),
+            select: (1..=select_bits)
+                .map(|pid| Pin::new(pid, 2, PIN_SPACING * pid as f32, height, PinValue::Single(Signal::Undefined)))
+                .collect(),
+            output: Pin::new(1, 0, width, PIN_SPACING * (lines / 2) as f32, undefined(bits)),
+            position: Point2 { x: 0.0, y: 0.0 },
+            #[cfg(feature = "gui")]
+            image: None,
+            hitbox: Hitbox {
+                rect: Rect { x: 0.0, y: 0.0, w: width, h: height },
+                r#type: HitboxType::Component,
+            },
+            ref_pin_pos: Point2 { x: 0.0, y: PIN_SPACING },
+            delay: DEFAULT_GATE_DELAY,
+        }
+    }
+
+    fn pins(&self) -> impl Iterator<Item = &Pin> {
+        self.input.iter().chain(&self.select).chain(std::iter::once(&self.output))
+    }
+
+    fn pins_mut(&mut self) -> impl Iterator<Item = &mut Pin> {
+        self.input.iter_mut().chain(&mut self.select).chain(std::iter::once(&mut self.output))
+    }
+
+    pub fn set_id(&mut self, id: usize) {
+        self.id = id;
+        set_pins_cid(self.pins_mut(), id);
+    }
+
+    pub fn get_pin(&mut self, pid: usize, ioc: usize) -> Result<&mut Pin, SimError> {
+        let id = self.id;
+        find_pin(self.pins_mut(), id, pid, ioc)
+    }
+
+    // Copy the selected input to the output
+    pub fn get_output(&mut self) {
+        self.output.value = match selected(&self.select) {
+            Some(index) if has_width(&self.input[index].value, self.bits) => self.input[index].value.clone(),
+            _ => undefined(self.bits),
+        };
+    }
+
+    #[cfg(feature = "gui")]
+    pub fn load_multiplexer_image(&mut self, ctx: &mut Context) -> GameResult<()> {
+        let image = Image::from_path(ctx, format!("/multiplexers/mux{}.png", 1 << self.select_bits))?;
+        self.image = Some(image);
+        Ok(())
+    }
+
+    pub fn update_multiplexer_position(&mut self, position: Point2<f32>) {
+        let (dx, dy) = move_component(&mut self.position, &mut self.hitbox, &mut self.ref_pin_pos, position);
+        move_pins(self.pins_mut(), dx, dy);
+    }
+
+    pub fn multiplexer_pins_hitbox(&self) -> Vec<Hitbox> {
+        pins_hitbox(self.pins())
+    }
+}
+
+/// 1:2^k demultiplexer: the data input (ioc 1) is on the left side, the select lines (ioc 2) at the
+/// bottom and the outputs (ioc 0) on the right side. The outputs that aren't selected are low
+#[derive(Debug, Clone)]
+pub struct Demultiplexer {
+    pub id: usize,
+    pub select_bits: usize,
+    pub bits: usize,
+    pub input: Pin,
+    pub select: Vec<Pin>,
+    pub output: Vec<Pin>,
+    pub position: Point2<f32>,
+    #[cfg(feature = "gui")]
+    pub image: Option<Image>,
+    pub hitbox: Hitbox,
+    pub ref_pin_pos: Point2<f32>,
+    pub delay: u64,     // Propagation delay in abstract time units
+}
+
+impl Demultiplexer {
+    pub fn new(select_bits: usize, bits: usize) -> Self {
+        let select_bits = select_bits.clamp(1, MAX_SELECT_BITS);
+        let bits = bits.max(1);
+        let lines = 1 << select_bits;
+        let (width, height) = block_size(lines, select_bits);
+        let input_y = PIN_SPACING * (lines / 2) as f32;
+
+        Self {
+            id: 0,
+            select_bits,
+            bits,
+            input: Pin::new(1, 1, 0.0, input_y, undefined(bits)),
+            select: (1..=select_bits)
+                .map(|pid| Pin::new(pid, 2, PIN_SPACING * pid as f32, height, PinValue::Single(Signal::Undefined)))
+                .collect(),
+            output: (1..=lines).map(|pid| Pin::new(pid, 0, width, PIN_SPACING * pid as f32, undefined(bits))).collect(),
+            position: Point2 { x: 0.0, y: 0.0 },
+            #[cfg(feature = "gui")]
+            image: None,
+            hitbox: Hitbox {
+                rect: Rect { x: 0.0, y: 0.0, w: width, h: height },
+                r#type: HitboxType::Component,
+            },
+            ref_pin_pos: Point2 { x: 0.0, y: input_y },
+            delay: DEFAULT_GATE_DELAY,
+        }
+    }
+
+    fn pins(&self) -> impl Iterator<Item = &Pin> {
+        std::iter::once(&self.input).chain(&self.select).chain(&self.output)
+    }
+
+    fn pins_mut(&mut self) -> impl Iterator<Item = &mut Pin> {
+        std::iter::once(&mut self.input).chain(&mut self.select).chain(&mut self.output)
+    }
+
+    pub fn set_id(&mut self, id: usize) {
+        self.id = id;
+        set_pins_cid(self.pins_mut(), id);
+    }
+
+    pub fn get_pin(&mut self, pid: usize, ioc: usize) -> Result<&mut Pin, SimError> {
+        let id = self.id;
+        find_pin(self.pins_mut(), id, pid, ioc)
+    }
+
+    // Copy the input to the selected output, without a valid selection every output is undefined
+    pub fn get_output(&mut self) {
+        let selection = selected(&self.select);
+        for (index, pin) in self.output.iter_mut().enumerate() {
+            pin.value = match selection {
+                Some(selected) if selected == index && has_width(&self.input.value, self.bits) => self.input.value.clone(),
+                Some(selected) if selected == index => undefined(self.bits),
+                Some(_) => low(self.bits),
+                None => undefined(self.bits),
+            };
+        }
+    }
+
+    #[cfg(feature = "gui")]
+    pub fn load_demultiplexer_image(&mut self, ctx: &mut Context) -> GameResult<()> {
+        let image = Image::from_path(ctx, format!("/multiplexers/demux{}.png", 1 << self.select_bits))?;
+        self.image = Some(image);
+        Ok(())
+    }
+
+    pub fn update_demultiplexer_position(&mut self, position: Point2<f32>) {
+        let (dx, dy) = move_component(&mut self.position, &mut self.hitbox, &mut self.ref_pin_pos, position);
+        move_pins(self.pins_mut(), dx, dy);
+    }
+
+    pub fn demultiplexer_pins_hitbox(&self) -> Vec<Hitbox> {
+        pins_hitbox(self.pins())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Drive the select lines with the bits of `index`, the first line is the least significant bit
+    fn select(pins: &mut [Pin], index: usize) {
+        for (bit, pin) in pins.iter_mut().enumerate() {
+            pin.value = PinValue::Single(if index >> bit & 1 == 1 { Signal::On } else { Signal::Off });
+        }
+    }
+
+    fn bus(bits: &[bool]) -> PinValue {
+        PinValue::Multiple(bits.iter().map(|&bit| if bit { Signal::On } else { Signal::Off }).collect())
+    }
+
+    #[test]
+    fn multiplexers_copy_the_selected_input() {
+        let mut mux = Multiplexer::new(2, 1);
+        for (index, pin) in mux.input.iter_mut().enumerate() {
+            pin.value = PinValue::Single(if index == 2 { Signal::On } else { Signal::Off });
+        }
+        for index in 0..4 {
+            select(&mut mux.select, index);
+            mux.get_output();
+            let expected = if index == 2 { Signal::On } else { Signal::Off };
+            assert_eq!(mux.output.value, PinValue::Single(expected));
+        }
+    }
+
+    #[test]
+    fn multiplexers_check_the_selection_and_the_width() {
+        let mut mux = Multiplexer::new(1, 2);
+        mux.input[0].value = bus(&[true, false]);
+        mux.input[1].value = PinValue::Single(Signal::On);
+        mux.get_output();
+        assert_eq!(mux.output.value, PinValue::Multiple(vec![Signal::Undefined; 2]));
+
+        select(&mut mux.select, 0);
+        mux.get_output();
+        assert_eq!(mux.output.value, bus(&[true, false]));
+        select(&mut mux.select, 1);
+        mux.get_output();
+        assert_eq!(mux.output.value, PinValue::Multiple(vec![Signal::Undefined; 2]));
+    }
+
+    #[test]
+    fn demultiplexers_route_the_input_to_the_selected_output() {
+        let mut demux = Demultiplexer::new(2, 3);
+        demux.input.value = bus(&[true, true, false]);
+        select(&mut demux.select, 1);
+        demux.get_output();
+        let outputs: Vec<PinValue> = demux.output.iter().map(|pin| pin.value.clone()).collect();
+        assert_eq!(outputs, vec![bus(&[false; 3]), bus(&[true, true, false]), bus(&[false; 3]), bus(&[false; 3])]);
+
+        demux.select[0].value = PinValue::Single(Signal::Undefined);
+        demux.get_output();
+        assert!(demux.output.iter().all(|pin| pin.value == PinValue::Multiple(vec![Signal::Undefined; 3])));
+    }
+
+    #[test]
+    fn sizes_are_clamped() {
+        let mux = Multiplexer::new(0, 0);
+        assert_eq!((mux.select_bits, mux.bits, mux.input.len()), (1, 1, 2));
+        let demux = Demultiplexer::new(MAX_SELECT_BITS + 1, 4);
+        assert_eq!(demux.output.len(), 1 << MAX_SELECT_BITS);
+    }
+}
@@ -0,0 +1,222 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// A pin of the circuit identified by (cid, ioc, pid)
+pub type PinId = (usize, usize, usize);
+
+/// A set of pins that are electrically connected
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Net {
+    pub drivers: Vec<PinId>,    // Pins driving the net (ioc = 0)
+    pub loads: Vec<PinId>,      // Pins reading the net (inputs and control pins)
+}
+
+impl Net {
+    // All the pins of the net, drivers first
+    pub fn pins(&self) -> impl Iterator<Item = &PinId> {
+        self.drivers.iter().chain(self.loads.iter())
+    }
+}
+
+/// Connectivity of a circuit: every net with its drivers and loads, and the net of every pin.
+/// The netlist only knows about pin ids, so it can be built and used without any geometry
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Netlist {
+    pub nets: Vec<Net>,
+    pin_net: HashMap<PinId, usize>,
+}
+
+impl Netlist {
+    // Build the netlist from groups of connected pins
+    pub fn from_groups<I>(groups: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: IntoIterator<Item = PinId>,
+    {
+        // Sort the pins and the nets so the same circuit always gives the same netlist
+        let mut groups: Vec<Vec<PinId>> = groups.into_iter()
+            .map(|group| {
+                let mut pins: Vec<PinId> = group.into_iter().collect();
+                pins.sort();
+                pins.dedup();
+                pins
+            })
+            .filter(|pins| !pins.is_empty())
+            .collect();
+        groups.sort();
+
+        let mut netlist = Netlist::default();
+        for pins in groups {
+            let (drivers, loads) = pins.into_iter().partition(|&(_, ioc, _)| ioc == 0);
+            netlist.push(Net { drivers, loads });
+        }
+        netlist
+    }
+
+    fn push(&mut self, net: Net) {
+        for &pin in net.pins() {
+            self.pin_net.insert(pin, self.nets.len());
+        }
+        self.nets.push(net);
+    }
+
+    // Index of the net a pin belongs to
+    pub fn net_of(&self, pin: PinId) -> Option<usize> {
+        self.pin_net.get(&pin).copied()
+    }
+
+    // The pins driving a net
+    pub fn drivers(&self, net: usize) -> &[PinId] {
+        &self.nets[net].drivers
+    }
+
+    // The pins reading a net
+    pub fn loads(&self, net: usize) -> &[PinId] {
+        &self.nets[net].loads
+    }
+
+    //* Groups of components that feed back into their own inputs, found as the strongly connected
+    //* components of the graph going from the components driving a net to the components reading it.
+    //* Only the components accepted by `combinational` are part of the graph, so loops going through
+    //* a storage element aren't reported
+    pub fn feedback_loops(&self, combinational: impl Fn(usize) -> bool) -> Vec<Vec<usize>> {
+        // Components reading the nets driven by each component
+        let mut edges: BTreeMap<usize, BTreeSet<usize>> = BTreeMap::new();
+        for net in &self.nets {
+            for &(driver, _, _) in net.drivers.iter().filter(|pin| combinational(pin.0)) {
+                let loads = net.loads.iter().map(|pin| pin.0).filter(|&load| combinational(load));
+                edges.entry(driver).or_default().extend(loads);
+            }
+        }
+
+        let mut search = LoopSearch {
+            edges: &edges,
+            index: HashMap::new(),
+            low: HashMap::new(),
+            stack: Vec::new(),
+            on_stack: BTreeSet::new(),
+            loops: Vec::new(),
+        };
+        for &cid in edges.keys() {
+            if !search.index.contains_key(&cid) {
+                search.visit(cid);
+            }
+        }
+        search.loops.sort();
+        search.loops
+    }
+}
+
+// State of Tarjan's algorithm for the strongly connected components
+struct LoopSearch<'a> {
+    edges: &'a BTreeMap<usize, BTreeSet<usize>>,
+    index: HashMap<usize, usize>,   // Order in which the components were visited
+    low: HashMap<usize, usize>,     // Smallest index reachable from each component
+    stack: Vec<usize>,
+    on_stack: BTreeSet<usize>,
+    loops: Vec<Vec<usize>>,
+}
+
+impl LoopSearch<'_> {
+    fn visit(&mut self, cid: usize) {
+        let order = self.index.len();
+        self.index.insert(cid, order);
+        self.low.insert(cid, order);
+        self.stack.push(cid);
+        self.on_stack.insert(cid);
+
+        let edges = self.edges;
+        for &next in edges.get(&cid).into_iter().flatten() {
+            if !self.index.contains_key(&next) {
+                self.visit(next);
+                self.low.insert(cid, self.low[&cid].min(self.low[&next]));
+            } else if self.on_stack.contains(&next) {
+                self.low.insert(cid, self.low[&cid].min(self.index[&next]));
+            }
+        }
+
+        // The component is the root of a strongly connected component
+        if self.low[&cid] == self.index[&cid] {
+            let mut group = Vec::new();
+            while let Some(member) = self.stack.pop() {
+                self.on_stack.remove(&member);
+                group.push(member);
+                if member == cid {
+                    break;
+                }
+            }
+            // A single component is only a loop when it reads its own output
+            let feeds_itself = edges.get(&cid).is_some_and(|loads| loads.contains(&cid));
+            if group.len() > 1 || feeds_itself {
+                group.sort_unstable();
+                self.loops.push(group);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pins_are_split_into_drivers_and_loads() {
+        let netlist = Netlist::from_groups([vec![(2, 1, 1), (1, 0, 1), (3, 2, 1)]]);
+        assert_eq!(netlist.nets.len(), 1);
+        assert_eq!(netlist.drivers(0), &[(1, 0, 1)]);
+        assert_eq!(netlist.loads(0), &[(2, 1, 1), (3, 2, 1)]);
+    }
+
+    #[test]
+    fn every_pin_knows_its_net() {
+        let netlist = Netlist::from_groups([vec![(1, 0, 1), (2, 1, 1)], vec![(2, 0, 1), (3, 1, 1)]]);
+        let net = netlist.net_of((2, 0, 1)).unwrap();
+        assert_eq!(netlist.net_of((3, 1, 1)), Some(net));
+        assert_ne!(netlist.net_of((1, 0, 1)), Some(net));
+        assert_eq!(netlist.net_of((4, 0, 1)), None);
+    }
+
+    #[test]
+    fn the_order_of_the_groups_does_not_matter() {
+        let a = Netlist::from_groups([vec![(2, 0, 1), (3, 1, 1)], vec![(1, 0, 1), (2, 1, 1), (2, 1, 1)]]);
+        let b = Netlist::from_groups([vec![(2, 1, 1), (1, 0, 1)], vec![(3, 1, 1), (2, 0, 1)], vec![]]);
+        assert_eq!(a, b);
+        assert_eq!(a.nets.len(), 2);
+    }
+
+    // Nets from the output of each component to the input of the next one
+    fn ring(components: &[usize]) -> Netlist {
+        Netlist::from_groups(components.iter().zip(components.iter().cycle().skip(1))
+            .map(|(&from, &to)| vec![(from, 0, 1), (to, 1, 1)]))
+    }
+
+    #[test]
+    fn chains_have_no_feedback_loops() {
+        let netlist = Netlist::from_groups([vec![(1, 0, 1), (2, 1, 1)], vec![(2, 0, 1), (3, 1, 1)]]);
+        assert!(netlist.feedback_loops(|_| true).is_empty());
+    }
+
+    #[test]
+    fn rings_are_feedback_loops() {
+        assert_eq!(ring(&[3, 1, 2]).feedback_loops(|_| true), vec![vec![1, 2, 3]]);
+        // A component reading its own output
+        assert_eq!(ring(&[4]).feedback_loops(|_| true), vec![vec![4]]);
+    }
+
+    #[test]
+    fn separate_loops_are_reported_apart() {
+        let mut groups: Vec<Vec<PinId>> = ring(&[1, 2]).nets.iter()
+            .map(|net| net.drivers.iter().chain(&net.loads).copied().collect())
+            .collect();
+        groups.extend(ring(&[5, 6, 7]).nets.iter().map(|net| net.drivers.iter().chain(&net.loads).copied().collect()));
+        // The first loop feeds the second one
+        groups.push(vec![(2, 0, 2), (5, 1, 2)]);
+        let netlist = Netlist::from_groups(groups);
+        assert_eq!(netlist.feedback_loops(|_| true), vec![vec![1, 2], vec![5, 6, 7]]);
+    }
+
+    #[test]
+    fn loops_through_storage_elements_are_not_reported() {
+        let netlist = ring(&[1, 2, 3]);
+        assert!(netlist.feedback_loops(|cid| cid != 2).is_empty());
+    }
+}
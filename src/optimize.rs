@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+
+use crate::circuit::{Circuit, LogicElements};
+use crate::error::SimError;
+use crate::expression::Expr;
+use crate::minimize::BooleanFunction;
+use crate::structure::*;
+use crate::truth_table::MAX_TRUTH_TABLE_INPUTS;
+
+/// Expression read by a led in the original circuit and in the optimized one
+#[derive(Debug, Clone, PartialEq)]
+pub struct OptimizedOutput {
+    pub name: String,
+    pub original: Expr,
+    pub optimized: Expr,
+}
+
+/// Circuit rebuilt with fewer gates, and whether it behaves like the original one
+#[derive(Debug, Clone)]
+pub struct Optimization {
+    pub outputs: Vec<OptimizedOutput>,
+    pub circuit: Circuit,
+    pub original_gates: usize,
+    pub optimized_gates: usize,
+    pub equivalent: bool,
+}
+
+fn gate_count(circuit: &Circuit) -> usize {
+    circuit.components.values().filter(|component| matches!(component, LogicElements::Gates(_))).count()
+}
+
+// Replace the variables of the constant sources by their value
+fn fix_constants(expr: &Expr, constants: &HashMap<String, bool>) -> Expr {
+    let fix = |operands: &[Expr]| operands.iter().map(|operand| fix_constants(operand, constants)).collect();
+    match expr {
+        Expr::Var(name) => constants.get(name).map_or_else(|| expr.clone(), |&value| Expr::Const(value)),
+        Expr::Const(_) => expr.clone(),
+        Expr::Not(inner) => Expr::Not(Box::new(fix_constants(inner, constants))),
+        Expr::And(operands) => Expr::And(fix(operands)),
+        Expr::Or(operands) => Expr::Or(fix(operands)),
+        Expr::Xor(operands) => Expr::Xor(fix(operands)),
+    }
+}
+
+// Smallest of the simplified expression and of its simplified minimal sum of products
+fn optimize_expression(expr: &Expr) -> Expr {
+    let simplified = expr.simplify();
+    let variables = simplified.variables();
+    match BooleanFunction::from_expression(&simplified, variables) {
+        Ok(function) => {
+            let minimized = function.minimize().simplify();
+            if minimized.gate_count() < simplified.gate_count() { minimized } else { simplified }
+        }
+        // Too many variables to go through the truth table
+        Err(_) => simplified,
+    }
+}
+
+impl Circuit {
+    //* Rebuild a combinational circuit with fewer gates: the expression of every led is simplified,
+    //* or replaced by its minimal sum of products when that one needs fewer gates, and a new laid out
+    //* circuit is built from them. The sources listed in `constants` keep their current value and
+    //* are folded into the expressions. The truth tables of both circuits are compared at the end.
+    pub fn optimize(&self, constants: &[usize]) -> Result<Optimization, SimError> {
+        let constant_values: HashMap<String, bool> = self.components.iter()
+            .filter(|(cid, _)| constants.contains(cid))
+            .filter_map(|(_, component)| match component {
+                LogicElements::Source(source) => Some((source.label(), source.output.value.bit(0) == Signal::On)),
+                _ => None,
+            })
+            .collect();
+
+        let mut outputs = Vec::new();
+        for (&cid, component) in &self.components {
+            if let LogicElements::Leds(led) = component {
+                let original = self.led_expression(cid)?;
+                let optimized = optimize_expression(&fix_constants(&original, &constant_values));
+                outputs.push(OptimizedOutput { name: led.label(), original, optimized });
+            }
+        }
+
+        let expressions: Vec<(String, Expr)> = outputs.iter()
+            .map(|output| (output.name.clone(), output.optimized.clone()))
+            .collect();
+        let circuit = Circuit::from_expressions(&expressions)?;
+        let equivalent = self.equivalent(&circuit, constants)?;
+        Ok(Optimization {
+            outputs,
+            original_gates: gate_count(self),
+            optimized_gates: gate_count(&circuit),
+            circuit,
+            equivalent,
+        })
+    }
+
+    //* Compare the truth tables of two circuits: every combination of the values of the sources of
+    //* this circuit, except the constant ones, is given to them and to the sources of `other` named
+    //* after their label. Only the sources of `other` given a name are driven, so the unnamed ones,
+    //* like the constants of a built circuit, keep their value even when their label is the same.
+    //* The leds, taken in order, must have the same names and read the same values
+    pub fn equivalent(&self, other: &Circuit, constants: &[usize]) -> Result<bool, SimError> {
+        let inputs: Vec<(usize, Option<usize>)> = self.components.iter()
+            .filter(|(cid, _)| !constants.contains(cid))
+            .filter_map(|(&cid, component)| match component {
+                LogicElements::Source(source) => Some((cid, source.label())),
+                _ => None,
+            })
+            .map(|(cid, name)| {
+                let matching = other.components.iter().find_map(|(&other_cid, component)| match component {
+                    LogicElements::Source(source) if source.name.as_deref() == Some(name.as_str()) => Some(other_cid),
+                    _ => None,
+                });
+                (cid, matching)
+            })
+            .collect();
+        if inputs.len() > MAX_TRUTH_TABLE_INPUTS {
+            return Err(SimError::TooManySources { found: inputs.len(), limit: MAX_TRUTH_TABLE_INPUTS });
+        }
+
+        let count = inputs.len();
+        let mut circuit = self.clone();
+        let mut other = other.clone();
+        for combination in 0..1usize << count {
+            for (index, &(cid, matching)) in inputs.iter().enumerate() {
+                let value = if combination >> (count - 1 - index) & 1 == 1 { Signal::On } else { Signal::Off };
+                circuit.set_source_value(cid, value);
+                if let Some(matching) = matching {
+                    other.set_source_value(matching, value);
+                }
+            }
+            circuit.simulate()?;
+            other.simulate()?;
+            if circuit.led_values() != other.led_values() {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn circuit(texts: &[&str]) -> Circuit {
+        let outputs: Vec<(String, Expr)> = texts.iter().map(|text| Expr::parse_assignment(text).unwrap()).collect();
+        Circuit::from_expressions(&outputs).unwrap()
+    }
+
+    #[test]
+    fn constant_sources_are_not_driven_like_inputs_of_the_same_label() {
+        // Z is always 0, its optimized circuit gets an unnamed constant source labelled in_2
+        let original = circuit(&["Y = in_1", "Z = in_2 & !in_2"]);
+        let optimization = original.optimize(&[]).unwrap();
+        let constant = optimization.circuit.components.values().find_map(|component| match component {
+            LogicElements::Source(source) if source.name.is_none() => Some(source.label()),
+            _ => None,
+        });
+        assert_eq!(constant.as_deref(), Some("in_2"));
+        assert!(optimization.equivalent);
+    }
+
+    #[test]
+    fn optimized_circuits_need_fewer_gates() {
+        let original = circuit(&["Y = !(!a & !b) | a & b & c"]);
+        let optimization = original.optimize(&[]).unwrap();
+        assert_eq!(optimization.outputs.len(), 1);
+        assert_eq!(optimization.outputs[0].name, "Y");
+        assert_eq!(optimization.outputs[0].optimized, Expr::parse("a | b").unwrap());
+        assert_eq!((optimization.original_gates, optimization.optimized_gates), (5, 1));
+        assert!(optimization.equivalent);
+    }
+
+    #[test]
+    fn constant_sources_are_folded() {
+        let original = circuit(&["Y = a & k | !k & b"]);
+        let constant = original.find_source("k").unwrap();
+        let optimization = original.optimize(&[constant]).unwrap();
+        // k is 0 in the built circuit
+        assert_eq!(optimization.outputs[0].optimized, Expr::parse("b").unwrap());
+        assert_eq!(optimization.circuit.find_source("k"), None);
+        assert!(optimization.equivalent);
+    }
+
+    #[test]
+    fn circuits_with_other_values_are_not_equivalent() {
+        let and = circuit(&["Y = a & b"]);
+        assert!(!and.equivalent(&circuit(&["Y = a | b"]), &[]).unwrap());
+        assert!(!and.equivalent(&circuit(&["Z = a & b"]), &[]).unwrap());
+        assert!(and.equivalent(&circuit(&["Y = !(!a | !b)"]), &[]).unwrap());
+    }
+}
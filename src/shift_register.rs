@@ -0,0 +1,285 @@
+#[cfg(feature = "gui")]
+use ggez::graphics::Image;
+use mint::Point2;
+#[cfg(feature = "gui")]
+use ggez::{Context, GameResult};
+use serde::{Deserialize, Serialize};
+
+use crate::logic_gates::DEFAULT_GATE_DELAY;
+use crate::structure::*;
+use crate::error::SimError;
+
+const BLOCK_HEIGHT: f32 = 60.0;
+// Size of the cell drawn for every stored bit
+pub const CELL_SIZE: f32 = 10.0;
+pub const CELL_SPACING: f32 = 12.0;
+
+// Input pins (ioc 1)
+pub const SERIAL_IN_PIN: usize = 1;
+pub const PARALLEL_IN_PIN: usize = 2;
+
+// Control pins (ioc 2)
+pub const CLOCK_PIN: usize = 1;
+pub const LOAD_PIN: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ShiftRegisterMode {
+    Siso,
+    Sipo,
+    Piso,
+    Pipo,
+}
+
+impl ShiftRegisterMode {
+    pub fn parallel_in(&self) -> bool {
+        matches!(self, ShiftRegisterMode::Piso | ShiftRegisterMode::Pipo)
+    }
+
+    pub fn parallel_out(&self) -> bool {
+        matches!(self, ShiftRegisterMode::Sipo | ShiftRegisterMode::Pipo)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ShiftDirection {
+    Left,
+    Right,
+}
+
+/// Shift register, the stages are numbered from left to right and bit i of the parallel buses is stage i.
+/// - On the rising edge of the clock (ioc 2, pid 1) the bits move one stage in the shift direction
+///   and the serial input (ioc 1, pid 1) enters the stage they move away from: the first stage when
+///   shifting right, the last one when shifting left.
+/// - The parallel input modes load the bus on the parallel input (ioc 1, pid 2) instead when the
+///   load pin (ioc 2, pid 2) is high.
+/// - The output (ioc 0) is the stage the bits leave from for the serial output modes, the last stage
+///   when shifting right and the first one when shifting left, and every stage for the parallel
+///   output modes. Parallel pins of a single stage register are single bit pins.
+#[derive(Debug, Clone)]
+pub struct ShiftRegister {
+    pub id: usize,
+    pub mode: ShiftRegisterMode,
+    pub direction: ShiftDirection,
+    pub bits: usize,
+    pub input: Vec<Pin>,
+    pub control: Vec<Pin>,
+    pub output: Pin,
+    pub state: Vec<Signal>,
+    last_clock: Signal,     // Value of the clock at the last evaluation, to detect the edges
+    pub position: Point2<f32>,
+    #[cfg(feature = "gui")]
+    pub image: Option<Image>,
+    pub hitbox: Hitbox,
+    pub ref_pin_pos: Point2<f32>,
+    pub delay: u64,     // Propagation delay in abstract time units
+}
+
+impl ShiftRegister {
+    pub fn new(mode: ShiftRegisterMode, direction: ShiftDirection, bits: usize) -> Self {
+        let bits = bits.max(1);
+        let width = (2.0 * CELL_SIZE + CELL_SPACING * bits as f32).max(60.0);
+        let single = || PinValue::Single(Signal::Undefined);
+        let bus = || PinValue::from_signals(vec![Signal::Undefined; bits]);
+
+        // Serial input on the left side, the parallel one below it
+        let mut input = vec![Pin::new(SERIAL_IN_PIN, 1, 0.0, 20.0, single())];
+        // Clock and load at the bottom
+        let mut control = vec![Pin::new(CLOCK_PIN, 2, 20.0, BLOCK_HEIGHT, single())];
+        if mode.parallel_in() {
+            input.push(Pin::new(PARALLEL_IN_PIN, 1, 0.0, 40.0, bus()));
+            control.push(Pin::new(LOAD_PIN, 2, 40.0, BLOCK_HEIGHT, single()));
+        }
+        let output = Pin::new(1, 0, width, 20.0, if mode.parallel_out() { bus() } else { single() });
+
+        let mut shift_register = Self {
+            id: 0,
+            mode,
+            direction,
+            bits,
+            input,
+            control,
+            output,
+            state: vec![Signal::Off; bits],
+            last_clock: Signal::Undefined,
+            position: Point2 { x: 0.0, y: 0.0 },
+            #[cfg(feature = "gui")]
+            image: None,
+            hitbox: Hitbox {
+                rect: Rect { x: 0.0, y: 0.0, w: width, h: BLOCK_HEIGHT },
+                r#type: HitboxType::Component,
+            },
+            ref_pin_pos: Point2 { x: 0.0, y: 20.0 },
+            delay: DEFAULT_GATE_DELAY,
+        };
+        shift_register.update_output();
+        shift_register
+    }
+
+    fn pins(&self) -> impl Iterator<Item = &Pin> {
+        self.input.iter().chain(&self.control).chain(std::iter::once(&self.output))
+    }
+
+    fn pins_mut(&mut self) -> impl Iterator<Item = &mut Pin> {
+        self.input.iter_mut().chain(&mut self.control).chain(std::iter::once(&mut self.output))
+    }
+
+    pub fn set_id(&mut self, id: usize) {
+        self.id = id;
+        set_pins_cid(self.pins_mut(), id);
+    }
+
+    pub fn get_pin(&mut self, pid: usize, ioc: usize) -> Result<&mut Pin, SimError> {
+        let id = self.id;
+        find_pin(self.pins_mut(), id, pid, ioc)
+    }
+
+    pub fn get_output(&mut self) {
+        let clock = self.control[0].signal();
+        if self.last_clock == Signal::Off && clock == Signal::On {
+            let load = self.mode.parallel_in() && self.control[1].signal() == Signal::On;
+            if load {
+                // A bus of the wrong width can't be loaded
+                let data = &self.input[1].value;
+                self.state = if data.width() == self.bits {
+                    (0..self.bits).map(|bit| data.bit(bit).read()).collect()
+                } else {
+                    vec![Signal::Undefined; self.bits]
+                };
+            } else {
+                let serial_in = self.input[0].signal();
+                match self.direction {
+                    ShiftDirection::Right => {
+                        self.state.pop();
+                        self.state.insert(0, serial_in);
+                    }
+                    ShiftDirection::Left => {
+                        self.state.remove(0);
+                        self.state.push(serial_in);
+                    }
+                }
+            }
+        }
+        self.last_clock = clock;
+        self.update_output();
+    }
+
+    // The serial output is the stage the bits leave the register from
+    fn update_output(&mut self) {
+        self.output.value = if self.mode.parallel_out() {
+            PinValue::from_signals(self.state.clone())
+        } else {
+            match self.direction {
+                ShiftDirection::Right => PinValue::Single(self.state[self.bits - 1]),
+                ShiftDirection::Left => PinValue::Single(self.state[0]),
+            }
+        };
+    }
+
+    // Rectangles of the cells showing the stored bits, from the first stage to the last one
+    pub fn cells(&self) -> Vec<(Rect, Signal)> {
+        self.state.iter()
+            .enumerate()
+            .map(|(index, signal)| {
+                let rect = Rect {
+                    x: self.hitbox.rect.x + CELL_SIZE + CELL_SPACING * index as f32,
+                    y: self.hitbox.rect.y + (BLOCK_HEIGHT - CELL_SIZE) / 2.0,
+                    w: CELL_SIZE,
+                    h: CELL_SIZE,
+                };
+                (rect, *signal)
+            })
+            .collect()
+    }
+
+    #[cfg(feature = "gui")]
+    pub fn load_shift_register_image(&mut self, ctx: &mut Context) -> GameResult<()> {
+        let path = match self.mode {
+            ShiftRegisterMode::Siso => "/shift_registers/siso.png",
+            ShiftRegisterMode::Sipo => "/shift_registers/sipo.png",
+            ShiftRegisterMode::Piso => "/shift_registers/piso.png",
+            ShiftRegisterMode::Pipo => "/shift_registers/pipo.png",
+        };
+        self.image = Some(Image::from_path(ctx, path)?);
+        Ok(())
+    }
+
+    pub fn update_shift_register_position(&mut self, position: Point2<f32>) {
+        let (dx, dy) = move_component(&mut self.position, &mut self.hitbox, &mut self.ref_pin_pos, position);
+        move_pins(self.pins_mut(), dx, dy);
+    }
+
+    pub fn shift_register_pins_hitbox(&self) -> Vec<Hitbox> {
+        pins_hitbox(self.pins())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Signal::{Off, On};
+
+    // Rising edge of the clock with the serial input at `serial_in`
+    fn shift(shift_register: &mut ShiftRegister, serial_in: Signal) {
+        shift_register.input[0].value = PinValue::Single(serial_in);
+        for clock in [Off, On] {
+            shift_register.control[0].value = PinValue::Single(clock);
+            shift_register.get_output();
+        }
+    }
+
+    #[test]
+    fn shifting_right_enters_the_first_stage() {
+        let mut shift_register = ShiftRegister::new(ShiftRegisterMode::Sipo, ShiftDirection::Right, 3);
+        shift(&mut shift_register, On);
+        assert_eq!(shift_register.state, vec![On, Off, Off]);
+        shift(&mut shift_register, Off);
+        assert_eq!(shift_register.output.value, PinValue::Multiple(vec![Off, On, Off]));
+    }
+
+    #[test]
+    fn shifting_left_enters_the_last_stage() {
+        let mut shift_register = ShiftRegister::new(ShiftRegisterMode::Sipo, ShiftDirection::Left, 3);
+        shift(&mut shift_register, On);
+        assert_eq!(shift_register.state, vec![Off, Off, On]);
+        shift(&mut shift_register, Off);
+        assert_eq!(shift_register.state, vec![Off, On, Off]);
+    }
+
+    #[test]
+    fn serial_outputs_are_the_stage_the_bits_leave_from() {
+        for direction in [ShiftDirection::Right, ShiftDirection::Left] {
+            let mut shift_register = ShiftRegister::new(ShiftRegisterMode::Siso, direction, 3);
+            shift(&mut shift_register, On);
+            for _ in 1..3 {
+                assert_eq!(shift_register.output.value, PinValue::Single(Off));
+                shift(&mut shift_register, Off);
+            }
+            assert_eq!(shift_register.output.value, PinValue::Single(On));
+        }
+    }
+
+    #[test]
+    fn parallel_inputs_load_when_enabled() {
+        let mut shift_register = ShiftRegister::new(ShiftRegisterMode::Pipo, ShiftDirection::Right, 2);
+        shift_register.input[1].value = PinValue::Multiple(vec![On, On]);
+        shift_register.control[1].value = PinValue::Single(On);
+        shift(&mut shift_register, Off);
+        assert_eq!(shift_register.state, vec![On, On]);
+
+        shift_register.control[1].value = PinValue::Single(Off);
+        shift(&mut shift_register, Off);
+        assert_eq!(shift_register.state, vec![Off, On]);
+    }
+
+    #[test]
+    fn single_stage_registers_have_single_bit_pins() {
+        let mut shift_register = ShiftRegister::new(ShiftRegisterMode::Pipo, ShiftDirection::Left, 1);
+        assert_eq!(shift_register.input[1].value, PinValue::Single(Signal::Undefined));
+        shift_register.input[1].value = PinValue::Single(On);
+        shift_register.control[1].value = PinValue::Single(On);
+        shift(&mut shift_register, Off);
+        assert_eq!(shift_register.output.value, PinValue::Single(On));
+    }
+}
@@ -0,0 +1,258 @@
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+
+use crate::structure::PinValue;
+
+/// Upper bound on the number of component evaluations a single `simulate` call may run
+pub const MAX_EVALUATIONS: usize = 100_000;
+/// How far the simulated time may advance during a single `simulate` call
+pub const SETTLE_TIME_LIMIT: u64 = 10_000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    // Evaluate the component with the given cid from the values on its inputs
+    Evaluate(usize),
+    // Put a new value on the output pin (cid, pid) of a component
+    Output(usize, usize, PinValue),
+    // Edge of the clock with the given cid
+    Tick(usize),
+}
+
+/// Time ordered queue of the events waiting to be processed by the simulation engine.
+/// Events scheduled for the same time are processed in the order they were scheduled.
+#[derive(Debug, Clone, Default)]
+pub struct EventQueue {
+    events: BTreeMap<u64, VecDeque<Event>>,
+    queued: HashSet<(u64, usize)>,                  // Evaluations already waiting, as (time, cid)
+    pending: HashMap<(usize, usize), (u64, PinValue)>,  // Output change waiting on each output pin (cid, pid), with its time
+    activity: usize,                                // Number of events that aren't clock edges
+}
+
+impl EventQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Schedule the evaluation of the component with the given cid
+    pub fn schedule_evaluation(&mut self, time: u64, cid: usize) {
+        if self.queued.insert((time, cid)) {
+            self.activity += 1;
+            self.events.entry(time).or_default().push_back(Event::Evaluate(cid));
+        }
+    }
+
+    // Schedule the next edge of a clock
+    pub fn schedule_tick(&mut self, time: u64, cid: usize) {
+        self.events.entry(time).or_default().push_back(Event::Tick(cid));
+    }
+
+    // Schedule a new value on an output pin, `current` is the value the pin has right now.
+    // Outputs have an inertial delay: a pin has at most one change waiting, a new value replaces it
+    // even when it comes earlier, and a value going back to the current one just cancels it.
+    // Nothing is scheduled if the pin is already going to end up with that value
+    pub fn schedule_output(&mut self, time: u64, cid: usize, pid: usize, value: PinValue, current: &PinValue) {
+        if let Some((pending_time, pending_value)) = self.pending.remove(&(cid, pid)) {
+            if pending_value == value {
+                self.pending.insert((cid, pid), (pending_time, pending_value));
+                return;
+            }
+            self.cancel_output(pending_time, cid, pid);
+        }
+        if *current != value {
+            self.pending.insert((cid, pid), (time, value.clone()));
+            self.activity += 1;
+            self.events.entry(time).or_default().push_back(Event::Output(cid, pid, value));
+        }
+    }
+
+    // Drop the output change waiting at `time` on the pin (cid, pid)
+    fn cancel_output(&mut self, time: u64, cid: usize, pid: usize) {
+        if let Some(events) = self.events.get_mut(&time) {
+            let count = events.len();
+            events.retain(|event| !matches!(event, Event::Output(c, p, _) if (*c, *p) == (cid, pid)));
+            self.activity -= count - events.len();
+            if events.is_empty() {
+                self.events.remove(&time);
+            }
+        }
+    }
+
+    // Time of the earliest event in the queue
+    pub fn next_time(&self) -> Option<u64> {
+        self.events.keys().next().copied()
+    }
+
+    // Take the earliest event in the queue together with its time
+    pub fn pop(&mut self) -> Option<(u64, Event)> {
+        let mut entry = self.events.first_entry()?;
+        let time = *entry.key();
+        let event = entry.get_mut().pop_front()?;
+        if entry.get().is_empty() {
+            entry.remove();
+        }
+        match event {
+            Event::Evaluate(cid) => {
+                self.queued.remove(&(time, cid));
+                self.activity -= 1;
+            }
+            Event::Output(cid, pid, _) => {
+                self.pending.remove(&(cid, pid));
+                self.activity -= 1;
+            }
+            Event::Tick(_) => (),
+        }
+        Some((time, event))
+    }
+
+    // Whether there are events left other than clock edges, when there are none
+    // the circuit settled
+    pub fn has_activity(&self) -> bool {
+        self.activity > 0
+    }
+
+    // Time of the next clock edge
+    pub fn next_tick(&self) -> Option<u64> {
+        self.events.iter()
+            .find(|(_, events)| events.iter().any(|event| matches!(event, Event::Tick(_))))
+            .map(|(time, _)| *time)
+    }
+
+    // Components with an evaluation or an output change still waiting, clock edges excluded
+    pub fn pending_components(&self) -> Vec<usize> {
+        let mut components: Vec<usize> = self.events.values()
+            .flatten()
+            .filter_map(|event| match event {
+                Event::Evaluate(cid) | Event::Output(cid, _, _) => Some(*cid),
+                Event::Tick(_) => None,
+            })
+            .collect();
+        components.sort_unstable();
+        components.dedup();
+        components
+    }
+
+    pub fn clear(&mut self) {
+        self.events.clear();
+        self.queued.clear();
+        self.pending.clear();
+        self.activity = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structure::Signal;
+
+    fn on() -> PinValue {
+        PinValue::Single(Signal::On)
+    }
+
+    fn off() -> PinValue {
+        PinValue::Single(Signal::Off)
+    }
+
+    #[test]
+    fn events_come_out_in_time_order() {
+        let mut queue = EventQueue::new();
+        queue.schedule_evaluation(5, 1);
+        queue.schedule_evaluation(2, 2);
+        queue.schedule_tick(3, 3);
+        assert_eq!(queue.next_time(), Some(2));
+        assert_eq!(queue.pop(), Some((2, Event::Evaluate(2))));
+        assert_eq!(queue.pop(), Some((3, Event::Tick(3))));
+        assert_eq!(queue.pop(), Some((5, Event::Evaluate(1))));
+        assert_eq!(queue.pop(), None);
+    }
+
+    #[test]
+    fn events_at_the_same_time_keep_their_order() {
+        let mut queue = EventQueue::new();
+        queue.schedule_evaluation(1, 3);
+        queue.schedule_output(1, 1, 1, on(), &off());
+        queue.schedule_evaluation(1, 2);
+        assert_eq!(queue.pop(), Some((1, Event::Evaluate(3))));
+        assert_eq!(queue.pop(), Some((1, Event::Output(1, 1, on()))));
+        assert_eq!(queue.pop(), Some((1, Event::Evaluate(2))));
+    }
+
+    #[test]
+    fn evaluations_are_queued_once_per_time() {
+        let mut queue = EventQueue::new();
+        queue.schedule_evaluation(1, 4);
+        queue.schedule_evaluation(1, 4);
+        queue.schedule_evaluation(2, 4);
+        assert_eq!(queue.pop(), Some((1, Event::Evaluate(4))));
+        assert_eq!(queue.pop(), Some((2, Event::Evaluate(4))));
+        assert_eq!(queue.pop(), None);
+
+        // Once processed it can be scheduled again at the same time
+        queue.schedule_evaluation(2, 4);
+        assert_eq!(queue.pop(), Some((2, Event::Evaluate(4))));
+    }
+
+    #[test]
+    fn outputs_keeping_their_value_are_not_scheduled() {
+        let mut queue = EventQueue::new();
+        queue.schedule_output(1, 1, 1, on(), &on());
+        assert_eq!(queue.next_time(), None);
+        assert!(!queue.has_activity());
+    }
+
+    #[test]
+    fn new_outputs_replace_the_waiting_ones() {
+        let mut queue = EventQueue::new();
+        queue.schedule_output(8, 1, 2, on(), &off());
+        queue.schedule_output(3, 1, 2, off(), &off());
+        queue.schedule_output(4, 1, 1, on(), &off());
+        assert_eq!(queue.pop(), Some((4, Event::Output(1, 1, on()))));
+        assert_eq!(queue.pop(), None);
+        assert!(!queue.has_activity());
+
+        queue.schedule_output(6, 2, 1, on(), &off());
+        queue.schedule_output(5, 2, 1, PinValue::Single(Signal::Undefined), &off());
+        assert_eq!(queue.pop(), Some((5, Event::Output(2, 1, PinValue::Single(Signal::Undefined)))));
+        assert_eq!(queue.pop(), None);
+    }
+
+    #[test]
+    fn outputs_already_waiting_keep_their_time() {
+        let mut queue = EventQueue::new();
+        queue.schedule_output(5, 1, 1, on(), &off());
+        queue.schedule_output(7, 1, 1, on(), &off());
+        assert_eq!(queue.pop(), Some((5, Event::Output(1, 1, on()))));
+        assert_eq!(queue.pop(), None);
+    }
+
+    #[test]
+    fn clock_edges_are_not_activity() {
+        let mut queue = EventQueue::new();
+        queue.schedule_tick(10, 1);
+        assert!(!queue.has_activity());
+        assert_eq!(queue.next_tick(), Some(10));
+
+        queue.schedule_evaluation(4, 2);
+        queue.schedule_output(6, 3, 1, on(), &off());
+        assert!(queue.has_activity());
+        assert_eq!(queue.pending_components(), vec![2, 3]);
+
+        queue.pop();
+        queue.pop();
+        assert!(!queue.has_activity());
+        assert_eq!(queue.next_tick(), Some(10));
+    }
+
+    #[test]
+    fn clear_drops_every_event() {
+        let mut queue = EventQueue::new();
+        queue.schedule_evaluation(1, 1);
+        queue.schedule_tick(2, 2);
+        queue.schedule_output(3, 3, 1, on(), &off());
+        queue.clear();
+        assert_eq!(queue.pop(), None);
+        assert!(!queue.has_activity());
+
+        // Nothing is remembered from the events that were dropped
+        queue.schedule_output(3, 3, 1, on(), &off());
+        assert_eq!(queue.pop(), Some((3, Event::Output(3, 1, on()))));
+    }
+}
@@ -1,28 +1,35 @@
 
 use crate::structure::*;
-use ggez::mint::Point2;
-use ggez::graphics::{Image, Rect};
-use ggez::{Context, GameResult};
+use crate::error::SimError;
+use mint::Point2;
+#[cfg(feature = "gui")]
+use ggez::graphics::Image;
+#[cfg(feature = "gui")]
+use ggez::{Context, GameError, GameResult};
 use multimap::MultiMap;
 
 #[derive(Debug, Clone)]
 pub struct Source {
     pub id: usize,
+    pub name: Option<String>,   // Name used to drive the source from the command line
     pub output: Pin,
     pub position: Point2<f32>,
+    #[cfg(feature = "gui")]
     pub image: Option<Image>, 
     pub hitbox: Hitbox,
     pub ref_pin_pos: Point2<f32>,  
+    pub delay: u64,     // Propagation delay in abstract time units
 }
 impl Source {
-    pub fn new(value: usize) -> Self{
-        Self {
+    pub fn new(value: usize) -> Result<Self, SimError> {
+        Ok(Self {
             id: 0,
+            name: None,
             output: Pin { 
                 value: match value {
                     0 => PinValue::Single(Signal::Off),
                     1 => PinValue::Single(Signal::On),
-                    _ => panic!("Invalid source type")
+                    _ => return Err(SimError::InvalidSource { value }),
                 },
                 cid: 0, pid: 1, ioc: 0,
                 hitbox:  Hitbox { 
@@ -31,29 +38,36 @@ impl Source {
                     },
                 },
             position: Point2 { x: 0.0, y: 0.0 },
+            #[cfg(feature = "gui")]
             image: None,
             hitbox: Hitbox { 
                     rect: Rect { x: 46.0, y: 27.0, w: 20.0, h: 20.0 },
                     r#type: HitboxType::Component,
                 },
             ref_pin_pos: Point2{ x: 73.0, y: 37.0},
-        }
+            delay: 0,
+        })
     }
 
-    pub fn get_pin(&mut self, pid: usize, ioc: usize) -> &mut Pin {
+    pub fn get_pin(&mut self, pid: usize, ioc: usize) -> Result<&mut Pin, SimError> {
         match ioc {
             0 => {
                 // For output pins (ioc == 0), the `id` should be checked against the `output` pin's `pid`.
                 if self.output.pid == pid {
-                    &mut self.output 
+                    Ok(&mut self.output)
                 } else {
-                    panic!("Output pin with id {} not found", pid);
+                    Err(SimError::UnknownPin { pin: (self.id, ioc, pid) })
                 }
             }
-            _ => panic!("Invalid ioc value: {}. Only 0 (output) is valid.", ioc),
+            _ => Err(SimError::UnknownPin { pin: (self.id, ioc, pid) }),
         }
     }
 
+    // Name of the source, `in_<id>` when it wasn't given one like the ports of the Verilog export
+    pub fn label(&self) -> String {
+        self.name.clone().unwrap_or_else(|| format!("in_{}", self.id))
+    }
+
     pub fn set_id(&mut self, id: usize) {
         self.id = id;
     
@@ -65,18 +79,20 @@ impl Source {
         }
     }
 
-    pub fn load_source_image(&mut self, ctx: &mut Context) -> GameResult<()> {
+    // Image showing the value of the source
+    pub fn image_path(&self) -> Result<&'static str, SimError> {
         match self.output.value {
-            PinValue::Single(signal) => {
-                let path = match signal {
-                    Signal::On => "/sources/normal/source_high.png",
-                    Signal::Off | Signal::Undefined => "/sources/normal/source_low.png",
-                };
-                let image = Image::from_path(ctx, path)?; 
-                self.image = Some(image);
-            }
-            _ => panic!("Nomral source can't generate a bus output"),
+            PinValue::Single(Signal::On) => Ok("/sources/normal/source_high.png"),
+            PinValue::Single(_) => Ok("/sources/normal/source_low.png"),
+            PinValue::Multiple(_) => Err(SimError::BusSource { cid: self.id }),
         }
+    }
+
+    #[cfg(feature = "gui")]
+    pub fn load_source_image(&mut self, ctx: &mut Context) -> GameResult<()> {
+        let path = self.image_path().map_err(|error| GameError::CustomError(error.to_string()))?;
+        let image = Image::from_path(ctx, path)?; 
+        self.image = Some(image);
         Ok(())
     }
 
@@ -110,4 +126,42 @@ impl Source {
     pub fn store_pin_pos(&self, map: &mut MultiMap<(i32, i32), (usize, usize, usize)>){
         map.insert((self.ref_pin_pos.x as i32, self.ref_pin_pos.y as i32), (self.id, 1, 0));
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sources_are_low_or_high() {
+        assert_eq!(Source::new(1).unwrap().output.value, PinValue::Single(Signal::On));
+        assert_eq!(Source::new(0).unwrap().output.value, PinValue::Single(Signal::Off));
+        assert_eq!(Source::new(2).err(), Some(SimError::InvalidSource { value: 2 }));
+    }
+
+    #[test]
+    fn the_image_shows_the_value() {
+        let mut source = Source::new(1).unwrap();
+        assert_eq!(source.image_path(), Ok("/sources/normal/source_high.png"));
+        source.output.value = PinValue::Single(Signal::Off);
+        assert_eq!(source.image_path(), Ok("/sources/normal/source_low.png"));
+    }
+
+    #[test]
+    fn bus_sources_are_reported() {
+        let mut source = Source::new(0).unwrap();
+        source.set_id(3);
+        source.output.value = PinValue::Multiple(vec![Signal::On; 2]);
+        let error = source.image_path().unwrap_err();
+        assert_eq!(error, SimError::BusSource { cid: 3 });
+        assert_eq!(error.components(), vec![3]);
+    }
+
+    #[test]
+    fn unnamed_sources_are_labelled_by_their_id() {
+        let mut source = Source::new(0).unwrap();
+        source.set_id(7);
+        assert_eq!(source.label(), "in_7");
+        source.name = Some("A".to_string());
+        assert_eq!(source.label(), "A");
+    }
+}
@@ -1,13 +1,18 @@
 use std::fmt;
 
 // structure.rs
-use ggez::{graphics::Rect, mint::Point2};
+use mint::Point2;
+use serde::{Deserialize, Serialize};
+
+use crate::error::SimError;
 
 #[derive(Debug, Clone, PartialEq, Copy)]
 pub enum Signal {
     Off, 
     On, 
     Undefined, 
+    HighZ,      // Nothing drives the net
+    Conflict,   // Drivers of the same net fight each other
 }
 impl fmt::Display for Signal {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -15,12 +20,50 @@ impl fmt::Display for Signal {
             Signal::Off => "OFF",
             Signal::On => "ON",
             Signal::Undefined => "UNDEFINED",
+            Signal::HighZ => "HIGH-Z",
+            Signal::Conflict => "CONFLICT",
         };
         write!(f, "{}", s)
     }
 }
 
-#[derive(Debug, Clone)]
+// Complement of a logic level
+impl std::ops::Not for Signal {
+    type Output = Signal;
+
+    fn not(self) -> Signal {
+        match self {
+            Signal::On => Signal::Off,
+            Signal::Off => Signal::On,
+            _ => Signal::Undefined,
+        }
+    }
+}
+
+impl Signal {
+    // Value seen by a logic input: a floating or shorted net can't be read as a logic level
+    pub fn read(self) -> Signal {
+        match self {
+            Signal::HighZ | Signal::Conflict => Signal::Undefined,
+            signal => signal,
+        }
+    }
+
+    //* Value of a net driven by two drivers: high impedance drivers leave the net to the other one,
+    //* drivers with opposite values make a conflict and an undefined driver makes the net undefined
+    pub fn resolve(self, other: Signal) -> Signal {
+        match (self, other) {
+            (Signal::HighZ, signal) | (signal, Signal::HighZ) => signal,
+            (Signal::Conflict, _) | (_, Signal::Conflict) => Signal::Conflict,
+            (Signal::Undefined, _) | (_, Signal::Undefined) => Signal::Undefined,
+            (a, b) if a == b => a,
+            _ => Signal::Conflict,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum LogicGates {
     And,
     Or,
@@ -31,12 +74,90 @@ pub enum LogicGates {
     Xnor,
 }
 
+impl LogicGates {
+    // The gate type number used by `LogicGate::new_gate`
+    pub fn index(&self) -> u32 {
+        match self {
+            LogicGates::And => 0,
+            LogicGates::Or => 1,
+            LogicGates::Not => 2,
+            LogicGates::Nand => 3,
+            LogicGates::Nor => 4,
+            LogicGates::Xor => 5,
+            LogicGates::Xnor => 6,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum PinValue {
     Single(Signal),
     Multiple(Vec<Signal>),
 }
 
+impl PinValue {
+    // Value carrying these bits, a bus when there is more than one
+    pub fn from_signals(signals: Vec<Signal>) -> PinValue {
+        match signals.as_slice() {
+            [signal] => PinValue::Single(*signal),
+            _ => PinValue::Multiple(signals),
+        }
+    }
+
+    // Number of bits carried by the value
+    pub fn width(&self) -> usize {
+        match self {
+            PinValue::Single(_) => 1,
+            PinValue::Multiple(signals) => signals.len(),
+        }
+    }
+
+    // Signal of a single bit, a single value is bit 0
+    pub fn bit(&self, index: usize) -> Signal {
+        match self {
+            PinValue::Single(signal) => *signal,
+            PinValue::Multiple(signals) => signals.get(index).copied().unwrap_or(Signal::Undefined),
+        }
+    }
+
+    // Value with the same width and every bit set to `signal`
+    pub fn filled(&self, signal: Signal) -> PinValue {
+        match self {
+            PinValue::Single(_) => PinValue::Single(signal),
+            PinValue::Multiple(signals) => PinValue::Multiple(vec![signal; signals.len()]),
+        }
+    }
+
+    // Undefined value with the same width
+    pub fn to_undefined(&self) -> PinValue {
+        self.filled(Signal::Undefined)
+    }
+
+    // Resolve bit by bit the values of two drivers with the same width
+    pub fn resolve(&self, other: &PinValue) -> PinValue {
+        match self {
+            PinValue::Single(signal) => PinValue::Single(signal.resolve(other.bit(0))),
+            PinValue::Multiple(signals) => PinValue::Multiple(
+                signals.iter().enumerate().map(|(index, signal)| signal.resolve(other.bit(index))).collect(),
+            ),
+        }
+    }
+
+    // Bits left in high impedance by the strong drivers take the value of the weak ones (pull resistors)
+    pub fn with_weak(&self, weak: &PinValue) -> PinValue {
+        let bit = |signal: Signal, index: usize| match signal {
+            Signal::HighZ => weak.bit(index),
+            signal => signal,
+        };
+        match self {
+            PinValue::Single(signal) => PinValue::Single(bit(*signal, 0)),
+            PinValue::Multiple(signals) => PinValue::Multiple(
+                signals.iter().enumerate().map(|(index, signal)| bit(*signal, index)).collect(),
+            ),
+        }
+    }
+}
+
 impl fmt::Display for PinValue {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -59,6 +180,134 @@ pub struct Pin {
     //TODO: instead of 3 separate parameters unite them in a single tuple
 }
 
+impl Pin {
+    // Pin with its hitbox centered on (x, y), relative to the top left corner of the component
+    pub fn new(pid: usize, ioc: usize, x: f32, y: f32, value: PinValue) -> Pin {
+        Pin {
+            value,
+            cid: 0,
+            pid,
+            ioc,
+            hitbox: Hitbox {
+                rect: Rect { x: x - 2.5, y: y - 2.5, w: 5.0, h: 5.0 },
+                r#type: HitboxType::Pin(0, pid, ioc),
+            },
+        }
+    }
+
+    // Value of a single bit pin as seen by a logic input, bus values are read as undefined
+    pub fn signal(&self) -> Signal {
+        match self.value {
+            PinValue::Single(signal) => signal.read(),
+            PinValue::Multiple(_) => Signal::Undefined,
+        }
+    }
+
+    // Give the pin and its hitbox the id of their component
+    pub fn set_cid(&mut self, cid: usize) {
+        self.cid = cid;
+        if let HitboxType::Pin(a, _, _) = &mut self.hitbox.r#type {
+            *a = cid;
+        }
+    }
+}
+
+//* Pin handling shared by the components, they give all their pins as a single iterator
+
+// Give all the pins the id of their component
+pub fn set_pins_cid<'a>(pins: impl IntoIterator<Item = &'a mut Pin>, cid: usize) {
+    for pin in pins {
+        pin.set_cid(cid);
+    }
+}
+
+// The pin with this pid and ioc among the pins of the component with the given id
+pub fn find_pin<'a>(pins: impl IntoIterator<Item = &'a mut Pin>, cid: usize, pid: usize, ioc: usize) -> Result<&'a mut Pin, SimError> {
+    pins.into_iter().find(|pin| pin.pid == pid && pin.ioc == ioc)
+        .ok_or(SimError::UnknownPin { pin: (cid, ioc, pid) })
+}
+
+pub fn move_pins<'a>(pins: impl IntoIterator<Item = &'a mut Pin>, dx: f32, dy: f32) {
+    for pin in pins {
+        pin.hitbox.rect.x += dx;
+        pin.hitbox.rect.y += dy;
+    }
+}
+
+pub fn pins_hitbox<'a>(pins: impl IntoIterator<Item = &'a Pin>) -> Vec<Hitbox> {
+    pins.into_iter().map(|pin| pin.hitbox.clone()).collect()
+}
+
+// Move a component, its hitbox and its reference pin to a new position, returns how far it moved
+// so that its pins can follow. The hitbox keeps its offset from the position
+pub fn move_component(
+    position: &mut Point2<f32>,
+    hitbox: &mut Hitbox,
+    ref_pin_pos: &mut Point2<f32>,
+    new_position: Point2<f32>,
+) -> (f32, f32) {
+    let dx = new_position.x - position.x;
+    let dy = new_position.y - position.y;
+    *position = new_position;
+    hitbox.rect.x += dx;
+    hitbox.rect.y += dy;
+    ref_pin_pos.x += dx;
+    ref_pin_pos.y += dy;
+    (dx, dy)
+}
+
+// Axis aligned rectangle, the edges count as inside. Kept out of ggez so that the simulation
+// builds without the editor.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub w: f32,
+    pub h: f32,
+}
+
+impl Rect {
+    pub fn new(x: f32, y: f32, w: f32, h: f32) -> Self {
+        Rect { x, y, w, h }
+    }
+
+    // Top left corner
+    pub fn point(&self) -> Point2<f32> {
+        Point2 { x: self.x, y: self.y }
+    }
+
+    pub fn right(&self) -> f32 {
+        self.x + self.w
+    }
+
+    pub fn bottom(&self) -> f32 {
+        self.y + self.h
+    }
+
+    pub fn contains<P: Into<Point2<f32>>>(&self, point: P) -> bool {
+        let point = point.into();
+        point.x >= self.x && point.x <= self.right() && point.y >= self.y && point.y <= self.bottom()
+    }
+
+    pub fn overlaps(&self, other: &Rect) -> bool {
+        self.x <= other.right() && self.right() >= other.x && self.y <= other.bottom() && self.bottom() >= other.y
+    }
+}
+
+#[cfg(feature = "gui")]
+impl From<Rect> for ggez::graphics::Rect {
+    fn from(rect: Rect) -> Self {
+        ggez::graphics::Rect::new(rect.x, rect.y, rect.w, rect.h)
+    }
+}
+
+#[cfg(feature = "gui")]
+impl From<ggez::graphics::Rect> for Rect {
+    fn from(rect: ggez::graphics::Rect) -> Self {
+        Rect::new(rect.x, rect.y, rect.w, rect.h)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum HitboxType{
     Pin(usize, usize, usize),
@@ -79,6 +328,28 @@ pub struct WireSegment {
     pub hitbox: Hitbox,       
 }
 
+impl WireSegment {
+    // Create a straight segment, the hitbox follows the main direction of the segment
+    pub fn new(start: Point2<f32>, end: Point2<f32>) -> Self {
+        let dx = (end.x - start.x).abs();
+        let dy = (end.y - start.y).abs();
+
+        let rect = if dx >= dy {
+            // Horizontal segment
+            Rect { x: start.x.min(end.x), y: start.y - 5.0, w: dx, h: 10.0 }
+        } else {
+            // Vertical segment
+            Rect { x: start.x - 5.0, y: start.y.min(end.y), w: 10.0, h: dy }
+        };
+
+        WireSegment {
+            start,
+            end,
+            hitbox: Hitbox { rect, r#type: HitboxType::Wire },
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Wire {
     pub pins: Vec<(usize, usize, usize)>, // Logical connections (component ID, I/O category, pin ID)
@@ -165,4 +436,119 @@ impl UnionFind {
             }
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn high_impedance_and_conflicts_read_as_undefined() {
+        assert_eq!(Signal::HighZ.read(), Signal::Undefined);
+        assert_eq!(Signal::Conflict.read(), Signal::Undefined);
+        assert_eq!(Signal::On.read(), Signal::On);
+        assert_eq!(!Signal::HighZ, Signal::Undefined);
+    }
+
+    #[test]
+    fn drivers_are_resolved_in_any_order() {
+        use Signal::*;
+        let cases = [
+            (On, On, On),
+            (Off, Off, Off),
+            (On, Off, Conflict),
+            (HighZ, On, On),
+            (HighZ, HighZ, HighZ),
+            (Undefined, Off, Undefined),
+            (Undefined, HighZ, Undefined),
+            (Conflict, HighZ, Conflict),
+            (Conflict, Undefined, Conflict),
+        ];
+        for (a, b, expected) in cases {
+            assert_eq!(a.resolve(b), expected, "{} and {}", a, b);
+            assert_eq!(b.resolve(a), expected, "{} and {}", b, a);
+        }
+    }
+
+    #[test]
+    fn buses_are_resolved_bit_by_bit() {
+        use Signal::*;
+        let a = PinValue::Multiple(vec![On, HighZ, Off]);
+        let b = PinValue::Multiple(vec![HighZ, Off, On]);
+        assert_eq!(a.resolve(&b), PinValue::Multiple(vec![On, Off, Conflict]));
+        assert_eq!(PinValue::Single(HighZ).resolve(&PinValue::Single(On)), PinValue::Single(On));
+    }
+
+    #[test]
+    fn weak_drivers_only_set_the_floating_bits() {
+        use Signal::*;
+        let strong = PinValue::Multiple(vec![On, HighZ, Conflict]);
+        let weak = PinValue::Multiple(vec![Off; 3]);
+        assert_eq!(strong.with_weak(&weak), PinValue::Multiple(vec![On, Off, Conflict]));
+        assert_eq!(PinValue::Single(HighZ).with_weak(&PinValue::Single(On)), PinValue::Single(On));
+    }
+
+    #[test]
+    fn values_keep_their_width() {
+        let bus = PinValue::Multiple(vec![Signal::On, Signal::Off]);
+        assert_eq!(bus.width(), 2);
+        assert_eq!(bus.bit(5), Signal::Undefined);
+        assert_eq!(bus.to_undefined(), PinValue::Multiple(vec![Signal::Undefined; 2]));
+        assert_eq!(PinValue::from_signals(vec![Signal::On]), PinValue::Single(Signal::On));
+        assert_eq!(PinValue::from_signals(vec![Signal::On, Signal::Off]), bus);
+    }
+
+    #[test]
+    fn pins_read_their_signal() {
+        let mut pin = Pin::new(1, 1, 10.0, 20.0, PinValue::Single(Signal::On));
+        assert_eq!(pin.signal(), Signal::On);
+        pin.value = PinValue::Single(Signal::Undefined);
+        assert_eq!(pin.signal(), Signal::Undefined);
+        // Bus values can't be read as a single bit
+        pin.value = PinValue::Multiple(vec![Signal::On]);
+        assert_eq!(pin.signal(), Signal::Undefined);
+    }
+
+    #[test]
+    fn pins_are_centered_on_their_position() {
+        let pin = Pin::new(2, 0, 10.0, 20.0, PinValue::Single(Signal::Off));
+        assert_eq!((pin.hitbox.rect.x, pin.hitbox.rect.y), (7.5, 17.5));
+        assert_eq!(pin.hitbox.r#type, HitboxType::Pin(0, 2, 0));
+    }
+
+    #[test]
+    fn pins_are_found_by_pid_and_ioc() {
+        let mut pins = vec![
+            Pin::new(1, 1, 0.0, 0.0, PinValue::Single(Signal::Off)),
+            Pin::new(1, 0, 0.0, 0.0, PinValue::Single(Signal::On)),
+        ];
+        set_pins_cid(&mut pins, 3);
+        assert_eq!(pins[1].hitbox.r#type, HitboxType::Pin(3, 1, 0));
+        assert_eq!(find_pin(&mut pins, 3, 1, 0).unwrap().value, PinValue::Single(Signal::On));
+        assert_eq!(find_pin(&mut pins, 3, 2, 1), Err(SimError::UnknownPin { pin: (3, 1, 2) }));
+    }
+
+    #[test]
+    fn pins_move_with_their_component() {
+        let mut position = Point2 { x: 0.0, y: 0.0 };
+        let mut hitbox = Hitbox { rect: Rect::new(0.0, 0.0, 10.0, 10.0), r#type: HitboxType::Component };
+        let mut ref_pin_pos = Point2 { x: 5.0, y: 5.0 };
+        let mut pins = vec![Pin::new(1, 1, 0.0, 5.0, PinValue::Single(Signal::Off))];
+
+        let (dx, dy) = move_component(&mut position, &mut hitbox, &mut ref_pin_pos, Point2 { x: 20.0, y: -10.0 });
+        move_pins(&mut pins, dx, dy);
+        assert_eq!((dx, dy), (20.0, -10.0));
+        assert_eq!((hitbox.rect.x, hitbox.rect.y), (20.0, -10.0));
+        assert_eq!((ref_pin_pos.x, ref_pin_pos.y), (25.0, -5.0));
+        assert_eq!(pins_hitbox(&pins)[0].rect.x, 17.5);
+    }
+
+    #[test]
+    fn rectangles_include_their_edges() {
+        let rect = Rect::new(10.0, 20.0, 30.0, 40.0);
+        assert_eq!((rect.right(), rect.bottom()), (40.0, 60.0));
+        assert!(rect.contains(Point2 { x: 10.0, y: 60.0 }));
+        assert!(!rect.contains(Point2 { x: 40.5, y: 30.0 }));
+        assert!(rect.overlaps(&Rect::new(40.0, 60.0, 5.0, 5.0)));
+        assert!(!rect.overlaps(&Rect::new(0.0, 0.0, 5.0, 19.0)));
+    }
+}
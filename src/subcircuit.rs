@@ -0,0 +1,165 @@
+use mint::Point2;
+
+use crate::circuit::{Circuit, LogicElements};
+use crate::logic_gates::DEFAULT_GATE_DELAY;
+use crate::structure::*;
+use crate::error::SimError;
+
+// Distance between two pins on the same side of the block
+const PIN_SPACING: f32 = 20.0;
+const BLOCK_WIDTH: f32 = 60.0;
+
+/// A circuit used as a component of another circuit. Every source inside becomes an input pin
+/// (ioc 1) and every led an output pin (ioc 0), both numbered in the order of their ids
+#[derive(Debug, Clone)]
+pub struct SubCircuit {
+    pub id: usize,
+    pub name: String,
+    pub circuit: Box<Circuit>,
+    pub input: Vec<Pin>,
+    pub output: Vec<Pin>,
+    pub position: Point2<f32>,
+    pub hitbox: Hitbox,
+    pub ref_pin_pos: Point2<f32>,
+    pub delay: u64,     // Propagation delay in abstract time units
+}
+
+impl SubCircuit {
+    pub fn new(name: &str, circuit: Circuit) -> Self {
+        let (sources, leds) = circuit.count_sources_and_leds();
+        let height = PIN_SPACING * (sources.max(leds) + 1) as f32;
+
+        // Inputs on the left side of the block and outputs on the right side
+        let pin = |pid: usize, ioc: usize, x: f32| {
+            Pin::new(pid, ioc, x, PIN_SPACING * pid as f32, PinValue::Single(Signal::Undefined))
+        };
+
+        Self {
+            id: 0,
+            name: name.to_string(),
+            circuit: Box::new(circuit),
+            input: (1..=sources).map(|pid| pin(pid, 1, 0.0)).collect(),
+            output: (1..=leds).map(|pid| pin(pid, 0, BLOCK_WIDTH)).collect(),
+            position: Point2 { x: 0.0, y: 0.0 },
+            hitbox: Hitbox {
+                rect: Rect { x: 0.0, y: 0.0, w: BLOCK_WIDTH, h: height },
+                r#type: HitboxType::Component,
+            },
+            ref_pin_pos: Point2 { x: 0.0, y: PIN_SPACING },
+            delay: DEFAULT_GATE_DELAY,
+        }
+    }
+
+    fn pins(&self) -> impl Iterator<Item = &Pin> {
+        self.input.iter().chain(&self.output)
+    }
+
+    fn pins_mut(&mut self) -> impl Iterator<Item = &mut Pin> {
+        self.input.iter_mut().chain(&mut self.output)
+    }
+
+    pub fn set_id(&mut self, id: usize) {
+        self.id = id;
+        set_pins_cid(self.pins_mut(), id);
+    }
+
+    pub fn get_pin(&mut self, pid: usize, ioc: usize) -> Result<&mut Pin, SimError> {
+        let id = self.id;
+        find_pin(self.pins_mut(), id, pid, ioc)
+    }
+
+    // Simulate the inner circuit with the values on the input pins
+    pub fn get_output(&mut self) {
+        let inputs: Vec<PinValue> = self.input.iter().map(|pin| pin.value.clone()).collect();
+        let outputs = self.circuit.evaluate_block(&inputs);
+        for (pin, value) in self.output.iter_mut().zip(outputs) {
+            pin.value = value;
+        }
+    }
+
+    pub fn update_subcircuit_position(&mut self, position: Point2<f32>) {
+        let (dx, dy) = move_component(&mut self.position, &mut self.hitbox, &mut self.ref_pin_pos, position);
+        move_pins(self.pins_mut(), dx, dy);
+    }
+
+    pub fn subcircuit_pins_hitbox(&self) -> Vec<Hitbox> {
+        pins_hitbox(self.pins())
+    }
+}
+
+impl Circuit {
+    //* Copy the components and wire segments lying inside an area into a new circuit,
+    //* the top left corner of the area becomes the origin of the new circuit
+    pub fn extract(&self, area: Rect) -> Circuit {
+        let inside = |rect: Rect| area.contains(rect.point()) && area.contains(Point2 { x: rect.right(), y: rect.bottom() });
+        let shift = |point: Point2<f32>| Point2 { x: point.x - area.x, y: point.y - area.y };
+
+        let mut circuit = Circuit::new();
+        for component in self.components.values() {
+            if inside(component.get_hitbox()) {
+                let mut component = component.clone();
+                let position = shift(component.get_position());
+                component.update_postion(position);
+                circuit.add_element(component);
+            }
+        }
+        for segment in &self.segments {
+            if inside(segment.hitbox.rect) {
+                circuit.add_segment(WireSegment::new(shift(segment.start), shift(segment.end)));
+            }
+        }
+        circuit
+    }
+
+    // Turn the content of an area into a block that can be placed as a component
+    pub fn collapse(&self, area: Rect, name: &str) -> LogicElements {
+        LogicElements::SubCircuit(SubCircuit::new(name, self.extract(area)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::tests::not_chain;
+
+    #[test]
+    fn blocks_have_a_pin_for_every_source_and_led() {
+        let (circuit, _, _, _) = not_chain(0, &[1]);
+        let block = SubCircuit::new("inverter", circuit);
+        assert_eq!(block.input.len(), 1);
+        assert_eq!(block.output.len(), 1);
+        assert_eq!(block.name, "inverter");
+    }
+
+    #[test]
+    fn blocks_simulate_the_inner_circuit() {
+        let (circuit, _, _, _) = not_chain(0, &[1, 1, 1]);
+        let mut block = SubCircuit::new("inverter", circuit);
+        for (input, output) in [(Signal::On, Signal::Off), (Signal::Off, Signal::On)] {
+            block.input[0].value = PinValue::Single(input);
+            block.get_output();
+            assert_eq!(block.output[0].value, PinValue::Single(output));
+        }
+    }
+
+    #[test]
+    fn only_what_lies_inside_the_area_is_extracted() {
+        let (mut circuit, _, gates, led) = not_chain(0, &[1]);
+        circuit.add_segment(WireSegment::new(Point2 { x: 500.0, y: 500.0 }, Point2 { x: 520.0, y: 500.0 }));
+
+        // The gate and the led, without the source on their left
+        let gate = circuit.components[&gates[0]].get_hitbox();
+        let area = Rect { x: gate.x - 1.0, y: gate.y - 1.0, w: 1000.0, h: 1000.0 };
+        let inner = circuit.extract(area);
+        assert_eq!(inner.count_sources_and_leds(), (0, 1));
+        assert_eq!(inner.components.len(), 2);
+        assert_eq!(inner.segments.len(), 1);
+
+        // Positions are relative to the area
+        let position = circuit.components[&led].get_position();
+        let moved = inner.components.values().find(|component| matches!(component, LogicElements::Leds(_))).unwrap();
+        assert_eq!(moved.get_position(), Point2 { x: position.x - area.x, y: position.y - area.y });
+
+        assert!(matches!(circuit.collapse(area, "tail"), LogicElements::SubCircuit(block) if block.output.len() == 1));
+    }
+}
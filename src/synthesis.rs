@@ -0,0 +1,357 @@
+use std::collections::HashMap;
+
+use mint::Point2;
+
+use crate::buffer::{Buffer, BufferType};
+use crate::circuit::{Circuit, LogicElements};
+use crate::error::SimError;
+use crate::expression::Expr;
+use crate::led::Led;
+use crate::logic_gates::LogicGate;
+use crate::source::Source;
+use crate::structure::*;
+
+// Distance between two vertical wires, a bridge buffer fits between them
+const RAIL_SPACING: f32 = 80.0;
+// Horizontal position of the first vertical wire
+const FIRST_RAIL: f32 = 120.0;
+// Top of the first row of gates, below the sources
+const FIRST_ROW: f32 = 100.0;
+// Distance between the wires coming to the inputs of a gate, a bridge buffer fits between them
+const LANE_SPACING: f32 = 50.0;
+// Distance between the vertical parts of the wires coming to the inputs of a gate
+const JOG_SPACING: f32 = 20.0;
+// Space left below a gate before the wire bringing its output back to the left, and after it
+const ROW_GAP: f32 = 40.0;
+
+// Distance from the left and right pins of a bridge buffer to its middle
+const BRIDGE_HALF_WIDTH: f32 = 30.0;
+
+// Pins of the components relative to their position
+const SOURCE_PIN: Point2<f32> = Point2 { x: 73.0, y: 37.0 };
+const LED_PIN: Point2<f32> = Point2 { x: 25.0, y: 30.0 };
+const GATE_INPUT_X: f32 = 6.5;
+const GATE_OUTPUT: Point2<f32> = Point2 { x: 76.0, y: 36.0 };
+
+// A value of the circuit: a source for a variable or a constant, or the output of a gate
+enum Node {
+    Input { name: Option<String>, value: usize },
+    Gate { gate: LogicGates, inputs: Vec<usize> },
+}
+
+// Wire from a vertical wire to a point on its right
+struct Lane {
+    node: usize,
+    y: f32,
+    end: f32,
+}
+
+#[derive(Default)]
+struct Synthesis {
+    nodes: Vec<Node>,
+    ids: HashMap<Expr, usize>,   // Identical sub-expressions share their gate
+}
+
+impl Synthesis {
+    // Index of the node computing the expression, created with the nodes of its operands
+    fn node(&mut self, expr: &Expr) -> usize {
+        if let Some(&index) = self.ids.get(expr) {
+            return index;
+        }
+        let node = match expr {
+            Expr::Const(value) => Node::Input { name: None, value: *value as usize },
+            Expr::Var(name) => Node::Input { name: Some(name.clone()), value: 0 },
+            // A single operand is its own value
+            Expr::And(operands) | Expr::Or(operands) | Expr::Xor(operands) if operands.len() == 1 => {
+                return self.node(&operands[0]);
+            }
+            // The complement of an and, or or xor is a single inverted gate
+            Expr::Not(inner) => match inner.as_ref() {
+                Expr::And(operands) if operands.len() > 1 => self.gate(LogicGates::Nand, operands),
+                Expr::Or(operands) if operands.len() > 1 => self.gate(LogicGates::Nor, operands),
+                Expr::Xor(operands) if operands.len() > 1 => self.gate(LogicGates::Xnor, operands),
+                inner => Node::Gate { gate: LogicGates::Not, inputs: vec![self.node(inner)] },
+            },
+            Expr::And(operands) => self.gate(LogicGates::And, operands),
+            Expr::Or(operands) => self.gate(LogicGates::Or, operands),
+            Expr::Xor(operands) => self.gate(LogicGates::Xor, operands),
+        };
+        self.nodes.push(node);
+        self.ids.insert(expr.clone(), self.nodes.len() - 1);
+        self.nodes.len() - 1
+    }
+
+    fn gate(&mut self, gate: LogicGates, operands: &[Expr]) -> Node {
+        Node::Gate { gate, inputs: operands.iter().map(|operand| self.node(operand)).collect() }
+    }
+}
+
+fn place(mut component: LogicElements, x: f32, y: f32) -> LogicElements {
+    component.update_postion(Point2 { x, y });
+    component
+}
+
+fn segment(circuit: &mut Circuit, start: (f32, f32), end: (f32, f32)) {
+    circuit.add_segment(WireSegment::new(Point2 { x: start.0, y: start.1 }, Point2 { x: end.0, y: end.1 }));
+}
+
+impl Expr {
+    // Number of gates of the circuit built from the expression, identical sub-expressions share their gate
+    pub fn gate_count(&self) -> usize {
+        let mut synthesis = Synthesis::default();
+        synthesis.node(self);
+        synthesis.nodes.iter().filter(|node| matches!(node, Node::Gate { .. })).count()
+    }
+}
+
+impl Circuit {
+    // Circuit computing a single expression, its led is called `output`
+    pub fn from_expression(output: &str, expr: &Expr) -> Result<Circuit, SimError> {
+        Circuit::from_expressions(&[(output.to_string(), expr.clone())])
+    }
+
+    //* Build a circuit computing expressions: a source for every variable and constant, a gate for
+    //* every operator and a led for every output, the outputs share the gates of their identical
+    //* sub-expressions. The sources are on the top row and every value gets a vertical wire on the
+    //* left, from where horizontal wires go to the inputs of the gates placed one per row on the
+    //* right. Wires touching each other are connected, so a horizontal wire crossing a vertical wire
+    //* goes through a buffer with no delay placed over it.
+    pub fn from_expressions(outputs: &[(String, Expr)]) -> Result<Circuit, SimError> {
+        let mut synthesis = Synthesis::default();
+        let roots: Vec<usize> = outputs.iter().map(|(_, expr)| synthesis.node(expr)).collect();
+        let nodes = synthesis.nodes;
+        if nodes.is_empty() {
+            return Ok(Circuit::new());
+        }
+
+        // The led of an output is on the right of its gate, unless the gate already has one or the
+        // output is a source, then it is at the bottom and its value comes from the vertical wire
+        let mut beside_gate: Vec<Option<usize>> = vec![None; nodes.len()];
+        let mut at_bottom = Vec::new();
+        for (output, &root) in roots.iter().enumerate() {
+            match nodes[root] {
+                Node::Gate { .. } if beside_gate[root].is_none() => beside_gate[root] = Some(output),
+                _ => at_bottom.push((output, root)),
+            }
+        }
+        // Gates bring their output back to their vertical wire when other gates or leds use it
+        let mut returns = vec![false; nodes.len()];
+        for node in &nodes {
+            if let Node::Gate { inputs, .. } = node {
+                for &input in inputs {
+                    returns[input] = true;
+                }
+            }
+        }
+        for &(_, root) in &at_bottom {
+            returns[root] = true;
+        }
+
+        // The vertical wires of the inputs come first, then the ones of the gates in the order they
+        // are computed, so a gate only uses wires on the left of its own
+        let mut order: Vec<usize> = (0..nodes.len()).filter(|&node| matches!(nodes[node], Node::Input { .. })).collect();
+        order.extend((0..nodes.len()).filter(|&node| matches!(nodes[node], Node::Gate { .. })));
+        let mut rail = vec![0.0; nodes.len()];
+        for (index, &node) in order.iter().enumerate() {
+            rail[node] = FIRST_RAIL + RAIL_SPACING * index as f32;
+        }
+        let last_rail = FIRST_RAIL + RAIL_SPACING * (nodes.len() - 1) as f32;
+        let max_inputs = nodes.iter()
+            .map(|node| match node {
+                Node::Gate { inputs, .. } => inputs.len(),
+                Node::Input { .. } => 0,
+            })
+            .max()
+            .unwrap_or(0);
+        let gate_x = last_rail + BRIDGE_HALF_WIDTH + JOG_SPACING * (max_inputs + 1) as f32 + 10.0;
+
+        let mut circuit = Circuit::new();
+        // Top and bottom of the vertical wire of every node
+        let mut top = vec![0.0; nodes.len()];
+        let mut bottom: Vec<f32> = vec![f32::NEG_INFINITY; nodes.len()];
+        let mut lanes = Vec::new();
+
+        // Sources on the top row, just on the left of their vertical wire
+        for &node in &order {
+            if let Node::Input { name, value } = &nodes[node] {
+                let mut source = Source::new(*value)?;
+                source.name = name.clone();
+                let pin_x = rail[node] - JOG_SPACING;
+                circuit.add_element(place(LogicElements::Source(source), pin_x - SOURCE_PIN.x, 0.0));
+                segment(&mut circuit, (pin_x, SOURCE_PIN.y), (rail[node], SOURCE_PIN.y));
+                top[node] = SOURCE_PIN.y;
+            }
+        }
+
+        // The leds are on the right of the gates, they are added last in the order of the outputs
+        let led_pin_x = gate_x + GATE_OUTPUT.x + 30.0;
+        let mut led_y = vec![0.0; outputs.len()];
+
+        // One row per gate
+        let mut y = FIRST_ROW;
+        for &node in order.iter().filter(|&&node| matches!(nodes[node], Node::Gate { .. })) {
+            let Node::Gate { gate, inputs } = &nodes[node] else { continue };
+            let mut logic_gate = LogicElements::Gates(LogicGate::new_gate(gate.index(), inputs.len(), false, 1)?);
+            logic_gate.update_postion(Point2 { x: gate_x, y });
+            let first_pin = match gate {
+                LogicGates::Not => y + 36.0,
+                _ => y + 26.0,
+            };
+            circuit.add_element(logic_gate);
+
+            // Each input comes on its own lane, far enough from the others to bridge wires, then goes up
+            // to its pin. The lanes further down go up closer to the gate so that they never cross
+            let count = inputs.len();
+            let mut last_lane = first_pin;
+            for (index, &input) in inputs.iter().enumerate() {
+                let pin_y = first_pin + 20.0 * index as f32;
+                let lane_y = first_pin + LANE_SPACING * index as f32;
+                let jog_x = gate_x - JOG_SPACING * (count - index) as f32;
+                lanes.push(Lane { node: input, y: lane_y, end: jog_x });
+                if lane_y != pin_y {
+                    segment(&mut circuit, (jog_x, lane_y), (jog_x, pin_y));
+                }
+                segment(&mut circuit, (jog_x, pin_y), (gate_x + GATE_INPUT_X, pin_y));
+                bottom[input] = bottom[input].max(lane_y);
+                last_lane = lane_y;
+            }
+
+            let output_y = y + GATE_OUTPUT.y;
+            let output_x = gate_x + GATE_OUTPUT.x;
+            if let Some(output) = beside_gate[node] {
+                segment(&mut circuit, (output_x, output_y), (led_pin_x, output_y));
+                led_y[output] = output_y;
+            }
+            // The next row starts below the lanes of the gate, and below the wire bringing its output back
+            let return_y = last_lane.max(y + 50.0) + ROW_GAP;
+            if returns[node] {
+                // The output goes down, below the gate, and back to the vertical wire of the gate
+                let turn_x = output_x + JOG_SPACING;
+                segment(&mut circuit, (output_x, output_y), (turn_x, output_y));
+                segment(&mut circuit, (turn_x, output_y), (turn_x, return_y));
+                segment(&mut circuit, (turn_x, return_y), (rail[node], return_y));
+                top[node] = return_y;
+            }
+            y = return_y + ROW_GAP;
+        }
+
+        // The other leds are driven straight from the vertical wires, one below the other
+        for (output, root) in at_bottom {
+            let lane_y = y + LED_PIN.y;
+            lanes.push(Lane { node: root, y: lane_y, end: led_pin_x });
+            bottom[root] = bottom[root].max(lane_y);
+            led_y[output] = lane_y;
+            y += LED_PIN.y * 2.0 + ROW_GAP;
+        }
+        for ((name, _), y) in outputs.iter().zip(led_y) {
+            let mut led = Led::new();
+            led.name = Some(name.clone());
+            circuit.add_element(place(LogicElements::Leds(led), led_pin_x - LED_PIN.x, y - LED_PIN.y));
+        }
+
+        for node in 0..nodes.len() {
+            if bottom[node] > top[node] {
+                segment(&mut circuit, (rail[node], top[node]), (rail[node], bottom[node]));
+            }
+        }
+
+        // The lanes cross the vertical wires on their right that are in use at their height
+        for lane in &lanes {
+            let mut crossed: Vec<f32> = (0..nodes.len())
+                .filter(|&node| rail[node] > rail[lane.node] && rail[node] < lane.end)
+                .filter(|&node| top[node] <= lane.y && lane.y <= bottom[node])
+                .map(|node| rail[node])
+                .collect();
+            crossed.sort_by(f32::total_cmp);
+
+            let mut x = rail[lane.node];
+            for crossed_x in crossed {
+                segment(&mut circuit, (x, lane.y), (crossed_x - BRIDGE_HALF_WIDTH, lane.y));
+                let mut bridge = LogicElements::Buffers(Buffer::new(BufferType::Buffer, 1));
+                bridge.set_delay(0);
+                circuit.add_element(place(bridge, crossed_x - BRIDGE_HALF_WIDTH, lane.y - 20.0));
+                x = crossed_x + BRIDGE_HALF_WIDTH;
+            }
+            segment(&mut circuit, (x, lane.y), (lane.end, lane.y));
+        }
+        Ok(circuit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(texts: &[&str]) -> Vec<(String, Expr)> {
+        texts.iter().map(|text| Expr::parse_assignment(text).unwrap()).collect()
+    }
+
+    // Simulate every combination of the variables and compare the leds with the expressions
+    fn check(outputs: &[(String, Expr)]) {
+        let mut circuit = Circuit::from_expressions(outputs).unwrap();
+        let mut variables: Vec<String> = Vec::new();
+        for name in outputs.iter().flat_map(|(_, expr)| expr.variables()) {
+            if !variables.contains(&name) {
+                variables.push(name);
+            }
+        }
+        let count = variables.len();
+        for combination in 0..1usize << count {
+            let on = |name: &str| {
+                let index = variables.iter().position(|variable| variable == name).unwrap();
+                combination >> (count - 1 - index) & 1 == 1
+            };
+            for name in &variables {
+                let cid = circuit.find_source(name).unwrap();
+                circuit.set_source_value(cid, if on(name) { Signal::On } else { Signal::Off });
+            }
+            circuit.simulate().unwrap();
+
+            let leds = circuit.led_values();
+            for (name, expr) in outputs {
+                let expected = PinValue::Single(if expr.evaluate(&on) { Signal::On } else { Signal::Off });
+                let value = &leds.iter().find(|(led, _)| led == name).unwrap().1;
+                assert_eq!(*value, expected, "{} = {} for combination {:b}", name, expr, combination);
+            }
+        }
+    }
+
+    #[test]
+    fn circuits_compute_their_expression() {
+        check(&parse(&["Y = a & !b | c"]));
+        check(&parse(&["Y = !(a ^ b) & (c | !d)"]));
+    }
+
+    #[test]
+    fn outputs_share_the_gates() {
+        check(&parse(&["S = a ^ b ^ c", "C = a & b | c & (a ^ b)"]));
+    }
+
+    #[test]
+    fn outputs_can_be_sources_or_constants() {
+        check(&parse(&["A = a", "B = a & b", "C = a & b", "K = 1"]));
+    }
+
+    #[test]
+    fn every_source_is_named_after_its_variable() {
+        let circuit = Circuit::from_expression("Y", &Expr::parse("a | b & a").unwrap()).unwrap();
+        let (sources, leds) = circuit.count_sources_and_leds();
+        assert_eq!((sources, leds), (2, 1));
+        assert!(circuit.find_source("a").is_some() && circuit.find_source("b").is_some());
+        assert_eq!(circuit.led_values()[0].0, "Y");
+    }
+
+    #[test]
+    fn complemented_operators_use_a_single_gate() {
+        assert_eq!(Expr::parse("!(a & b)").unwrap().gate_count(), 1);
+        assert_eq!(Expr::parse("!(a | b) ^ !a").unwrap().gate_count(), 3);
+        // The and is built once
+        assert_eq!(Expr::parse("a & b | c ^ (a & b)").unwrap().gate_count(), 3);
+    }
+
+    #[test]
+    fn nothing_to_compute_gives_an_empty_circuit() {
+        let circuit = Circuit::from_expressions(&[]).unwrap();
+        assert!(circuit.components.is_empty());
+    }
+}
@@ -0,0 +1,215 @@
+use std::fmt::Write;
+
+use crate::circuit::{Circuit, LogicElements};
+use crate::error::SimError;
+use crate::structure::*;
+
+// More sources than this make tables too long to be read, and to be simulated
+pub const MAX_TRUTH_TABLE_INPUTS: usize = 12;
+
+/// Outputs of a circuit for every combination of its inputs. The inputs are the sources and the
+/// outputs the leds, both in the order of their ids. The rows count up in binary, the first source
+/// being the most significant bit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TruthTable {
+    pub inputs: Vec<String>,
+    pub outputs: Vec<String>,
+    pub rows: Vec<TruthTableRow>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TruthTableRow {
+    pub inputs: Vec<Signal>,
+    pub outputs: Vec<PinValue>,
+    pub error: Option<SimError>,    // Problem found by the simulation of the row, its outputs are then undefined
+}
+
+// Single character for a signal: 0, 1, X for undefined, Z for high impedance and ! for a conflict
+fn signal_cell(signal: Signal) -> char {
+    match signal {
+        Signal::Off => '0',
+        Signal::On => '1',
+        Signal::Undefined => 'X',
+        Signal::HighZ => 'Z',
+        Signal::Conflict => '!',
+    }
+}
+
+// Buses are written most significant bit first
+pub fn value_cell(value: &PinValue) -> String {
+    (0..value.width()).rev().map(|index| signal_cell(value.bit(index))).collect()
+}
+
+// Quote the CSV fields containing separators or quotes
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+impl TruthTableRow {
+    // Text of every cell of the row, inputs first
+    pub fn cells(&self) -> Vec<String> {
+        self.inputs.iter()
+            .map(|signal| signal_cell(*signal).to_string())
+            .chain(self.outputs.iter().map(value_cell))
+            .collect()
+    }
+}
+
+impl TruthTable {
+    // Names of all the columns, inputs first
+    pub fn header(&self) -> Vec<String> {
+        self.inputs.iter().chain(self.outputs.iter()).cloned().collect()
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::new();
+        let header: Vec<String> = self.header().iter().map(|name| csv_field(name)).collect();
+        let _ = writeln!(csv, "{}", header.join(","));
+        for row in &self.rows {
+            let _ = writeln!(csv, "{}", row.cells().join(","));
+        }
+        csv
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = String::new();
+        let header: Vec<String> = self.header().iter().map(|name| name.replace('|', "\\|")).collect();
+        let _ = writeln!(markdown, "| {} |", header.join(" | "));
+        let _ = writeln!(markdown, "|{}", "---|".repeat(header.len()));
+        for row in &self.rows {
+            let _ = writeln!(markdown, "| {} |", row.cells().join(" | "));
+        }
+        markdown
+    }
+}
+
+impl Circuit {
+    //* Simulate every combination of the values of the sources and record the values of the leds.
+    //* The rows are simulated one after the other on a single copy of the circuit, so the flip flops
+    //* and registers of sequential circuits keep the state left by the previous row. A row whose
+    //* simulation fails, because it doesn't settle for example, gets undefined outputs and its error.
+    pub fn truth_table(&self) -> Result<TruthTable, SimError> {
+        let sources: Vec<(usize, String)> = self.components.iter()
+            .filter_map(|(&cid, component)| match component {
+                LogicElements::Source(source) => Some((cid, source.label())),
+                _ => None,
+            })
+            .collect();
+        if sources.len() > MAX_TRUTH_TABLE_INPUTS {
+            return Err(SimError::TooManySources { found: sources.len(), limit: MAX_TRUTH_TABLE_INPUTS });
+        }
+
+        let count = sources.len();
+        let mut circuit = self.clone();
+        let mut rows = Vec::with_capacity(1 << count);
+        for combination in 0..1usize << count {
+            let inputs: Vec<Signal> = (0..count)
+                .map(|index| if combination >> (count - 1 - index) & 1 == 1 { Signal::On } else { Signal::Off })
+                .collect();
+
+            for ((cid, _), value) in sources.iter().zip(&inputs) {
+                circuit.set_source_value(*cid, *value);
+            }
+            let error = circuit.simulate().err();
+
+            let outputs = circuit.led_values().into_iter()
+                .map(|(_, value)| if error.is_some() { value.to_undefined() } else { value })
+                .collect();
+            rows.push(TruthTableRow { inputs, outputs, error });
+        }
+
+        Ok(TruthTable {
+            inputs: sources.into_iter().map(|(_, name)| name).collect(),
+            outputs: self.led_values().into_iter().map(|(name, _)| name).collect(),
+            rows,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mint::Point2;
+    use crate::circuit::tests::{attach, not_chain, pin_center};
+    use crate::latch_register::{LatchRegister, LatchRegisterType, ENABLE_PIN};
+    use crate::led::Led;
+    use crate::logic_gates::LogicGate;
+    use crate::source::Source;
+    use Signal::{Off, On};
+
+    #[test]
+    fn rows_count_up_in_binary() {
+        let (circuit, _, _, _) = not_chain(0, &[1]);
+        let table = circuit.truth_table().unwrap();
+        assert_eq!(table.inputs.len(), 1);
+        assert_eq!(table.outputs.len(), 1);
+        let rows: Vec<(Vec<Signal>, Vec<PinValue>)> = table.rows.iter()
+            .map(|row| (row.inputs.clone(), row.outputs.clone()))
+            .collect();
+        assert_eq!(rows, vec![
+            (vec![Off], vec![PinValue::Single(On)]),
+            (vec![On], vec![PinValue::Single(Off)]),
+        ]);
+        assert!(table.rows.iter().all(|row| row.error.is_none()));
+    }
+
+    #[test]
+    fn tables_are_written_as_csv_and_markdown() {
+        let table = TruthTable {
+            inputs: vec!["a,b".to_string()],
+            outputs: vec!["y|z".to_string()],
+            rows: vec![TruthTableRow {
+                inputs: vec![On],
+                outputs: vec![PinValue::Multiple(vec![Off, Signal::HighZ, Signal::Undefined])],
+                error: None,
+            }],
+        };
+        assert_eq!(table.to_csv(), "\"a,b\",y|z\n1,XZ0\n");
+        assert_eq!(table.to_markdown(), "| a,b | y\\|z |\n|---|---|\n| 1 | XZ0 |\n");
+    }
+
+    // A D latch whose Q̅ output is wired back to its data input, through a not gate driven by the
+    // source for the enable pin: the latch keeps toggling while the source is 0
+    fn toggling_latch() -> Circuit {
+        let mut circuit = Circuit::new();
+        let latch = circuit.add_element(LogicElements::LatchRegisters(LatchRegister::new(LatchRegisterType::D, 1)));
+        let output = pin_center(&circuit, (latch, 0, 2));
+        let input = pin_center(&circuit, (latch, 1, 1));
+        let corners = [
+            output,
+            Point2 { x: output.x, y: 200.0 },
+            Point2 { x: input.x - 20.0, y: 200.0 },
+            Point2 { x: input.x - 20.0, y: input.y },
+            input,
+        ];
+        for corner in corners.windows(2) {
+            circuit.add_segment(WireSegment::new(corner[0], corner[1]));
+        }
+        let not = circuit.add_element(LogicElements::Gates(LogicGate::new_gate(2, 1, false, 1).unwrap()));
+        attach(&mut circuit, (latch, 2, ENABLE_PIN), (not, 0, 1));
+        let source = circuit.add_element(LogicElements::Source(Source::new(0).unwrap()));
+        attach(&mut circuit, (not, 1, 1), (source, 0, 1));
+        let led = circuit.add_element(LogicElements::Leds(Led::new()));
+        attach(&mut circuit, (latch, 0, 1), (led, 1, 1));
+        circuit
+    }
+
+    #[test]
+    fn rows_that_fail_are_undefined_and_the_table_goes_on() {
+        let table = toggling_latch().truth_table().unwrap();
+        assert_eq!(table.rows.len(), 2);
+
+        let failed = &table.rows[0];
+        assert!(matches!(failed.error, Some(SimError::Oscillation { .. })));
+        assert_eq!(failed.outputs, vec![PinValue::Single(Signal::Undefined)]);
+
+        // The latch keeps the last value it took
+        let held = &table.rows[1];
+        assert_eq!(held.error, None);
+        assert!(matches!(held.outputs[..], [PinValue::Single(On | Off)]));
+    }
+}
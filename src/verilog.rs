@@ -0,0 +1,263 @@
+use std::collections::HashSet;
+use std::fmt::Write;
+
+use crate::circuit::{Circuit, LogicElements};
+use crate::netlist::{Netlist, PinId};
+use crate::structure::*;
+
+// Name of the Verilog primitive implementing a gate
+fn primitive(gate: &LogicGates) -> &'static str {
+    match gate {
+        LogicGates::And => "and",
+        LogicGates::Or => "or",
+        LogicGates::Not => "not",
+        LogicGates::Nand => "nand",
+        LogicGates::Nor => "nor",
+        LogicGates::Xor => "xor",
+        LogicGates::Xnor => "xnor",
+    }
+}
+
+// Range of a vector declaration, empty for single bits
+fn range(bits: usize) -> String {
+    if bits > 1 {
+        format!("[{}:0] ", bits - 1)
+    } else {
+        String::new()
+    }
+}
+
+// Reserved words of Verilog-2005, they can't be used as identifiers
+const KEYWORDS: &[&str] = &[
+    "always", "and", "assign", "automatic", "begin", "buf", "bufif0", "bufif1", "case", "casex", "casez",
+    "cell", "cmos", "config", "deassign", "default", "defparam", "design", "disable", "edge", "else", "end",
+    "endcase", "endconfig", "endfunction", "endgenerate", "endmodule", "endprimitive", "endspecify",
+    "endtable", "endtask", "event", "for", "force", "forever", "fork", "function", "generate", "genvar",
+    "highz0", "highz1", "if", "ifnone", "incdir", "include", "initial", "inout", "input", "instance",
+    "integer", "join", "large", "liblist", "library", "localparam", "macromodule", "medium", "module",
+    "nand", "negedge", "nmos", "nor", "noshowcancelled", "not", "notif0", "notif1", "or", "output",
+    "parameter", "pmos", "posedge", "primitive", "pull0", "pull1", "pulldown", "pullup",
+    "pulsestyle_ondetect", "pulsestyle_onevent", "rcmos", "real", "realtime", "reg", "release", "repeat",
+    "rnmos", "rpmos", "rtran", "rtranif0", "rtranif1", "scalared", "showcancelled", "signed", "small",
+    "specify", "specparam", "strong0", "strong1", "supply0", "supply1", "table", "task", "time", "tran",
+    "tranif0", "tranif1", "tri", "tri0", "tri1", "triand", "trior", "trireg", "unsigned", "use", "uwire",
+    "vectored", "wait", "wand", "weak0", "weak1", "while", "wire", "wor", "xnor", "xor",
+];
+
+// Turn any name into a valid Verilog identifier, keywords get a trailing `_`
+fn identifier(name: &str) -> String {
+    let mut identifier: String = name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' { c } else { '_' })
+        .collect();
+    if !identifier.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        identifier.insert(0, '_');
+    }
+    if KEYWORDS.contains(&identifier.as_str()) {
+        identifier.push('_');
+    }
+    identifier
+}
+
+/// Verilog source of a circuit, with the components that have no Verilog equivalent and were
+/// left out of the module
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerilogModule {
+    pub source: String,
+    pub skipped: Vec<usize>,
+}
+
+impl Circuit {
+    //* Export the circuit as a structural Verilog module: sources become input ports, leds become
+    //* output ports named after them and every gate is instantiated as the matching Verilog
+    //* primitive. The other components are left out and listed in `skipped`
+    pub fn export_verilog(&self, module_name: &str) -> VerilogModule {
+        let netlist = self.build_netlist();
+        // Pins connected to nothing get a wire of their own, a constant can't be driven
+        let net_name = |netlist: &Netlist, (cid, ioc, pid): PinId| match netlist.net_of((cid, ioc, pid)) {
+            Some(net) => format!("n{}", net),
+            None => format!("u{}_{}_{}", cid, ioc, pid),
+        };
+
+        // Exported pins that are connected to nothing, with their width
+        let mut dangling = Vec::new();
+        for (&cid, component) in &self.components {
+            let pins: Vec<(PinId, usize)> = match component {
+                LogicElements::Source(_) | LogicElements::Clock(_) => vec![((cid, 0, 1), 1)],
+                LogicElements::Leds(_) => vec![((cid, 1, 1), 1)],
+                LogicElements::Gates(logic_gate) => logic_gate.input.iter()
+                    .chain(std::iter::once(&logic_gate.output))
+                    .map(|pin| ((cid, pin.ioc, pin.pid), pin.value.width()))
+                    .collect(),
+                _ => Vec::new(),
+            };
+            dangling.extend(pins.into_iter()
+                .filter(|(pin, _)| netlist.net_of(*pin).is_none())
+                .map(|(pin, bits)| (net_name(&netlist, pin), bits)));
+        }
+
+        // Ports can't take the names of the wires, of the gates or of another port, the id of the
+        // component is added to the name when it is already taken
+        let mut taken: HashSet<String> = (0..netlist.nets.len()).map(|net| format!("n{}", net)).collect();
+        taken.extend(dangling.iter().map(|(name, _)| name.clone()));
+        taken.extend(self.components.keys().map(|cid| format!("g{}", cid)));
+        let mut port_name = |name: String, cid: usize| {
+            let mut port = identifier(&name);
+            if taken.contains(&port) {
+                port = format!("{}_{}", port, cid);
+            }
+            taken.insert(port.clone());
+            port
+        };
+
+        // Width of every net, taken from the widest gate pin connected to it
+        let mut net_widths = vec![1; netlist.nets.len()];
+        for component in self.components.values() {
+            if let LogicElements::Gates(logic_gate) = component {
+                for pin in logic_gate.input.iter().chain(std::iter::once(&logic_gate.output)) {
+                    if let Some(net) = netlist.net_of((pin.cid, pin.ioc, pin.pid)) {
+                        net_widths[net] = net_widths[net].max(pin.value.width());
+                    }
+                }
+            }
+        }
+
+        let mut inputs = Vec::new();
+        let mut outputs = Vec::new();
+        let mut skipped = Vec::new();
+        let mut body = String::new();
+        for (&cid, component) in &self.components {
+            match component {
+                LogicElements::Source(source) => {
+                    let port = port_name(source.label(), cid);
+                    let _ = writeln!(body, "    assign {} = {};", net_name(&netlist, (cid, 0, 1)), port);
+                    inputs.push(port);
+                }
+                LogicElements::Clock(_) => {
+                    let port = port_name(format!("clk_{}", cid), cid);
+                    let _ = writeln!(body, "    assign {} = {};", net_name(&netlist, (cid, 0, 1)), port);
+                    inputs.push(port);
+                }
+                LogicElements::Leds(led) => {
+                    let port = port_name(led.label(), cid);
+                    let _ = writeln!(body, "    assign {} = {};", port, net_name(&netlist, (cid, 1, 1)));
+                    outputs.push(port);
+                }
+                LogicElements::Gates(logic_gate) => {
+                    // Bus gates are written as arrays of primitives, one for each bit
+                    let bits = logic_gate.output.value.width();
+                    let mut terminals = vec![net_name(&netlist, (cid, 0, logic_gate.output.pid))];
+                    terminals.extend(logic_gate.input.iter().map(|pin| net_name(&netlist, (cid, 1, pin.pid))));
+                    let _ = writeln!(
+                        body,
+                        "    {} g{} {}({});",
+                        primitive(&logic_gate.r#type),
+                        cid,
+                        range(bits),
+                        terminals.join(", "),
+                    );
+                }
+                _ => {
+                    let _ = writeln!(body, "    // component {} can't be exported to Verilog", cid);
+                    skipped.push(cid);
+                }
+            }
+        }
+
+        let mut verilog = String::new();
+        let ports: Vec<&String> = inputs.iter().chain(outputs.iter()).collect();
+        let _ = writeln!(verilog, "module {} (", identifier(module_name));
+        for (index, port) in ports.iter().enumerate() {
+            let separator = if index + 1 < ports.len() { "," } else { "" };
+            let _ = writeln!(verilog, "    {}{}", port, separator);
+        }
+        let _ = writeln!(verilog, ");");
+        for port in &inputs {
+            let _ = writeln!(verilog, "    input {};", port);
+        }
+        for port in &outputs {
+            let _ = writeln!(verilog, "    output {};", port);
+        }
+        let _ = writeln!(verilog);
+        for (net, bits) in net_widths.iter().enumerate() {
+            let _ = writeln!(verilog, "    wire {}n{};", range(*bits), net);
+        }
+        for (name, bits) in &dangling {
+            let _ = writeln!(verilog, "    wire {}{};", range(*bits), name);
+        }
+        let _ = writeln!(verilog);
+        verilog.push_str(&body);
+        let _ = writeln!(verilog, "endmodule");
+        VerilogModule { source: verilog, skipped }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adder::{Adder, AdderType};
+    use crate::circuit::tests::not_chain;
+    use mint::Point2;
+    use crate::logic_gates::LogicGate;
+    use crate::source::Source;
+
+    #[test]
+    fn identifiers_are_valid_verilog() {
+        assert_eq!(identifier("carry in"), "carry_in");
+        assert_eq!(identifier("2x"), "_2x");
+        assert_eq!(identifier("module"), "module_");
+        assert_eq!(identifier("wire"), "wire_");
+        assert_eq!(identifier("sum"), "sum");
+    }
+
+    #[test]
+    fn ports_are_named_after_sources_and_leds() {
+        let (mut circuit, source, gates, led) = not_chain(0, &[1]);
+        circuit.set_name(source, Some("enable".to_string()));
+        circuit.set_name(led, Some("output".to_string()));
+        let module = circuit.export_verilog("module");
+
+        assert!(module.source.starts_with("module module_ ("));
+        assert!(module.source.contains("    input enable;"));
+        assert!(module.source.contains("    output output_;"));
+        assert!(module.source.contains(&format!("    not g{} (", gates[0])));
+        assert!(module.skipped.is_empty());
+    }
+
+    #[test]
+    fn unnamed_ports_and_clashing_names_get_the_component_id() {
+        let (mut circuit, source, _, led) = not_chain(0, &[1]);
+        circuit.set_name(source, Some("n0".to_string()));
+        let module = circuit.export_verilog("top");
+        assert!(module.source.contains(&format!("    input n0_{};", source)));
+        assert!(module.source.contains(&format!("    output out_{};", led)));
+    }
+
+    #[test]
+    fn components_without_verilog_equivalent_are_reported() {
+        let (mut circuit, _, _, _) = not_chain(0, &[1]);
+        let adder = circuit.add_element(LogicElements::Adders(Adder::new(AdderType::Full, 1)));
+        let module = circuit.export_verilog("top");
+        assert_eq!(module.skipped, vec![adder]);
+        assert!(module.source.contains(&format!("// component {} can't be exported", adder)));
+    }
+
+    #[test]
+    fn unconnected_pins_drive_declared_wires() {
+        let mut circuit = Circuit::new();
+        let source = circuit.add_element(LogicElements::Source(Source::new(0).unwrap()));
+        circuit.move_element(source, Point2 { x: 0.0, y: 300.0 });
+        let gate = circuit.add_element(LogicElements::Gates(LogicGate::new_gate(2, 1, false, 1).unwrap()));
+        let module = circuit.export_verilog("top");
+        assert!(!module.source.contains("1'bz"));
+
+        // The source and the gate output are assigned to wires of the module
+        let declared = |name: &str| module.source.contains(&format!("    wire {};", name));
+        let assign = format!(" = in_{};", source);
+        let line = module.source.lines().find(|line| line.ends_with(&assign)).unwrap();
+        assert!(declared(line.trim_start_matches("    assign ").trim_end_matches(&assign)));
+        let instance = format!("    not g{} (", gate);
+        let line = module.source.lines().find(|line| line.starts_with(&instance)).unwrap();
+        let terminals = line.trim_start_matches(&instance).trim_end_matches(");");
+        assert!(terminals.split(", ").all(declared));
+    }
+}